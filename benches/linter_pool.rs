@@ -0,0 +1,69 @@
+//! Compares per-request linter construction against pooled checkout.
+//!
+//! Run with `cargo bench --bench linter_pool`.
+
+use grammar_api::{curated_dictionary, LinterPool};
+use harper_core::{
+    linting::{LintGroup, Linter},
+    parsers::PlainEnglish,
+    Dialect, Document,
+};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 200;
+
+const SHORT_TEXTS: [&str; 3] = [
+    "This is an test.",
+    "I saw a elephant at the zoo.",
+    "The the cat sat on the mat.",
+];
+
+/// Documents for `rounds` passes over [`SHORT_TEXTS`], each with its own
+/// suffix so no check can be answered from the linter's result cache.
+fn documents(label: &str, rounds: u32) -> Vec<Document> {
+    (0..rounds)
+        .flat_map(|round| {
+            SHORT_TEXTS.iter().map(move |text| {
+                let text = format!("{text} Seen in {label} round {round}.");
+                Document::new_curated(&text, &PlainEnglish)
+            })
+        })
+        .collect()
+}
+
+fn run(label: &str, mut check: impl FnMut(&Document)) {
+    // Warm up on other texts, then measure on texts never linted before,
+    // so both cases pay for linting rather than hitting Harper's cache.
+    for document in &documents("warm-up", 1) {
+        check(document);
+    }
+    let documents = documents(label, ITERATIONS);
+
+    let start = Instant::now();
+    for document in &documents {
+        check(document);
+    }
+    let elapsed = start.elapsed();
+    let per_check = elapsed / (ITERATIONS * SHORT_TEXTS.len() as u32);
+
+    println!("{label:<24} {:>10.3} µs/check", as_micros(per_check));
+}
+
+fn as_micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+fn main() {
+    let dictionary = curated_dictionary();
+
+    run("new_curated per check", |document| {
+        let mut linter = LintGroup::new_curated(dictionary.clone(), Dialect::American);
+        std::hint::black_box(linter.lint(document));
+    });
+
+    let pool = LinterPool::new(dictionary.clone(), &[Dialect::American], 1);
+    run("pooled checkout", |document| {
+        let mut linter = pool.checkout(Dialect::American);
+        std::hint::black_box(linter.lint(document));
+    });
+}
@@ -0,0 +1,44 @@
+//! Compiles the gRPC service definition and records the Harper version.
+
+use std::{error::Error, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed=proto");
+    let descriptors = protox::compile(["grammar.proto"], ["proto"])?;
+    tonic_prost_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .build_transport(false)
+        // Implemented by hand so the text stays out of logs.
+        .skip_debug([".grammar.v1.CheckRequest"])
+        .compile_fds(descriptors)?;
+
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rustc-env=HARPER_VERSION={}", harper_version());
+    Ok(())
+}
+
+/// The harper-core version resolved in `Cargo.lock`, or the version
+/// required in `Cargo.toml` when there is no lockfile.
+fn harper_version() -> String {
+    let locked = fs::read_to_string("Cargo.lock").ok().and_then(|lock| {
+        let mut lines = lock.lines().skip_while(|l| *l != r#"name = "harper-core""#);
+        lines.nth(1).and_then(quoted_version)
+    });
+    locked
+        .or_else(|| {
+            let manifest = fs::read_to_string("Cargo.toml").ok()?;
+            manifest
+                .lines()
+                .find(|l| l.starts_with("harper-core"))
+                .and_then(quoted_version)
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The quoted value after `version = ` in `line`.
+fn quoted_version(line: &str) -> Option<String> {
+    let rest = &line[line.find("version = \"")? + "version = \"".len()..];
+    Some(rest[..rest.find('"')?].to_string())
+}
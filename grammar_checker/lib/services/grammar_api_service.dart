@@ -41,7 +41,7 @@ class GrammarApiService {
         final json = jsonDecode(response.body) as Map<String, dynamic>;
         return Success(CheckResponse.fromJson(json));
       } else if (response.statusCode == 413) {
-        return const Error('Text exceeds maximum size (100KB)');
+        return Error(_payloadTooLargeMessage(response.body));
       } else {
         return Error('API error: ${response.statusCode}');
       }
@@ -52,6 +52,28 @@ class GrammarApiService {
     }
   }
 
+  /// Builds a message from the limit reported in a 413 error body.
+  static String _payloadTooLargeMessage(String body) {
+    try {
+      final json = jsonDecode(body) as Map<String, dynamic>;
+      final limit = json['limit'];
+      if (limit is int) {
+        return 'Text exceeds maximum size (${_formatBytes(limit)})';
+      }
+    } on FormatException {
+      // Fall through to the generic message.
+    }
+    return 'Text exceeds maximum size';
+  }
+
+  static String _formatBytes(int bytes) {
+    const kb = 1024;
+    const mb = kb * 1024;
+    if (bytes >= mb) return '${(bytes / mb).toStringAsFixed(1)}MB';
+    if (bytes >= kb) return '${(bytes / kb).toStringAsFixed(1)}KB';
+    return '${bytes}B';
+  }
+
   /// Disposes of resources used by this service.
   void dispose() {
     _client.close();
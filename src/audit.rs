@@ -0,0 +1,708 @@
+//! Tamper-evident audit log of checks.
+//!
+//! Every check is appended to a local file as one JSON line recording who
+//! made it, through which endpoint, how much text it covered and how it
+//! ended, but never the text itself. Check and feedback requests turned
+//! away before reaching a check, by auth, rate limits or validation, are
+//! recorded too. Each line carries the HMAC-SHA256,
+//! under `audit.chain_key`, of the line before it (`prev`) and of its own
+//! content (`hash`), so editing, removing or reordering records breaks the
+//! chain, which [`verify`] detects, and without the key the chain cannot
+//! be rebuilt. The file is rotated to `<file>.1`, `<file>.2`, … by size,
+//! and the chain carries on across files. When the oldest file is dropped,
+//! its last record is kept as a signed anchor in `<file>.anchor`, so a log
+//! starting partway through the chain is only accepted where rotation
+//! left it. The newest record is reported in the `audit.head_seq` gauge
+//! and logged at startup and on rotation, so records cut from the end can
+//! be noticed too.
+
+use crate::{calendar::timestamp, AuditConfig, ConfigError};
+use metrics::{counter, gauge};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    ffi::OsString,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::SystemTime,
+};
+
+/// `prev` of the first record ever written.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The last field of every line, holding the line's hash.
+const HASH_FIELD: &str = ",\"hash\":\"";
+
+/// Bytes read from the end of a file to find its last record.
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// Suffix of the file holding the rotation anchor.
+const ANCHOR_SUFFIX: &str = "anchor";
+
+/// One check, as recorded in the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditRecord {
+    /// Position in the log, starting at 1.
+    pub seq: u64,
+    /// When the check finished, as an RFC 3339 UTC timestamp.
+    pub time: String,
+    /// Key name or token subject of the caller.
+    pub key: String,
+    /// Endpoint called, as in the `endpoint` metric label.
+    pub endpoint: String,
+    /// Characters of text submitted; 0 for feedback and for requests
+    /// rejected before their text was read.
+    pub characters: u64,
+    /// HMAC-SHA256 of the text under `audit.hash_salt`, if set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_hash: Option<String>,
+    /// `ok`, `partial`, or the error code the check failed with.
+    pub outcome: String,
+    /// Matches returned, for checks that did not fail.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matches: Option<u64>,
+    /// Hash of the previous record, or zeros for the first.
+    pub prev: String,
+}
+
+/// The last record of the newest file dropped by rotation.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Anchor {
+    seq: u64,
+    hash: String,
+    /// HMAC of `seq` and `hash` under the chain key.
+    mac: String,
+}
+
+/// Reasons an audit log could not be verified.
+#[derive(Debug)]
+pub enum AuditError {
+    /// A file could not be read.
+    Io {
+        /// Path of the file.
+        path: PathBuf,
+        /// Underlying I/O error.
+        source: io::Error,
+    },
+    /// A record was changed, removed, reordered or damaged.
+    Tampered {
+        /// File holding the record.
+        path: PathBuf,
+        /// Line of the record, starting at 1.
+        line: usize,
+        /// What is wrong with it.
+        reason: String,
+    },
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "cannot read {}: {source}", path.display()),
+            Self::Tampered { path, line, reason } => {
+                write!(f, "{}:{line}: {reason}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for AuditError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Tampered { .. } => None,
+        }
+    }
+}
+
+/// Summary of an audit log whose chain is intact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditSummary {
+    /// Files checked, rotated ones included.
+    pub files: usize,
+    /// Records checked.
+    pub records: u64,
+    /// Sequence number of the oldest record kept, if any.
+    pub first_seq: Option<u64>,
+    /// Whether the oldest record kept is the first ever written, rather
+    /// than earlier ones having been rotated out.
+    pub from_start: bool,
+    /// Sequence number of the newest record, to compare with the last
+    /// reported head.
+    pub last_seq: Option<u64>,
+    /// Hash of the newest record.
+    pub last_hash: Option<String>,
+}
+
+/// Appends check records to the configured audit log.
+///
+/// Cloning the log is cheap; all clones append to the same file.
+#[derive(Clone)]
+pub struct AuditLog {
+    inner: Option<Arc<AuditInner>>,
+}
+
+struct AuditInner {
+    writer: Mutex<Writer>,
+    salt: Option<hmac::Key>,
+}
+
+struct Writer {
+    path: PathBuf,
+    key: hmac::Key,
+    file: File,
+    size: u64,
+    seq: u64,
+    last_hash: String,
+    max_file_bytes: u64,
+    max_files: usize,
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.inner.as_ref().map(|inner| {
+            inner
+                .writer
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .path
+                .clone()
+        });
+        f.debug_struct("AuditLog")
+            .field("path", &path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    /// An audit log that records nothing.
+    pub const fn disabled() -> Self {
+        Self { inner: None }
+    }
+
+    /// Opens the audit log configured by `config`, continuing the chain of
+    /// records already there.
+    pub fn open(config: &AuditConfig) -> Result<Self, ConfigError> {
+        let Some(path) = &config.file else {
+            return Ok(Self::disabled());
+        };
+        let error = |message: String| ConfigError::AuditLog {
+            path: path.clone(),
+            message,
+        };
+        let key = chain_key(config.chain_key.as_deref().unwrap_or_default());
+
+        let last = match last_record(path, &key).map_err(&error)? {
+            Some(last) => Some(last),
+            None => last_record(&rotated(path, 1), &key).map_err(&error)?,
+        };
+        let (seq, last_hash) = last.unwrap_or_else(|| (0, GENESIS.to_string()));
+        report_head(seq, &last_hash);
+        let file = open_append(path).map_err(|e| error(e.to_string()))?;
+        let size = file.metadata().map_err(|e| error(e.to_string()))?.len();
+
+        Ok(Self {
+            inner: Some(Arc::new(AuditInner {
+                writer: Mutex::new(Writer {
+                    path: path.clone(),
+                    key,
+                    file,
+                    size,
+                    seq,
+                    last_hash,
+                    max_file_bytes: config.max_file_bytes,
+                    max_files: config.max_files,
+                }),
+                salt: config
+                    .hash_salt
+                    .as_ref()
+                    .map(|salt| hmac::Key::new(hmac::HMAC_SHA256, salt.as_bytes())),
+            })),
+        })
+    }
+
+    /// Whether records are being written.
+    pub const fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Starts the record of a request to `endpoint` by `key` that carries
+    /// no checked text, or returns `None` when the log is disabled.
+    pub(crate) fn request_entry(&self, key: &str, endpoint: &str) -> Option<AuditEntry> {
+        let inner = self.inner.as_ref()?;
+        Some(AuditEntry {
+            inner: inner.clone(),
+            key: key.to_string(),
+            endpoint: endpoint.to_string(),
+            characters: 0,
+            text_hash: None,
+        })
+    }
+
+    /// Starts the record of a check of `text` by `key`, or returns `None`
+    /// when the log is disabled. Only the size and salted hash of `text`
+    /// are kept.
+    pub(crate) fn entry(&self, key: &str, endpoint: &str, text: &str) -> Option<AuditEntry> {
+        let inner = self.inner.as_ref()?;
+        Some(AuditEntry {
+            inner: inner.clone(),
+            key: key.to_string(),
+            endpoint: endpoint.to_string(),
+            characters: text.chars().count() as u64,
+            text_hash: inner
+                .salt
+                .as_ref()
+                .map(|salt| hex(hmac::sign(salt, text.as_bytes()).as_ref())),
+        })
+    }
+}
+
+/// What the audit middleware learns about a request from the layers and
+/// handlers inside it, shared through the request's extensions.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AuditScope(Arc<ScopeState>);
+
+#[derive(Debug, Default)]
+struct ScopeState {
+    caller: Mutex<Option<String>>,
+    recorded: AtomicBool,
+}
+
+impl AuditScope {
+    /// Notes the name of the authenticated caller.
+    pub(crate) fn set_caller(&self, name: &str) {
+        *self.0.caller.lock().unwrap_or_else(PoisonError::into_inner) = Some(name.to_string());
+    }
+
+    /// The authenticated caller, if auth got that far.
+    pub(crate) fn caller(&self) -> Option<String> {
+        self.0
+            .caller
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Notes that a check of the request has been recorded.
+    pub(crate) fn mark_recorded(&self) {
+        self.0.recorded.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether a check of the request has been recorded.
+    pub(crate) fn is_recorded(&self) -> bool {
+        self.0.recorded.load(Ordering::Relaxed)
+    }
+}
+
+/// A request in progress, written to the log when it finishes.
+pub(crate) struct AuditEntry {
+    inner: Arc<AuditInner>,
+    key: String,
+    endpoint: String,
+    characters: u64,
+    text_hash: Option<String>,
+}
+
+impl AuditEntry {
+    /// Appends the record of the request, which ended with `outcome` and
+    /// `matches`. The file is written on the blocking pool, off the async
+    /// workers. Failures are logged rather than failing the check.
+    pub(crate) async fn finish(self, outcome: &str, matches: Option<usize>) {
+        let record = AuditRecord {
+            seq: 0,
+            time: timestamp(SystemTime::now()),
+            key: self.key,
+            endpoint: self.endpoint,
+            characters: self.characters,
+            text_hash: self.text_hash,
+            outcome: outcome.to_string(),
+            matches: matches.map(|m| m as u64),
+            prev: String::new(),
+        };
+
+        let inner = self.inner;
+        let written = tokio::task::spawn_blocking(move || {
+            inner
+                .writer
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .append(record)
+        })
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)));
+        match written {
+            Ok(()) => counter!("audit.records").increment(1),
+            Err(e) => {
+                counter!("audit.errors").increment(1);
+                tracing::error!("Failed to write audit record: {}", e);
+            }
+        }
+    }
+}
+
+impl Writer {
+    fn append(&mut self, mut record: AuditRecord) -> io::Result<()> {
+        record.seq = self.seq + 1;
+        record.prev.clone_from(&self.last_hash);
+        let (line, hash) = render(&record, &self.key).map_err(io::Error::other)?;
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_file_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        self.seq = record.seq;
+        self.last_hash = hash;
+        gauge!("audit.head_seq").set(self.seq as f64);
+        Ok(())
+    }
+
+    /// Shifts `<file>.N` to `<file>.N+1`, dropping the oldest beyond
+    /// `max_files` and anchoring the chain at its last record, and starts
+    /// a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let dropped = rotated(&self.path, self.max_files);
+        match last_record(&dropped, &self.key) {
+            Ok(Some((seq, hash))) => write_anchor(&self.path, &self.key, seq, &hash)?,
+            Ok(None) => {}
+            // Verification will report the damage; keep recording.
+            Err(e) => tracing::warn!("Cannot anchor {}: {}", dropped.display(), e),
+        }
+        for index in (1..self.max_files).rev() {
+            match fs::rename(rotated(&self.path, index), rotated(&self.path, index + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        report_head(self.seq, &self.last_hash);
+        Ok(())
+    }
+}
+
+/// Publishes the newest record, so a log cut short can be noticed.
+fn report_head(seq: u64, hash: &str) {
+    gauge!("audit.head_seq").set(seq as f64);
+    tracing::info!(seq, hash, "Audit log head");
+}
+
+fn chain_key(secret: &str) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())
+}
+
+fn sign(key: &hmac::Key, data: &str) -> String {
+    hex(hmac::sign(key, data.as_bytes()).as_ref())
+}
+
+fn anchor_mac(key: &hmac::Key, seq: u64, hash: &str) -> String {
+    sign(key, &format!("{ANCHOR_SUFFIX}:{seq}:{hash}"))
+}
+
+/// Records `seq` and `hash` as the chain's anchor, replacing the file
+/// atomically.
+fn write_anchor(path: &Path, key: &hmac::Key, seq: u64, hash: &str) -> io::Result<()> {
+    let anchor = Anchor {
+        seq,
+        hash: hash.to_string(),
+        mac: anchor_mac(key, seq, hash),
+    };
+    let line = serde_json::to_string(&anchor).map_err(io::Error::other)?;
+    let target = anchor_path(path);
+    let temporary = rotated(&target, 0);
+    fs::write(&temporary, format!("{line}\n"))?;
+    fs::rename(temporary, target)
+}
+
+/// Reads the chain's anchor, checking its signature, or `None` if there
+/// is none.
+fn read_anchor(path: &Path, key: &hmac::Key) -> Result<Option<Anchor>, AuditError> {
+    let path = anchor_path(path);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(AuditError::Io { path, source }),
+    };
+    let tampered = |reason: &str| AuditError::Tampered {
+        path: path.clone(),
+        line: 1,
+        reason: reason.to_string(),
+    };
+    let anchor: Anchor =
+        serde_json::from_str(contents.trim_end()).map_err(|_| tampered("anchor is damaged"))?;
+    if anchor.mac != anchor_mac(key, anchor.seq, &anchor.hash) {
+        return Err(tampered("anchor is not signed by the chain key"));
+    }
+    Ok(Some(anchor))
+}
+
+/// Path of the rotation anchor of `path`, `<path>.anchor`.
+fn anchor_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{ANCHOR_SUFFIX}"));
+    PathBuf::from(name)
+}
+
+/// Checks the hash chain of the audit log at `path` and its rotated files,
+/// oldest first, under `chain_key`.
+pub fn verify(path: &Path, chain_key: &str) -> Result<AuditSummary, AuditError> {
+    let key = self::chain_key(chain_key);
+    let mut files: Vec<PathBuf> = rotated_indexes(path)
+        .into_iter()
+        .rev()
+        .map(|index| rotated(path, index))
+        .collect();
+    if path.exists() || files.is_empty() {
+        files.push(path.to_path_buf());
+    }
+
+    let mut summary = AuditSummary {
+        files: files.len(),
+        records: 0,
+        first_seq: None,
+        from_start: false,
+        last_seq: None,
+        last_hash: None,
+    };
+    let anchor = read_anchor(path, &key)?;
+    let mut last: Option<(u64, String)> = None;
+
+    for file in &files {
+        let contents = fs::read_to_string(file).map_err(|source| AuditError::Io {
+            path: file.clone(),
+            source,
+        })?;
+        let lines: Vec<&str> = contents.split_terminator('\n').collect();
+        for (index, line) in lines.iter().enumerate() {
+            let tampered = |reason: String| AuditError::Tampered {
+                path: file.clone(),
+                line: index + 1,
+                reason,
+            };
+            if index + 1 == lines.len() && !contents.ends_with('\n') {
+                return Err(tampered("record is incomplete".to_string()));
+            }
+
+            let (record, hash) = parse_line(line, &key).map_err(tampered)?;
+            match &last {
+                Some((seq, _)) if record.seq != seq + 1 => {
+                    return Err(tampered(format!(
+                        "record {} follows record {seq}",
+                        record.seq
+                    )));
+                }
+                Some((_, prev)) if record.prev != *prev => {
+                    return Err(tampered(
+                        "record does not follow from the one before it".to_string(),
+                    ));
+                }
+                Some(_) => {}
+                None if record.prev == GENESIS && record.seq != 1 => {
+                    return Err(tampered(format!(
+                        "first record has sequence number {}",
+                        record.seq
+                    )));
+                }
+                None if record.prev != GENESIS
+                    && !anchor
+                        .as_ref()
+                        .is_some_and(|a| a.seq + 1 == record.seq && a.hash == record.prev) =>
+                {
+                    return Err(tampered(format!(
+                        "log starts at record {} without an anchor for the ones before",
+                        record.seq
+                    )));
+                }
+                None => {
+                    summary.first_seq = Some(record.seq);
+                    summary.from_start = record.prev == GENESIS;
+                }
+            }
+            summary.records += 1;
+            last = Some((record.seq, hash));
+        }
+    }
+
+    if let (None, Some(anchor)) = (&last, &anchor) {
+        return Err(AuditError::Tampered {
+            path: path.to_path_buf(),
+            line: 1,
+            reason: format!("records after the anchor at {} are missing", anchor.seq),
+        });
+    }
+    if let Some((seq, hash)) = last {
+        summary.last_seq = Some(seq);
+        summary.last_hash = Some(hash);
+    }
+    Ok(summary)
+}
+
+/// Serializes `record` as a log line ending in its hash under `key`,
+/// returning the line and the hash.
+fn render(record: &AuditRecord, key: &hmac::Key) -> serde_json::Result<(String, String)> {
+    let body = serde_json::to_string(record)?;
+    let hash = sign(key, &body);
+    let open = body.strip_suffix('}').unwrap_or(&body);
+    Ok((format!("{open}{HASH_FIELD}{hash}\"}}\n"), hash))
+}
+
+/// Splits a log line into its record and hash, checking that the hash
+/// matches the rest of the line under `key`.
+fn parse_line(line: &str, key: &hmac::Key) -> Result<(AuditRecord, String), String> {
+    let (open, hash) = line
+        .rsplit_once(HASH_FIELD)
+        .and_then(|(open, rest)| Some((open, rest.strip_suffix("\"}")?)))
+        .ok_or_else(|| "record has no hash".to_string())?;
+    let body = format!("{open}}}");
+    if sign(key, &body) != hash {
+        return Err("hash does not match the record".to_string());
+    }
+    let record = serde_json::from_str(&body).map_err(|e| format!("not an audit record: {e}"))?;
+    Ok((record, hash.to_string()))
+}
+
+/// Sequence number and hash of the last record in `path`, or `None` if the
+/// file is missing or empty.
+fn last_record(path: &Path, key: &hmac::Key) -> Result<Option<(u64, String)>, String> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))
+        .and_then(|_| file.read_to_end(&mut tail))
+        .map_err(|e| e.to_string())?;
+    if tail.is_empty() {
+        return Ok(None);
+    }
+
+    let tail = String::from_utf8_lossy(&tail);
+    let line = tail
+        .strip_suffix('\n')
+        .and_then(|complete| complete.rsplit('\n').next())
+        .ok_or_else(|| "the last record is incomplete; check it with `audit verify`".to_string())?;
+    let (record, hash) = parse_line(line, key)
+        .map_err(|e| format!("the last record is damaged ({e}); check it with `audit verify`"))?;
+    Ok(Some((record.seq, hash)))
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Path of the `index`th rotated file, `<path>.<index>`.
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+/// Indexes of the rotated files of `path` that exist, in ascending order.
+fn rotated_indexes(path: &Path) -> Vec<usize> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut indexes: Vec<usize> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix(name)?
+                .strip_prefix('.')?
+                .parse()
+                .ok()
+        })
+        .collect();
+    indexes.sort_unstable();
+    indexes
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(seq: u64) -> AuditRecord {
+        AuditRecord {
+            seq,
+            time: "2024-03-01T09:30:00.250Z".to_string(),
+            key: "default".to_string(),
+            endpoint: "check".to_string(),
+            characters: 42,
+            text_hash: None,
+            outcome: "ok".to_string(),
+            matches: Some(3),
+            prev: GENESIS.to_string(),
+        }
+    }
+
+    fn key() -> hmac::Key {
+        chain_key("paprika")
+    }
+
+    #[test]
+    fn rendered_lines_parse_back() {
+        let (line, hash) = render(&record(1), &key()).unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(
+            line.ends_with(&format!("\"hash\":\"{hash}\"}}\n")),
+            "{line}"
+        );
+
+        let parsed = parse_line(line.trim_end(), &key()).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(parsed, (record(1), hash));
+    }
+
+    #[test]
+    fn edited_lines_are_rejected() {
+        let (line, _) = render(&record(1), &key()).unwrap_or_else(|e| unreachable!("{e}"));
+        let edited = line
+            .trim_end()
+            .replace("\"characters\":42", "\"characters\":4");
+        assert_eq!(
+            parse_line(&edited, &key()),
+            Err("hash does not match the record".to_string())
+        );
+    }
+
+    #[test]
+    fn lines_hashed_under_another_key_are_rejected() {
+        let (line, _) =
+            render(&record(1), &chain_key("other")).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(
+            parse_line(line.trim_end(), &key()),
+            Err("hash does not match the record".to_string())
+        );
+    }
+
+    #[test]
+    fn rotated_paths_get_a_suffix() {
+        assert_eq!(
+            rotated(Path::new("/var/log/audit.log"), 2),
+            PathBuf::from("/var/log/audit.log.2")
+        );
+    }
+}
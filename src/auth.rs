@@ -0,0 +1,383 @@
+//! API key store and request identities.
+//!
+//! Keys are listed in a TOML key store file. Each key has a name, the
+//! SHA-256 hash of its secret, the scopes it grants and an optional expiry.
+//! Secrets themselves are never stored; presented keys are hashed and
+//! looked up by hash.
+
+use crate::{calendar::days_from_civil, AuthConfig, ConfigError};
+use ring::digest::{digest, SHA256};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use toml::value::{Datetime, Offset};
+
+/// Scope required to call the check endpoint.
+pub const SCOPE_CHECK: &str = "check";
+
+/// Name given to the shared key configured with `auth.api_key`.
+const SHARED_KEY_NAME: &str = "default";
+
+/// The authenticated caller of a request.
+///
+/// Attached to requests as an extension by the auth middleware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Name of the key used, or the token subject.
+    pub name: String,
+    /// Tenant the caller belongs to, if known.
+    pub tenant: Option<String>,
+    /// Scopes granted to the caller.
+    pub scopes: Vec<String>,
+    /// How the caller authenticated.
+    pub credential: Credential,
+}
+
+/// How a caller authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Credential {
+    /// A configured API key.
+    ApiKey,
+    /// A signed JWT bearer token.
+    Jwt,
+    /// A verified TLS client certificate.
+    Certificate,
+}
+
+impl Credential {
+    /// Lowercase name used in metric labels.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::ApiKey => "api_key",
+            Self::Jwt => "jwt",
+            Self::Certificate => "certificate",
+        }
+    }
+}
+
+impl Identity {
+    /// Whether the caller was granted `scope`.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    /// Key the caller's usage is counted under: its name prefixed with
+    /// `key:`, `jwt:` or `cert:` by credential, so a token subject equal
+    /// to a key name does not share that key's quota.
+    pub fn usage_key(&self) -> String {
+        let prefix = match self.credential {
+            Credential::ApiKey => "key",
+            Credential::Jwt => "jwt",
+            Credential::Certificate => "cert",
+        };
+        format!("{prefix}:{}", self.name)
+    }
+
+    /// The caller's name in the `key` metric label: the key name for API
+    /// keys, which are few and configured, and the kind of credential for
+    /// tokens and certificates, which can name any number of callers.
+    pub fn metric_label(&self) -> &str {
+        match self.credential {
+            Credential::ApiKey => &self.name,
+            credential => credential.as_str(),
+        }
+    }
+}
+
+/// Why a presented key was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyError {
+    /// No key matches the presented secret.
+    Unknown,
+    /// The key matched but has expired.
+    Expired,
+}
+
+/// Known API keys, indexed by the hash of their secret.
+#[derive(Default)]
+pub struct KeyStore {
+    keys: HashMap<[u8; 32], StoredKey>,
+}
+
+struct StoredKey {
+    identity: Identity,
+    expires_at: Option<SystemTime>,
+}
+
+impl fmt::Debug for KeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.keys.values().map(|k| &k.identity.name).collect();
+        names.sort();
+        f.debug_struct("KeyStore").field("keys", &names).finish()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyStoreFile {
+    #[serde(default)]
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyEntry {
+    name: String,
+    secret_sha256: String,
+    #[serde(default = "default_scopes")]
+    scopes: Vec<String>,
+    #[serde(default)]
+    expires_at: Option<Datetime>,
+}
+
+fn default_scopes() -> Vec<String> {
+    vec![SCOPE_CHECK.to_string()]
+}
+
+impl KeyStore {
+    /// Builds the key store for `config`, or `None` when authentication is
+    /// disabled because neither a shared key nor a key store is configured.
+    pub fn load(config: &AuthConfig) -> Result<Option<Self>, ConfigError> {
+        let mut store = match &config.key_store {
+            Some(path) => Self::from_file(path)?,
+            None if config.api_key.is_some() => Self::default(),
+            None => return Ok(None),
+        };
+
+        if let Some(secret) = &config.api_key {
+            store.keys.insert(
+                hash_secret(secret),
+                StoredKey {
+                    identity: Identity {
+                        name: SHARED_KEY_NAME.to_string(),
+                        tenant: None,
+                        scopes: default_scopes(),
+                        credential: Credential::ApiKey,
+                    },
+                    expires_at: None,
+                },
+            );
+        }
+
+        Ok(Some(store))
+    }
+
+    /// Reads a key store file.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let error = |message: String| ConfigError::KeyStore {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let contents = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        Self::from_toml(&contents).map_err(error)
+    }
+
+    /// Parses key store TOML.
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        let file: KeyStoreFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+        let mut names = HashSet::new();
+        let mut keys = HashMap::new();
+
+        for entry in file.keys {
+            if entry.name.trim().is_empty() {
+                return Err("key name must not be empty".to_string());
+            }
+            if !names.insert(entry.name.clone()) {
+                return Err(format!("duplicate key name {:?}", entry.name));
+            }
+
+            let hash = decode_hash(&entry.secret_sha256).ok_or_else(|| {
+                format!(
+                    "key {:?}: secret_sha256 must be 64 hex characters",
+                    entry.name
+                )
+            })?;
+            let expires_at = entry
+                .expires_at
+                .map(|datetime| {
+                    to_system_time(&datetime)
+                        .ok_or_else(|| format!("key {:?}: invalid expires_at", entry.name))
+                })
+                .transpose()?;
+
+            let stored = StoredKey {
+                identity: Identity {
+                    name: entry.name,
+                    tenant: None,
+                    scopes: entry.scopes,
+                    credential: Credential::ApiKey,
+                },
+                expires_at,
+            };
+            if keys.insert(hash, stored).is_some() {
+                return Err("two keys share the same secret".to_string());
+            }
+        }
+
+        Ok(Self { keys })
+    }
+
+    /// Number of keys in the store.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the store holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Resolves the identity for a presented `secret` at time `now`.
+    pub fn authenticate(&self, secret: &str, now: SystemTime) -> Result<&Identity, KeyError> {
+        let key = self
+            .keys
+            .get(&hash_secret(secret))
+            .ok_or(KeyError::Unknown)?;
+
+        if key.expires_at.is_some_and(|expiry| now >= expiry) {
+            return Err(KeyError::Expired);
+        }
+
+        Ok(&key.identity)
+    }
+}
+
+/// SHA-256 hash of a key secret.
+pub fn hash_secret(secret: &str) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(digest(&SHA256, secret.as_bytes()).as_ref());
+    hash
+}
+
+fn decode_hash(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim().as_bytes();
+    if hex.len() != 64 {
+        return None;
+    }
+
+    let mut hash = [0; 32];
+    for (byte, pair) in hash.iter_mut().zip(hex.chunks(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// Converts a TOML date or date-time to a point in time. Dates without a
+/// time mean midnight, and times without an offset are taken as UTC.
+fn to_system_time(datetime: &Datetime) -> Option<SystemTime> {
+    let date = datetime.date?;
+    let days = days_from_civil(
+        i64::from(date.year),
+        i64::from(date.month),
+        i64::from(date.day),
+    );
+    let seconds_of_day = datetime.time.map_or(0, |t| {
+        i64::from(t.hour) * 3600 + i64::from(t.minute) * 60 + i64::from(t.second)
+    });
+    let offset_seconds = match datetime.offset {
+        Some(Offset::Custom { minutes }) => i64::from(minutes) * 60,
+        Some(Offset::Z) | None => 0,
+    };
+
+    let seconds = days * 86_400 + seconds_of_day - offset_seconds;
+    u64::try_from(seconds)
+        .ok()
+        .map(|s| UNIX_EPOCH + Duration::from_secs(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET_HASH: &str = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+
+    fn store(contents: &str) -> KeyStore {
+        KeyStore::from_toml(contents).unwrap_or_else(|e| unreachable!("{e}"))
+    }
+
+    #[test]
+    fn resolves_identity_by_secret() {
+        let store = store(&format!(
+            "[[keys]]\nname = \"docs\"\nsecret_sha256 = \"{SECRET_HASH}\"\n"
+        ));
+
+        let identity = store.authenticate("secret", SystemTime::now());
+        assert_eq!(identity.map(|i| i.name.as_str()), Ok("docs"));
+        assert!(identity.is_ok_and(|i| i.has_scope(SCOPE_CHECK)));
+        assert_eq!(
+            store.authenticate("other", SystemTime::now()),
+            Err(KeyError::Unknown)
+        );
+    }
+
+    #[test]
+    fn metric_labels_name_keys_but_not_token_subjects() {
+        let identity = |name: &str, credential| Identity {
+            name: name.to_string(),
+            tenant: None,
+            scopes: Vec::new(),
+            credential,
+        };
+        assert_eq!(identity("docs", Credential::ApiKey).metric_label(), "docs");
+        assert_eq!(identity("user-1", Credential::Jwt).metric_label(), "jwt");
+        assert_eq!(
+            identity("svc-a", Credential::Certificate).metric_label(),
+            "certificate"
+        );
+    }
+
+    #[test]
+    fn usage_keys_are_prefixed_by_credential() {
+        let identity = |credential| Identity {
+            name: "docs".to_string(),
+            tenant: None,
+            scopes: Vec::new(),
+            credential,
+        };
+        assert_eq!(identity(Credential::ApiKey).usage_key(), "key:docs");
+        assert_eq!(identity(Credential::Jwt).usage_key(), "jwt:docs");
+        assert_eq!(identity(Credential::Certificate).usage_key(), "cert:docs");
+    }
+
+    #[test]
+    fn expired_keys_are_rejected() {
+        let store = store(&format!(
+            "[[keys]]\nname = \"old\"\nsecret_sha256 = \"{SECRET_HASH}\"\nexpires_at = 2020-01-01T00:00:00Z\n"
+        ));
+
+        assert_eq!(
+            store.authenticate("secret", SystemTime::now()),
+            Err(KeyError::Expired)
+        );
+        assert!(store.authenticate("secret", UNIX_EPOCH).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_hashes_and_duplicate_names() {
+        assert!(KeyStore::from_toml("[[keys]]\nname = \"a\"\nsecret_sha256 = \"abc\"\n").is_err());
+
+        let duplicate = format!(
+            "[[keys]]\nname = \"a\"\nsecret_sha256 = \"{SECRET_HASH}\"\n\
+             [[keys]]\nname = \"a\"\nsecret_sha256 = \"{}\"\n",
+            "0".repeat(64)
+        );
+        assert!(KeyStore::from_toml(&duplicate).is_err());
+    }
+
+    #[test]
+    fn converts_offset_datetimes() {
+        let datetime: Datetime = "2024-03-01T01:30:00+01:30"
+            .parse()
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(
+            to_system_time(&datetime),
+            Some(UNIX_EPOCH + Duration::from_hours(474_792))
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! UTC calendar arithmetic for expiry dates, quota periods and audit
+//! timestamps.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds in a day.
+pub(crate) const SECS_PER_DAY: u64 = 86_400;
+
+/// Days since 1970-01-01 for a proleptic Gregorian calendar date.
+pub(crate) const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Calendar date `(year, month, day)` for a number of days since
+/// 1970-01-01.
+pub(crate) const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Whole seconds since the Unix epoch, or zero for earlier times.
+pub(crate) fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// RFC 3339 UTC timestamp with milliseconds, such as
+/// `2024-03-01T09:30:00.250Z`; earlier times than the epoch give the epoch.
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let days = i64::try_from(secs / SECS_PER_DAY).unwrap_or(i64::MAX);
+    let (year, month, day) = civil_from_days(days);
+    let of_day = secs % SECS_PER_DAY;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        of_day / 3600,
+        of_day / 60 % 60,
+        of_day % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_dates() {
+        for (year, month, day) in [(1970, 1, 1), (2000, 2, 29), (2024, 3, 1), (2026, 12, 31)] {
+            let days = days_from_civil(year, month, day);
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        assert_eq!(days_from_civil(2024, 3, 1), 19_783);
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        let time = UNIX_EPOCH + std::time::Duration::from_millis(1_709_285_400_250);
+        assert_eq!(timestamp(time), "2024-03-01T09:30:00.250Z");
+    }
+}
@@ -0,0 +1,1934 @@
+//! Typed server configuration.
+//!
+//! Settings are resolved with the following precedence, highest first:
+//! command-line flags, environment variables, the TOML configuration file,
+//! and built-in defaults. The result is validated once at startup so that
+//! invalid values fail fast instead of silently falling back to defaults.
+
+use crate::ListenAddress;
+use axum::http::{HeaderName, HeaderValue};
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, error::Error, fmt, fs, path::PathBuf, str::FromStr};
+use tracing::level_filters::LevelFilter;
+
+/// Default maximum text size in bytes (4MB).
+pub const MAX_TEXT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default maximum number of matches returned per check.
+const DEFAULT_MAX_MATCHES: usize = 10_000;
+
+/// Default maximum number of texts in one gRPC batch.
+const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+/// Default rate limit: requests per second per client.
+const DEFAULT_RATE_LIMIT_PER_SECOND: u64 = 10;
+
+/// Default rate limit burst size.
+const DEFAULT_RATE_LIMIT_BURST: u32 = 30;
+
+/// Default number of lint jobs waiting for a worker before rejecting.
+const DEFAULT_LINT_QUEUE_DEPTH: usize = 64;
+
+/// Default deadline for a single check in milliseconds.
+const DEFAULT_CHECK_TIMEOUT_MS: u64 = 10_000;
+
+/// Default time between failing readiness and closing listeners on
+/// shutdown, in seconds.
+const DEFAULT_DRAIN_DELAY_SECS: u64 = 5;
+
+/// Default allowed clock skew for JWT time claims in seconds.
+const DEFAULT_JWT_LEEWAY_SECS: u64 = 30;
+
+/// Default interval between saves of usage counters in seconds.
+const DEFAULT_QUOTA_FLUSH_INTERVAL_SECS: u64 = 30;
+
+/// Default size at which the audit log is rotated, in bytes (100 MiB).
+const DEFAULT_AUDIT_MAX_FILE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Default number of rotated audit log files kept.
+const DEFAULT_AUDIT_MAX_FILES: usize = 10;
+
+/// Default longest context accepted with match feedback, in characters.
+const DEFAULT_FEEDBACK_MAX_CONTEXT_CHARS: usize = 500;
+
+/// Default buckets of request and queue wait time histograms, in
+/// milliseconds.
+const DEFAULT_DURATION_BUCKETS_MS: [u64; 12] =
+    [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10_000];
+
+/// Default buckets of the submitted text size histogram, in bytes.
+const DEFAULT_TEXT_SIZE_BUCKETS_BYTES: [u64; 9] = [
+    64, 256, 1024, 4096, 16_384, 65_536, 262_144, 1_048_576, 4_194_304,
+];
+
+/// Default interval between metric exports in seconds.
+const DEFAULT_METRICS_EXPORT_INTERVAL_SECS: u64 = 60;
+
+/// Environment variable naming the configuration file.
+const CONFIG_FILE_ENV: &str = "CONFIG_FILE";
+
+/// Complete server configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Listener settings.
+    pub server: ServerConfig,
+    /// Authentication settings.
+    pub auth: AuthConfig,
+    /// CORS settings.
+    pub cors: CorsConfig,
+    /// Rate limiting settings.
+    pub rate_limit: RateLimitConfig,
+    /// Lint execution settings.
+    pub lint: LintConfig,
+    /// Request size limits.
+    pub limits: Limits,
+    /// Spelling dictionary settings.
+    pub dictionary: DictionaryConfig,
+    /// Per-key usage quotas.
+    pub quota: QuotaConfig,
+    /// Logging settings.
+    pub log: LogConfig,
+    /// OpenTelemetry export settings.
+    pub telemetry: TelemetryConfig,
+    /// Privacy mode settings.
+    pub privacy: PrivacyConfig,
+    /// Audit log settings.
+    pub audit: AuditConfig,
+    /// Metric histogram settings.
+    pub metrics: MetricsConfig,
+    /// Match feedback settings.
+    pub feedback: FeedbackConfig,
+}
+
+/// Listener settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Bind address.
+    pub host: String,
+    /// Listen port.
+    pub port: u16,
+    /// Addresses to serve the API on, as `host:port` or `unix:/path`;
+    /// replaces `host` and `port` when set.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub listen: Vec<String>,
+    /// Octal permissions for Unix sockets, such as `"660"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unix_socket_mode: Option<String>,
+    /// Separate listener for health and metrics; they are served on the
+    /// main listener when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin: Option<AdminConfig>,
+    /// gRPC service settings; gRPC is not served when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc: Option<GrpcConfig>,
+    /// TLS termination for the main listener; plain HTTP when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Seconds between failing readiness and closing the listeners on
+    /// shutdown, so load balancers see the probe fail first.
+    pub drain_delay_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            listen: Vec::new(),
+            unix_socket_mode: None,
+            admin: None,
+            grpc: None,
+            tls: None,
+            drain_delay_secs: DEFAULT_DRAIN_DELAY_SECS,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// The addresses the API is served on.
+    ///
+    /// Assumes a validated configuration; unparsable entries are skipped.
+    pub fn listen_addresses(&self) -> Vec<ListenAddress> {
+        if self.listen.is_empty() {
+            return vec![ListenAddress::Tcp(format!("{}:{}", self.host, self.port))];
+        }
+        self.listen.iter().filter_map(|a| a.parse().ok()).collect()
+    }
+
+    /// Permissions for Unix sockets, if configured.
+    pub fn socket_mode(&self) -> Option<u32> {
+        self.unix_socket_mode
+            .as_deref()
+            .and_then(|mode| u32::from_str_radix(mode.trim(), 8).ok())
+    }
+}
+
+/// Admin listener settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bind address.
+    pub host: String,
+    /// Listen port.
+    pub port: u16,
+    /// Unix socket to listen on instead of `host` and `port`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<PathBuf>,
+}
+
+impl AdminConfig {
+    /// The address the admin endpoints are served on.
+    pub fn address(&self) -> ListenAddress {
+        self.unix_socket.as_ref().map_or_else(
+            || ListenAddress::Tcp(format!("{}:{}", self.host, self.port)),
+            |path| ListenAddress::Unix(path.clone()),
+        )
+    }
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 9090,
+            unix_socket: None,
+        }
+    }
+}
+
+/// gRPC service settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrpcConfig {
+    /// Port for a dedicated gRPC listener on `server.host`; gRPC shares the
+    /// main listeners, told apart from HTTP by protocol, when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+/// TLS settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first.
+    pub cert_file: PathBuf,
+    /// PEM private key.
+    pub key_file: PathBuf,
+    /// PEM CA certificates trusted to sign client certificates; enables
+    /// mutual TLS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ca_file: Option<PathBuf>,
+    /// Reject clients without a certificate instead of falling back to
+    /// other authentication.
+    pub client_cert_required: bool,
+    /// Scopes granted to clients identified by certificate.
+    pub client_scopes: Vec<String>,
+    /// Seconds between checks for changed certificate files.
+    pub reload_interval_secs: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            cert_file: PathBuf::new(),
+            key_file: PathBuf::new(),
+            client_ca_file: None,
+            client_cert_required: false,
+            client_scopes: vec![crate::SCOPE_CHECK.to_string()],
+            reload_interval_secs: 10,
+        }
+    }
+}
+
+/// Authentication settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Shared API key; authentication is disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// TOML file of named API keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_store: Option<PathBuf>,
+    /// JWT bearer token settings; JWTs are not accepted when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<JwtConfig>,
+}
+
+/// JWT bearer token settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// Shared secret for HS256 tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// JWKS file with RS256 and ES256 public keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_file: Option<PathBuf>,
+    /// Accepted `aud` values; not checked when empty.
+    pub audience: Vec<String>,
+    /// Accepted `iss` values; not checked when empty.
+    pub issuer: Vec<String>,
+    /// Allowed clock skew for `exp` and `nbf` in seconds.
+    pub leeway_secs: u64,
+    /// Claim holding the caller's scopes.
+    pub scopes_claim: String,
+    /// Claim holding the caller's tenant.
+    pub tenant_claim: String,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            jwks_file: None,
+            audience: Vec::new(),
+            issuer: Vec::new(),
+            leeway_secs: DEFAULT_JWT_LEEWAY_SECS,
+            scopes_claim: "scope".to_string(),
+            tenant_claim: "tenant".to_string(),
+        }
+    }
+}
+
+/// CORS settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Allowed origins; empty or `["*"]` allows any origin.
+    pub origins: Vec<String>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            origins: vec!["*".to_string()],
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Whether any origin is allowed.
+    pub fn is_permissive(&self) -> bool {
+        self.origins.is_empty() || self.origins.iter().any(|o| o == "*")
+    }
+}
+
+/// Rate limiting settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Whether rate limiting is applied.
+    pub enabled: bool,
+    /// Requests per second per client.
+    pub per_second: u64,
+    /// Burst size.
+    pub burst: u32,
+    /// What requests are grouped by for rate limiting.
+    pub key: RateLimitKey,
+    /// Header holding the client IP when `key` is `client_ip_header`.
+    pub client_ip_header: String,
+    /// Proxies, as CIDRs or addresses, trusted to set `client_ip_header`.
+    pub trusted_proxies: Vec<String>,
+    /// Limits by API key name or client address, overriding `per_second`
+    /// and `burst`. Token and certificate callers always get the defaults.
+    pub keys: BTreeMap<String, RateLimitOverride>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            per_second: DEFAULT_RATE_LIMIT_PER_SECOND,
+            burst: DEFAULT_RATE_LIMIT_BURST,
+            key: RateLimitKey::PeerIp,
+            client_ip_header: "x-forwarded-for".to_string(),
+            trusted_proxies: Vec::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+/// What requests are grouped by for rate limiting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// The address of the connecting peer.
+    #[default]
+    PeerIp,
+    /// The client address reported by a trusted proxy.
+    ClientIpHeader,
+    /// The authenticated key name, falling back to the peer address.
+    ApiKey,
+}
+
+impl FromStr for RateLimitKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+/// Rate limit for one key or client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitOverride {
+    /// Requests per second.
+    pub per_second: u64,
+    /// Burst size.
+    pub burst: u32,
+}
+
+/// Lint execution settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Concurrent lint jobs.
+    pub max_concurrency: usize,
+    /// Waiting lint jobs before requests are rejected with 503.
+    pub queue_depth: usize,
+    /// Deadline per check in milliseconds.
+    pub timeout_ms: u64,
+    /// TOML file of house-style rules run alongside Harper's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_rules: Option<PathBuf>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: std::thread::available_parallelism().map_or(1, usize::from),
+            queue_depth: DEFAULT_LINT_QUEUE_DEPTH,
+            timeout_ms: DEFAULT_CHECK_TIMEOUT_MS,
+            custom_rules: None,
+        }
+    }
+}
+
+/// Size limits applied to check requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Maximum text size in bytes.
+    pub max_text_size: usize,
+    /// Maximum request body size in bytes; twice `max_text_size` when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<usize>,
+    /// Maximum number of matches returned per check.
+    pub max_matches: usize,
+    /// Maximum number of texts in one gRPC `CheckBatch` call.
+    pub max_batch_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_text_size: MAX_TEXT_SIZE,
+            max_body_size: None,
+            max_matches: DEFAULT_MAX_MATCHES,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        }
+    }
+}
+
+impl Limits {
+    /// Effective maximum request body size in bytes.
+    pub fn body_limit(&self) -> usize {
+        // Leave room for JSON escaping around the text.
+        self.max_body_size
+            .unwrap_or_else(|| self.max_text_size.saturating_mul(2))
+    }
+}
+
+/// Spelling dictionary settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DictionaryConfig {
+    /// Files of extra words, one per line, accepted as correctly spelled.
+    pub word_lists: Vec<PathBuf>,
+}
+
+/// Per-key usage quota settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaConfig {
+    /// File usage counters are saved to; counters are kept in memory only
+    /// when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_file: Option<PathBuf>,
+    /// How often counters are saved, in seconds.
+    pub flush_interval_secs: u64,
+    /// Limits for keys without their own entry in `keys`.
+    pub default: QuotaLimits,
+    /// Limits by API key name, or by `jwt:<subject>` or `cert:<name>` for
+    /// token and certificate callers, overriding `default`.
+    pub keys: BTreeMap<String, QuotaLimits>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            state_file: None,
+            flush_interval_secs: DEFAULT_QUOTA_FLUSH_INTERVAL_SECS,
+            default: QuotaLimits::default(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl QuotaConfig {
+    /// Effective limits for the key called `name`.
+    pub fn limits_for(&self, name: &str) -> QuotaLimits {
+        self.keys
+            .get(name)
+            .map_or(self.default, |limits| QuotaLimits {
+                daily_requests: limits.daily_requests.or(self.default.daily_requests),
+                daily_characters: limits.daily_characters.or(self.default.daily_characters),
+                monthly_requests: limits.monthly_requests.or(self.default.monthly_requests),
+                monthly_characters: limits
+                    .monthly_characters
+                    .or(self.default.monthly_characters),
+            })
+    }
+}
+
+/// Usage limits per UTC day and calendar month; unset limits are unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaLimits {
+    /// Check requests per day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_requests: Option<u64>,
+    /// Characters checked per day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_characters: Option<u64>,
+    /// Check requests per month.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monthly_requests: Option<u64>,
+    /// Characters checked per month.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monthly_characters: Option<u64>,
+}
+
+/// Logging settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Output format.
+    pub format: LogFormat,
+    /// Level for everything without an entry in `modules`: `off`, `error`,
+    /// `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+    /// Levels by module path, such as `"grammar_api::auth" = "debug"`;
+    /// they apply to the module and everything below it.
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            level: "info".to_string(),
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+/// Log output format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// One line per event, with the fields of every enclosing span.
+    #[default]
+    Full,
+    /// One line per event, with the fields of the enclosing spans but not
+    /// their names.
+    Compact,
+    /// Several indented lines per event, for reading in a terminal.
+    Pretty,
+    /// One JSON object per event, with the fields of the current span
+    /// under `span`.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+/// OpenTelemetry export settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// OTLP/gRPC collector endpoint, such as `http://localhost:4317`;
+    /// nothing is exported when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    /// `service.name` reported with traces and metrics.
+    pub service_name: String,
+    /// Export request traces.
+    pub traces: bool,
+    /// Export metrics.
+    pub metrics: bool,
+    /// How often metrics are exported, in seconds.
+    pub metrics_interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: env!("CARGO_PKG_NAME").to_string(),
+            traces: true,
+            metrics: true,
+            metrics_interval_secs: DEFAULT_METRICS_EXPORT_INTERVAL_SECS,
+        }
+    }
+}
+
+/// Privacy mode settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrivacyConfig {
+    /// Keep submitted text out of logs, traces, error messages, panic
+    /// messages and linter caches. Applies from startup; a reload cannot
+    /// turn it on or off.
+    pub enabled: bool,
+}
+
+/// Audit log settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// File checks are recorded in; no audit log is kept when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Size in bytes at which the file is rotated to `<file>.1`.
+    pub max_file_bytes: u64,
+    /// Rotated files kept; the oldest is deleted beyond this.
+    pub max_files: usize,
+    /// Secret for an HMAC-SHA256 of each checked text, recorded as
+    /// `text_hash`; texts are not hashed when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash_salt: Option<String>,
+    /// Secret for the HMAC-SHA256 chaining records together; required
+    /// with `file`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_key: Option<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            max_file_bytes: DEFAULT_AUDIT_MAX_FILE_BYTES,
+            max_files: DEFAULT_AUDIT_MAX_FILES,
+            hash_salt: None,
+            chain_key: None,
+        }
+    }
+}
+
+/// Metric histogram settings. Buckets are upper bounds in ascending order,
+/// used by `/metrics` and OTLP export alike.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Buckets of `api.request_duration_ms` and `lint.queue_wait_ms`, in
+    /// milliseconds.
+    pub duration_buckets_ms: Vec<u64>,
+    /// Buckets of `api.text_size_bytes`, in bytes.
+    pub text_size_buckets_bytes: Vec<u64>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            duration_buckets_ms: DEFAULT_DURATION_BUCKETS_MS.to_vec(),
+            text_size_buckets_bytes: DEFAULT_TEXT_SIZE_BUCKETS_BYTES.to_vec(),
+        }
+    }
+}
+
+/// Match feedback settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedbackConfig {
+    /// File feedback is appended to; feedback is kept in memory only when
+    /// unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Longest context accepted with a report, in characters.
+    pub max_context_chars: usize,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            max_context_chars: DEFAULT_FEEDBACK_MAX_CONTEXT_CHARS,
+        }
+    }
+}
+
+/// Command-line flags overriding configuration values.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigOverrides {
+    /// Path to a TOML configuration file [env: CONFIG_FILE].
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+    /// Bind address.
+    #[arg(long, global = true)]
+    pub host: Option<String>,
+    /// Listen port.
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+    /// Comma-separated addresses to serve on (`host:port` or `unix:/path`).
+    #[arg(long, value_name = "ADDRESSES", value_delimiter = ',', global = true)]
+    pub listen: Option<Vec<String>>,
+    /// Admin listener port for health and metrics.
+    #[arg(long, global = true)]
+    pub admin_port: Option<u16>,
+    /// Unix socket for the admin listener.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub admin_socket: Option<PathBuf>,
+    /// Dedicated gRPC port; enables gRPC.
+    #[arg(long, global = true)]
+    pub grpc_port: Option<u16>,
+    /// PEM certificate chain; enables TLS.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub tls_cert_file: Option<PathBuf>,
+    /// PEM private key for TLS.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub tls_key_file: Option<PathBuf>,
+    /// TOML file of named API keys.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub api_key_store: Option<PathBuf>,
+    /// Comma-separated allowed CORS origins.
+    #[arg(long, value_delimiter = ',', global = true)]
+    pub cors_origins: Option<Vec<String>>,
+    /// Disable rate limiting.
+    #[arg(long, global = true)]
+    pub disable_rate_limiting: bool,
+    /// Requests per second per client.
+    #[arg(long, global = true)]
+    pub rate_limit_per_second: Option<u64>,
+    /// Rate limit burst size.
+    #[arg(long, global = true)]
+    pub rate_limit_burst: Option<u32>,
+    /// What requests are grouped by for rate limiting.
+    #[arg(long, value_enum, global = true)]
+    pub rate_limit_key: Option<RateLimitKey>,
+    /// Concurrent lint jobs.
+    #[arg(long, global = true)]
+    pub lint_max_concurrency: Option<usize>,
+    /// Waiting lint jobs before 503.
+    #[arg(long, global = true)]
+    pub lint_queue_depth: Option<usize>,
+    /// Deadline per check in milliseconds.
+    #[arg(long, global = true)]
+    pub check_timeout_ms: Option<u64>,
+    /// Maximum text size in bytes.
+    #[arg(long, global = true)]
+    pub max_text_size: Option<usize>,
+    /// Maximum request body size in bytes.
+    #[arg(long, global = true)]
+    pub max_body_size: Option<usize>,
+    /// Maximum matches per response.
+    #[arg(long, global = true)]
+    pub max_matches: Option<usize>,
+    /// Maximum texts per gRPC batch.
+    #[arg(long, global = true)]
+    pub max_batch_size: Option<usize>,
+    /// File usage counters are saved to.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub quota_state_file: Option<PathBuf>,
+    /// Comma-separated custom word list files.
+    #[arg(long, value_name = "PATHS", value_delimiter = ',', global = true)]
+    pub word_lists: Option<Vec<PathBuf>>,
+    /// TOML file of custom house-style rules.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub custom_rules: Option<PathBuf>,
+    /// Log output format.
+    #[arg(long, value_enum, global = true)]
+    pub log_format: Option<LogFormat>,
+    /// Default log level.
+    #[arg(long, value_name = "LEVEL", global = true)]
+    pub log_level: Option<String>,
+    /// OTLP/gRPC collector to export traces and metrics to.
+    #[arg(long, value_name = "URL", global = true)]
+    pub otlp_endpoint: Option<String>,
+    /// Keep submitted text out of logs, traces and error messages.
+    #[arg(long, global = true)]
+    pub privacy_mode: bool,
+    /// File checks are recorded in.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub audit_file: Option<PathBuf>,
+}
+
+/// Errors raised while loading or validating configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read.
+    Read {
+        /// Path of the file.
+        path: PathBuf,
+        /// Underlying I/O error.
+        source: std::io::Error,
+    },
+    /// The configuration file is not valid TOML for [`Config`].
+    Parse {
+        /// Path of the file.
+        path: PathBuf,
+        /// Parser error message.
+        message: String,
+    },
+    /// An environment variable holds a value of the wrong type.
+    InvalidEnv {
+        /// Name of the variable.
+        var: &'static str,
+        /// The rejected value.
+        value: String,
+        /// Why the value was rejected.
+        message: String,
+    },
+    /// A custom word list could not be read.
+    WordList {
+        /// Path of the word list.
+        path: PathBuf,
+        /// Underlying I/O error.
+        source: std::io::Error,
+    },
+    /// The API key store could not be loaded.
+    KeyStore {
+        /// Path of the key store.
+        path: PathBuf,
+        /// Why loading failed.
+        message: String,
+    },
+    /// The JWKS file could not be loaded.
+    Jwks {
+        /// Path of the JWKS file.
+        path: PathBuf,
+        /// Why loading failed.
+        message: String,
+    },
+    /// The saved usage counters could not be read.
+    UsageState {
+        /// Path of the state file.
+        path: PathBuf,
+        /// Why loading failed.
+        message: String,
+    },
+    /// The audit log could not be opened.
+    AuditLog {
+        /// Path of the audit log.
+        path: PathBuf,
+        /// Why opening failed.
+        message: String,
+    },
+    /// Saved feedback could not be read or the file opened.
+    Feedback {
+        /// Path of the feedback file.
+        path: PathBuf,
+        /// Why loading failed.
+        message: String,
+    },
+    /// The custom rules file could not be loaded.
+    CustomRules {
+        /// Path of the rules file.
+        path: PathBuf,
+        /// Why loading failed.
+        message: String,
+    },
+    /// A TLS certificate or key could not be loaded.
+    Tls {
+        /// Path of the certificate or key file.
+        path: PathBuf,
+        /// Why loading failed.
+        message: String,
+    },
+    /// A setting has an invalid value.
+    Invalid {
+        /// Dotted path of the setting.
+        field: &'static str,
+        /// Why the value was rejected.
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, source } => {
+                write!(f, "cannot read config file {}: {source}", path.display())
+            }
+            Self::Parse { path, message } => {
+                write!(f, "invalid config file {}: {message}", path.display())
+            }
+            Self::InvalidEnv {
+                var,
+                value,
+                message,
+            } => write!(f, "invalid value {value:?} for {var}: {message}"),
+            Self::WordList { path, source } => {
+                write!(f, "cannot read word list {}: {source}", path.display())
+            }
+            Self::KeyStore { path, message } => {
+                write!(f, "invalid key store {}: {message}", path.display())
+            }
+            Self::Jwks { path, message } => {
+                write!(f, "invalid JWKS file {}: {message}", path.display())
+            }
+            Self::UsageState { path, message } => {
+                write!(f, "invalid usage state file {}: {message}", path.display())
+            }
+            Self::AuditLog { path, message } => {
+                write!(f, "cannot open audit log {}: {message}", path.display())
+            }
+            Self::Feedback { path, message } => {
+                write!(f, "invalid feedback file {}: {message}", path.display())
+            }
+            Self::CustomRules { path, message } => {
+                write!(f, "invalid custom rules file {}: {message}", path.display())
+            }
+            Self::Tls { path, message } => {
+                write!(f, "invalid TLS file {}: {message}", path.display())
+            }
+            Self::Invalid { field, message } => write!(f, "invalid setting {field}: {message}"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Read { source, .. } | Self::WordList { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads configuration from the process environment, the configuration
+    /// file and `overrides`, then validates it.
+    pub fn load(overrides: &ConfigOverrides) -> Result<Self, ConfigError> {
+        Self::load_from(overrides, |name| env::var(name).ok())
+    }
+
+    /// Like [`Config::load`], reading environment variables through `env`.
+    pub fn load_from(
+        overrides: &ConfigOverrides,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let path = overrides.config.clone().or_else(|| {
+            env(CONFIG_FILE_ENV)
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+        });
+
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        config.apply_env(&env)?;
+        config.apply_overrides(overrides);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads a configuration file, with defaults for any missing settings.
+    pub fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(source) => return Err(ConfigError::Read { path, source }),
+        };
+
+        toml::from_str(&contents).map_err(|e| ConfigError::Parse {
+            path,
+            message: e.message().to_string(),
+        })
+    }
+
+    fn apply_env(&mut self, env: &impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(host) = env("HOST") {
+            self.server.host = host;
+        }
+        parse_env(env, "PORT", &mut self.server.port)?;
+        if let Some(addresses) = env("LISTEN") {
+            self.server.listen = split_list(&addresses);
+        }
+        if let Some(mode) = env("UNIX_SOCKET_MODE").filter(|m| !m.is_empty()) {
+            self.server.unix_socket_mode = Some(mode);
+        }
+        parse_env(env, "DRAIN_DELAY_SECS", &mut self.server.drain_delay_secs)?;
+        if let Some(host) = env("ADMIN_HOST").filter(|h| !h.is_empty()) {
+            self.admin_mut().host = host;
+        }
+        if env("ADMIN_PORT").is_some() {
+            parse_env(env, "ADMIN_PORT", &mut self.admin_mut().port)?;
+        }
+        if let Some(path) = env("ADMIN_SOCKET").filter(|p| !p.is_empty()) {
+            self.admin_mut().unix_socket = Some(PathBuf::from(path));
+        }
+        if bool_env(env, "GRPC_ENABLED")? == Some(true) {
+            self.grpc_mut();
+        }
+        if env("GRPC_PORT").is_some() {
+            let mut port = 0;
+            parse_env(env, "GRPC_PORT", &mut port)?;
+            self.grpc_mut().port = Some(port);
+        }
+        if let Some(path) = env("TLS_CERT_FILE").filter(|p| !p.is_empty()) {
+            self.tls_mut().cert_file = PathBuf::from(path);
+        }
+        if let Some(path) = env("TLS_KEY_FILE").filter(|p| !p.is_empty()) {
+            self.tls_mut().key_file = PathBuf::from(path);
+        }
+        if let Some(path) = env("TLS_CLIENT_CA_FILE").filter(|p| !p.is_empty()) {
+            self.tls_mut().client_ca_file = Some(PathBuf::from(path));
+        }
+
+        if let Some(key) = env("API_KEY").filter(|k| !k.is_empty()) {
+            self.auth.api_key = Some(key);
+        }
+        if let Some(path) = env("API_KEY_STORE").filter(|p| !p.is_empty()) {
+            self.auth.key_store = Some(PathBuf::from(path));
+        }
+        if let Some(secret) = env("JWT_SECRET").filter(|s| !s.is_empty()) {
+            self.jwt_mut().secret = Some(secret);
+        }
+        if let Some(path) = env("JWT_JWKS_FILE").filter(|p| !p.is_empty()) {
+            self.jwt_mut().jwks_file = Some(PathBuf::from(path));
+        }
+        if let Some(audience) = env("JWT_AUDIENCE") {
+            self.jwt_mut().audience = split_list(&audience);
+        }
+        if let Some(issuer) = env("JWT_ISSUER") {
+            self.jwt_mut().issuer = split_list(&issuer);
+        }
+
+        if let Some(origins) = env("CORS_ORIGINS").filter(|o| !o.is_empty()) {
+            self.cors.origins = split_list(&origins);
+        }
+
+        if let Some(disabled) = bool_env(env, "DISABLE_RATE_LIMITING")? {
+            self.rate_limit.enabled = !disabled;
+        }
+        parse_env(
+            env,
+            "RATE_LIMIT_PER_SECOND",
+            &mut self.rate_limit.per_second,
+        )?;
+        parse_env(env, "RATE_LIMIT_BURST", &mut self.rate_limit.burst)?;
+        parse_env(env, "RATE_LIMIT_KEY", &mut self.rate_limit.key)?;
+        if let Some(header) = env("RATE_LIMIT_CLIENT_IP_HEADER").filter(|h| !h.is_empty()) {
+            self.rate_limit.client_ip_header = header;
+        }
+        if let Some(proxies) = env("RATE_LIMIT_TRUSTED_PROXIES") {
+            self.rate_limit.trusted_proxies = split_list(&proxies);
+        }
+
+        parse_env(env, "LINT_MAX_CONCURRENCY", &mut self.lint.max_concurrency)?;
+        parse_env(env, "LINT_QUEUE_DEPTH", &mut self.lint.queue_depth)?;
+        parse_env(env, "CHECK_TIMEOUT_MS", &mut self.lint.timeout_ms)?;
+        if let Some(path) = env("LINT_CUSTOM_RULES").filter(|p| !p.is_empty()) {
+            self.lint.custom_rules = Some(PathBuf::from(path));
+        }
+
+        parse_env(env, "MAX_TEXT_SIZE", &mut self.limits.max_text_size)?;
+        if env("MAX_BODY_SIZE").is_some() {
+            let mut max_body_size = 0;
+            parse_env(env, "MAX_BODY_SIZE", &mut max_body_size)?;
+            self.limits.max_body_size = Some(max_body_size);
+        }
+        parse_env(env, "MAX_MATCHES", &mut self.limits.max_matches)?;
+        parse_env(env, "MAX_BATCH_SIZE", &mut self.limits.max_batch_size)?;
+
+        if let Some(path) = env("QUOTA_STATE_FILE").filter(|p| !p.is_empty()) {
+            self.quota.state_file = Some(PathBuf::from(path));
+        }
+
+        if let Some(paths) = env("DICTIONARY_WORD_LISTS") {
+            self.dictionary.word_lists =
+                split_list(&paths).into_iter().map(PathBuf::from).collect();
+        }
+
+        parse_env(env, "LOG_FORMAT", &mut self.log.format)?;
+        if let Some(level) = env("LOG_LEVEL").filter(|l| !l.is_empty()) {
+            self.log.level = level;
+        }
+        if let Some(modules) = env("LOG_MODULES") {
+            for entry in split_list(&modules) {
+                let Some((module, level)) = entry.split_once('=') else {
+                    return Err(ConfigError::InvalidEnv {
+                        var: "LOG_MODULES",
+                        value: modules,
+                        message: format!("{entry:?} is not module=level"),
+                    });
+                };
+                self.log
+                    .modules
+                    .insert(module.trim().to_string(), level.trim().to_string());
+            }
+        }
+
+        if let Some(endpoint) = env("OTEL_EXPORTER_OTLP_ENDPOINT").filter(|e| !e.is_empty()) {
+            self.telemetry.otlp_endpoint = Some(endpoint);
+        }
+        if let Some(name) = env("OTEL_SERVICE_NAME").filter(|n| !n.is_empty()) {
+            self.telemetry.service_name = name;
+        }
+
+        if let Some(enabled) = bool_env(env, "PRIVACY_MODE")? {
+            self.privacy.enabled = enabled;
+        }
+
+        if let Some(path) = env("AUDIT_FILE").filter(|p| !p.is_empty()) {
+            self.audit.file = Some(PathBuf::from(path));
+        }
+        if let Some(salt) = env("AUDIT_HASH_SALT").filter(|s| !s.is_empty()) {
+            self.audit.hash_salt = Some(salt);
+        }
+        if let Some(key) = env("AUDIT_CHAIN_KEY").filter(|k| !k.is_empty()) {
+            self.audit.chain_key = Some(key);
+        }
+
+        if let Some(path) = env("FEEDBACK_FILE").filter(|p| !p.is_empty()) {
+            self.feedback.file = Some(PathBuf::from(path));
+        }
+        parse_env(
+            env,
+            "FEEDBACK_MAX_CONTEXT_CHARS",
+            &mut self.feedback.max_context_chars,
+        )?;
+
+        parse_list_env(
+            env,
+            "METRICS_DURATION_BUCKETS_MS",
+            &mut self.metrics.duration_buckets_ms,
+        )?;
+        parse_list_env(
+            env,
+            "METRICS_TEXT_SIZE_BUCKETS_BYTES",
+            &mut self.metrics.text_size_buckets_bytes,
+        )?;
+
+        Ok(())
+    }
+
+    fn admin_mut(&mut self) -> &mut AdminConfig {
+        self.server.admin.get_or_insert_with(AdminConfig::default)
+    }
+
+    fn grpc_mut(&mut self) -> &mut GrpcConfig {
+        self.server.grpc.get_or_insert_with(GrpcConfig::default)
+    }
+
+    fn tls_mut(&mut self) -> &mut TlsConfig {
+        self.server.tls.get_or_insert_with(TlsConfig::default)
+    }
+
+    fn jwt_mut(&mut self) -> &mut JwtConfig {
+        self.auth.jwt.get_or_insert_with(JwtConfig::default)
+    }
+
+    fn apply_overrides(&mut self, overrides: &ConfigOverrides) {
+        let o = overrides.clone();
+        set(&mut self.server.host, o.host);
+        set(&mut self.server.port, o.port);
+        set(&mut self.server.listen, o.listen);
+        if let Some(port) = o.admin_port {
+            self.admin_mut().port = port;
+        }
+        if o.admin_socket.is_some() {
+            self.admin_mut().unix_socket = o.admin_socket;
+        }
+        if o.grpc_port.is_some() {
+            self.grpc_mut().port = o.grpc_port;
+        }
+        if let Some(path) = o.tls_cert_file {
+            self.tls_mut().cert_file = path;
+        }
+        if let Some(path) = o.tls_key_file {
+            self.tls_mut().key_file = path;
+        }
+        if o.api_key_store.is_some() {
+            self.auth.key_store = o.api_key_store;
+        }
+        set(&mut self.cors.origins, o.cors_origins);
+        if o.disable_rate_limiting {
+            self.rate_limit.enabled = false;
+        }
+        set(&mut self.rate_limit.per_second, o.rate_limit_per_second);
+        set(&mut self.rate_limit.burst, o.rate_limit_burst);
+        set(&mut self.rate_limit.key, o.rate_limit_key);
+        set(&mut self.lint.max_concurrency, o.lint_max_concurrency);
+        set(&mut self.lint.queue_depth, o.lint_queue_depth);
+        set(&mut self.lint.timeout_ms, o.check_timeout_ms);
+        set(&mut self.limits.max_text_size, o.max_text_size);
+        if o.max_body_size.is_some() {
+            self.limits.max_body_size = o.max_body_size;
+        }
+        set(&mut self.limits.max_matches, o.max_matches);
+        set(&mut self.limits.max_batch_size, o.max_batch_size);
+        if o.quota_state_file.is_some() {
+            self.quota.state_file = o.quota_state_file;
+        }
+        set(&mut self.dictionary.word_lists, o.word_lists);
+        if o.custom_rules.is_some() {
+            self.lint.custom_rules = o.custom_rules;
+        }
+        set(&mut self.log.format, o.log_format);
+        set(&mut self.log.level, o.log_level);
+        if o.otlp_endpoint.is_some() {
+            self.telemetry.otlp_endpoint = o.otlp_endpoint;
+        }
+        if o.privacy_mode {
+            self.privacy.enabled = true;
+        }
+        if o.audit_file.is_some() {
+            self.audit.file = o.audit_file;
+        }
+    }
+
+    /// Checks that every setting is usable.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.server.host.trim().is_empty() {
+            return Err(invalid("server.host", "must not be empty"));
+        }
+        for address in &self.server.listen {
+            match address.parse::<ListenAddress>() {
+                Err(message) => return Err(invalid("server.listen", message)),
+                Ok(ListenAddress::Unix(_)) if !cfg!(unix) => {
+                    return Err(invalid(
+                        "server.listen",
+                        "Unix sockets are only supported on Unix",
+                    ))
+                }
+                Ok(_) => {}
+            }
+        }
+        if let Some(mode) = &self.server.unix_socket_mode {
+            if self.server.socket_mode().is_none_or(|mode| mode > 0o777) {
+                return Err(invalid(
+                    "server.unix_socket_mode",
+                    format!("{mode:?} is not an octal mode such as \"660\""),
+                ));
+            }
+        }
+        if let Some(admin) = &self.server.admin {
+            if admin.unix_socket.is_some() && !cfg!(unix) {
+                return Err(invalid(
+                    "server.admin.unix_socket",
+                    "is only supported on Unix",
+                ));
+            }
+            if admin.unix_socket.is_none() {
+                if admin.host.trim().is_empty() {
+                    return Err(invalid("server.admin.host", "must not be empty"));
+                }
+                if self.server.listen.is_empty() && admin.port == self.server.port {
+                    return Err(invalid("server.admin.port", "must differ from server.port"));
+                }
+            }
+        }
+        if let Some(port) = self.server.grpc.as_ref().and_then(|g| g.port) {
+            if self.server.listen.is_empty() && port == self.server.port {
+                return Err(invalid(
+                    "server.grpc.port",
+                    "must differ from server.port; omit it to share the main port",
+                ));
+            }
+            let admin_port = self
+                .server
+                .admin
+                .as_ref()
+                .filter(|a| a.unix_socket.is_none())
+                .map(|a| a.port);
+            if admin_port == Some(port) {
+                return Err(invalid(
+                    "server.grpc.port",
+                    "must differ from server.admin.port",
+                ));
+            }
+        }
+        if let Some(tls) = &self.server.tls {
+            if tls.cert_file.as_os_str().is_empty() || tls.key_file.as_os_str().is_empty() {
+                return Err(invalid("server.tls", "requires cert_file and key_file"));
+            }
+            if tls.client_cert_required && tls.client_ca_file.is_none() {
+                return Err(invalid(
+                    "server.tls.client_cert_required",
+                    "requires client_ca_file",
+                ));
+            }
+            if tls.reload_interval_secs == 0 {
+                return Err(invalid(
+                    "server.tls.reload_interval_secs",
+                    "must be greater than 0",
+                ));
+            }
+        }
+        if self.auth.api_key.as_deref() == Some("") {
+            return Err(invalid("auth.api_key", "must not be empty when set"));
+        }
+        if let Some(jwt) = &self.auth.jwt {
+            if jwt.secret.is_none() && jwt.jwks_file.is_none() {
+                return Err(invalid("auth.jwt", "requires secret or jwks_file"));
+            }
+            if jwt.secret.as_deref() == Some("") {
+                return Err(invalid("auth.jwt.secret", "must not be empty when set"));
+            }
+        }
+        if !self.cors.is_permissive() {
+            for origin in &self.cors.origins {
+                if HeaderValue::from_str(origin.trim()).is_err() {
+                    return Err(invalid(
+                        "cors.origins",
+                        format!("invalid origin {origin:?}"),
+                    ));
+                }
+            }
+        }
+        if self.rate_limit.per_second == 0 {
+            return Err(invalid("rate_limit.per_second", "must be greater than 0"));
+        }
+        if self.rate_limit.burst == 0 {
+            return Err(invalid("rate_limit.burst", "must be greater than 0"));
+        }
+        if HeaderName::try_from(self.rate_limit.client_ip_header.as_str()).is_err() {
+            return Err(invalid(
+                "rate_limit.client_ip_header",
+                "must be a valid header name",
+            ));
+        }
+        for proxy in &self.rate_limit.trusted_proxies {
+            if crate::rate_limit::parse_cidr(proxy).is_none() {
+                return Err(invalid(
+                    "rate_limit.trusted_proxies",
+                    format!("invalid CIDR {proxy:?}"),
+                ));
+            }
+        }
+        for (key, limits) in &self.rate_limit.keys {
+            if limits.per_second == 0 || limits.burst == 0 {
+                return Err(invalid(
+                    "rate_limit.keys",
+                    format!("{key:?}: per_second and burst must be greater than 0"),
+                ));
+            }
+        }
+        if self.lint.max_concurrency == 0 {
+            return Err(invalid("lint.max_concurrency", "must be greater than 0"));
+        }
+        if self.lint.timeout_ms == 0 {
+            return Err(invalid("lint.timeout_ms", "must be greater than 0"));
+        }
+        if self.limits.max_text_size == 0 {
+            return Err(invalid("limits.max_text_size", "must be greater than 0"));
+        }
+        if self.limits.body_limit() < self.limits.max_text_size {
+            return Err(invalid(
+                "limits.max_body_size",
+                "must be at least limits.max_text_size",
+            ));
+        }
+        if self.limits.max_matches == 0 {
+            return Err(invalid("limits.max_matches", "must be greater than 0"));
+        }
+        if self.limits.max_batch_size == 0 {
+            return Err(invalid("limits.max_batch_size", "must be greater than 0"));
+        }
+        if self.quota.flush_interval_secs == 0 {
+            return Err(invalid(
+                "quota.flush_interval_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.log.level.parse::<LevelFilter>().is_err() {
+            return Err(invalid(
+                "log.level",
+                format!("{:?} is not a log level", self.log.level),
+            ));
+        }
+        for (module, level) in &self.log.modules {
+            if module.trim().is_empty() {
+                return Err(invalid("log.modules", "module names must not be empty"));
+            }
+            if level.parse::<LevelFilter>().is_err() {
+                return Err(invalid(
+                    "log.modules",
+                    format!("{module}: {level:?} is not a log level"),
+                ));
+            }
+        }
+        if let Some(endpoint) = &self.telemetry.otlp_endpoint {
+            let valid = endpoint
+                .parse::<http::Uri>()
+                .is_ok_and(|uri| matches!(uri.scheme_str(), Some("http" | "https")));
+            if !valid {
+                return Err(invalid(
+                    "telemetry.otlp_endpoint",
+                    format!("{endpoint:?} is not an http or https URL"),
+                ));
+            }
+        }
+        if self.telemetry.service_name.trim().is_empty() {
+            return Err(invalid("telemetry.service_name", "must not be empty"));
+        }
+        if self.telemetry.metrics_interval_secs == 0 {
+            return Err(invalid(
+                "telemetry.metrics_interval_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.audit.max_file_bytes == 0 {
+            return Err(invalid("audit.max_file_bytes", "must be greater than 0"));
+        }
+        if self.audit.max_files == 0 {
+            return Err(invalid("audit.max_files", "must be at least 1"));
+        }
+        if self.audit.hash_salt.as_deref().is_some_and(str::is_empty) {
+            return Err(invalid("audit.hash_salt", "must not be empty"));
+        }
+        if self.audit.file.is_some() && self.audit.chain_key.as_deref().is_none_or(str::is_empty) {
+            return Err(invalid("audit.chain_key", "must be set when audit.file is"));
+        }
+        if self.feedback.max_context_chars == 0 {
+            return Err(invalid("feedback.max_context_chars", "must be at least 1"));
+        }
+        validate_buckets(
+            "metrics.duration_buckets_ms",
+            &self.metrics.duration_buckets_ms,
+        )?;
+        validate_buckets(
+            "metrics.text_size_buckets_bytes",
+            &self.metrics.text_size_buckets_bytes,
+        )?;
+        Ok(())
+    }
+
+    /// Renders the configuration as TOML with secrets redacted.
+    pub fn to_redacted_toml(&self) -> Result<String, toml::ser::Error> {
+        let mut redacted = self.clone();
+        if redacted.auth.api_key.is_some() {
+            redacted.auth.api_key = Some("<redacted>".to_string());
+        }
+        if let Some(secret) = redacted.auth.jwt.as_mut().and_then(|j| j.secret.as_mut()) {
+            *secret = "<redacted>".to_string();
+        }
+        if redacted.audit.hash_salt.is_some() {
+            redacted.audit.hash_salt = Some("<redacted>".to_string());
+        }
+        if redacted.audit.chain_key.is_some() {
+            redacted.audit.chain_key = Some("<redacted>".to_string());
+        }
+        toml::to_string_pretty(&redacted)
+    }
+}
+
+fn parse_env<T: FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    var: &'static str,
+    target: &mut T,
+) -> Result<(), ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    let Some(value) = env(var) else {
+        return Ok(());
+    };
+
+    match value.trim().parse() {
+        Ok(parsed) => {
+            *target = parsed;
+            Ok(())
+        }
+        Err(e) => Err(ConfigError::InvalidEnv {
+            var,
+            message: e.to_string(),
+            value,
+        }),
+    }
+}
+
+/// Reads a boolean environment variable: `true`, `false`, `1` or `0`, in
+/// any case. Anything else is an error rather than a silent `false`.
+fn bool_env(
+    env: &impl Fn(&str) -> Option<String>,
+    var: &'static str,
+) -> Result<Option<bool>, ConfigError> {
+    let Some(value) = env(var) else {
+        return Ok(None);
+    };
+
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" => Ok(Some(true)),
+        "false" | "0" => Ok(Some(false)),
+        _ => Err(ConfigError::InvalidEnv {
+            var,
+            value,
+            message: "expected true, false, 1 or 0".to_string(),
+        }),
+    }
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+/// Like [`parse_env`], for a comma-separated list.
+fn parse_list_env<T: FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    var: &'static str,
+    target: &mut Vec<T>,
+) -> Result<(), ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    let Some(value) = env(var) else {
+        return Ok(());
+    };
+
+    *target = split_list(&value)
+        .iter()
+        .map(|item| item.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|e: T::Err| ConfigError::InvalidEnv {
+            var,
+            value: value.clone(),
+            message: e.to_string(),
+        })?;
+    Ok(())
+}
+
+fn validate_buckets(field: &'static str, buckets: &[u64]) -> Result<(), ConfigError> {
+    if buckets.is_empty() {
+        return Err(invalid(field, "must not be empty"));
+    }
+    if buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(invalid(field, "must be in strictly ascending order"));
+    }
+    Ok(())
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn invalid(field: &'static str, message: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid {
+        field,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(env: &[(&str, &str)], overrides: &ConfigOverrides) -> Result<Config, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        Config::load_from(overrides, |name| env.get(name).cloned())
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn flags_override_env() {
+        let overrides = ConfigOverrides {
+            port: Some(9000),
+            ..ConfigOverrides::default()
+        };
+        let config = load(&[("PORT", "7000"), ("HOST", "127.0.0.1")], &overrides);
+
+        let config = config.unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.host, "127.0.0.1");
+    }
+
+    #[test]
+    fn drain_delay_env_sets_delay() {
+        let config = load(&[], &ConfigOverrides::default()).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(config.server.drain_delay_secs, DEFAULT_DRAIN_DELAY_SECS);
+
+        let config = load(&[("DRAIN_DELAY_SECS", "0")], &ConfigOverrides::default())
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(config.server.drain_delay_secs, 0);
+    }
+
+    #[test]
+    fn admin_env_enables_admin_listener() {
+        let config = load(&[("ADMIN_PORT", "9100")], &ConfigOverrides::default())
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        let admin = config.server.admin.unwrap_or_default();
+        assert_eq!((admin.host.as_str(), admin.port), ("127.0.0.1", 9100));
+
+        let clash = load(&[("ADMIN_PORT", "8080")], &ConfigOverrides::default());
+        assert!(matches!(
+            clash,
+            Err(ConfigError::Invalid {
+                field: "server.admin.port",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn listen_env_sets_several_addresses() {
+        let config = load(
+            &[
+                ("LISTEN", "0.0.0.0:8080, [::]:8080, unix:/tmp/api.sock"),
+                ("UNIX_SOCKET_MODE", "660"),
+            ],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(
+            config.server.listen_addresses(),
+            vec![
+                ListenAddress::Tcp("0.0.0.0:8080".to_string()),
+                ListenAddress::Tcp("[::]:8080".to_string()),
+                ListenAddress::Unix(PathBuf::from("/tmp/api.sock")),
+            ]
+        );
+        assert_eq!(config.server.socket_mode(), Some(0o660));
+
+        for (var, value, field) in [
+            ("LISTEN", "8080", "server.listen"),
+            ("UNIX_SOCKET_MODE", "rw", "server.unix_socket_mode"),
+            ("UNIX_SOCKET_MODE", "1777", "server.unix_socket_mode"),
+        ] {
+            let err = load(&[(var, value)], &ConfigOverrides::default());
+            assert!(
+                matches!(err, Err(ConfigError::Invalid { field: f, .. }) if f == field),
+                "{var}={value}"
+            );
+        }
+    }
+
+    #[test]
+    fn grpc_env_enables_grpc() {
+        let shared = load(&[("GRPC_ENABLED", "true")], &ConfigOverrides::default())
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(shared.server.grpc, Some(GrpcConfig { port: None }));
+
+        let dedicated = load(&[("GRPC_PORT", "50051")], &ConfigOverrides::default())
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(
+            dedicated.server.grpc,
+            Some(GrpcConfig { port: Some(50051) })
+        );
+
+        let clash = load(&[("GRPC_PORT", "8080")], &ConfigOverrides::default());
+        assert!(matches!(
+            clash,
+            Err(ConfigError::Invalid {
+                field: "server.grpc.port",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn boolean_env_vars_are_parsed_strictly() {
+        let config = load(
+            &[("GRPC_ENABLED", "TRUE"), ("DISABLE_RATE_LIMITING", "0")],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(config.server.grpc.is_some());
+        assert!(config.rate_limit.enabled);
+
+        let config = load(
+            &[("DISABLE_RATE_LIMITING", "True")],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(!config.rate_limit.enabled);
+
+        for var in ["GRPC_ENABLED", "DISABLE_RATE_LIMITING"] {
+            for value in ["yes", "on", ""] {
+                let err = load(&[(var, value)], &ConfigOverrides::default());
+                assert!(
+                    matches!(err, Err(ConfigError::InvalidEnv { var: v, .. }) if v == var),
+                    "{var}={value}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn log_env_sets_format_and_levels() {
+        let config = load(
+            &[
+                ("LOG_FORMAT", "json"),
+                ("LOG_LEVEL", "warn"),
+                ("LOG_MODULES", "grammar_api::auth=debug, tower_http=error"),
+            ],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert_eq!(config.log.level, "warn");
+        assert_eq!(
+            config
+                .log
+                .modules
+                .get("grammar_api::auth")
+                .map(String::as_str),
+            Some("debug")
+        );
+        assert_eq!(
+            config.log.modules.get("tower_http").map(String::as_str),
+            Some("error")
+        );
+
+        let invalid = load(&[("LOG_LEVEL", "loud")], &ConfigOverrides::default());
+        assert!(matches!(
+            invalid,
+            Err(ConfigError::Invalid {
+                field: "log.level",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn otel_env_sets_telemetry() {
+        let config = load(
+            &[
+                ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4317"),
+                ("OTEL_SERVICE_NAME", "grammar-eu"),
+            ],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(
+            config.telemetry.otlp_endpoint.as_deref(),
+            Some("http://collector:4317")
+        );
+        assert_eq!(config.telemetry.service_name, "grammar-eu");
+
+        let invalid = load(
+            &[("OTEL_EXPORTER_OTLP_ENDPOINT", "collector:4317")],
+            &ConfigOverrides::default(),
+        );
+        assert!(matches!(
+            invalid,
+            Err(ConfigError::Invalid {
+                field: "telemetry.otlp_endpoint",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn privacy_mode_from_env_or_flag() {
+        let config = load(&[], &ConfigOverrides::default()).unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(!config.privacy.enabled);
+
+        let config = load(&[("PRIVACY_MODE", "1")], &ConfigOverrides::default())
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(config.privacy.enabled);
+
+        let overrides = ConfigOverrides {
+            privacy_mode: true,
+            ..ConfigOverrides::default()
+        };
+        let config =
+            load(&[("PRIVACY_MODE", "false")], &overrides).unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(config.privacy.enabled);
+
+        let config = load(&[("PRIVACY_MODE", "TRUE")], &ConfigOverrides::default())
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(config.privacy.enabled);
+
+        for value in ["yes", "on", "enabled"] {
+            let err = load(&[("PRIVACY_MODE", value)], &ConfigOverrides::default());
+            assert!(
+                matches!(
+                    err,
+                    Err(ConfigError::InvalidEnv {
+                        var: "PRIVACY_MODE",
+                        ..
+                    })
+                ),
+                "PRIVACY_MODE={value}"
+            );
+        }
+    }
+
+    #[test]
+    fn audit_env_sets_file_and_secrets() {
+        let config = load(
+            &[
+                ("AUDIT_FILE", "/var/log/grammar-api/audit.log"),
+                ("AUDIT_HASH_SALT", "pepper"),
+                ("AUDIT_CHAIN_KEY", "paprika"),
+            ],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(
+            config.audit.file,
+            Some(PathBuf::from("/var/log/grammar-api/audit.log"))
+        );
+        assert_eq!(config.audit.hash_salt.as_deref(), Some("pepper"));
+        assert_eq!(config.audit.chain_key.as_deref(), Some("paprika"));
+
+        let rendered = config
+            .to_redacted_toml()
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(!rendered.contains("pepper"), "{rendered}");
+        assert!(!rendered.contains("paprika"), "{rendered}");
+    }
+
+    #[test]
+    fn feedback_env_sets_file_and_context_limit() {
+        let config = load(
+            &[
+                ("FEEDBACK_FILE", "/var/lib/grammar-api/feedback.jsonl"),
+                ("FEEDBACK_MAX_CONTEXT_CHARS", "200"),
+            ],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(
+            config.feedback.file,
+            Some(PathBuf::from("/var/lib/grammar-api/feedback.jsonl"))
+        );
+        assert_eq!(config.feedback.max_context_chars, 200);
+
+        let zero = load(
+            &[("FEEDBACK_MAX_CONTEXT_CHARS", "0")],
+            &ConfigOverrides::default(),
+        );
+        assert!(matches!(
+            zero,
+            Err(ConfigError::Invalid {
+                field: "feedback.max_context_chars",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn metrics_env_sets_buckets() {
+        let config = load(
+            &[
+                ("METRICS_DURATION_BUCKETS_MS", "10, 100, 1000"),
+                ("METRICS_TEXT_SIZE_BUCKETS_BYTES", "1024"),
+            ],
+            &ConfigOverrides::default(),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(config.metrics.duration_buckets_ms, [10, 100, 1000]);
+        assert_eq!(config.metrics.text_size_buckets_bytes, [1024]);
+
+        let unordered = load(
+            &[("METRICS_DURATION_BUCKETS_MS", "100,10")],
+            &ConfigOverrides::default(),
+        );
+        assert!(matches!(
+            unordered,
+            Err(ConfigError::Invalid {
+                field: "metrics.duration_buckets_ms",
+                ..
+            })
+        ));
+
+        let invalid = load(
+            &[("METRICS_TEXT_SIZE_BUCKETS_BYTES", "1k")],
+            &ConfigOverrides::default(),
+        );
+        assert!(matches!(
+            invalid,
+            Err(ConfigError::InvalidEnv {
+                var: "METRICS_TEXT_SIZE_BUCKETS_BYTES",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn invalid_env_value_fails() {
+        let err = load(
+            &[("RATE_LIMIT_PER_SECOND", "fast")],
+            &ConfigOverrides::default(),
+        );
+        assert!(matches!(
+            err,
+            Err(ConfigError::InvalidEnv {
+                var: "RATE_LIMIT_PER_SECOND",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn zero_rate_fails_validation() {
+        let err = load(
+            &[("RATE_LIMIT_PER_SECOND", "0")],
+            &ConfigOverrides::default(),
+        );
+        assert!(matches!(
+            err,
+            Err(ConfigError::Invalid {
+                field: "rate_limit.per_second",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn file_settings_fill_defaults() {
+        let config: Config = toml::from_str("[limits]\nmax_text_size = 1000\n")
+            .unwrap_or_else(|e| unreachable!("{e}"));
+
+        assert_eq!(config.limits.max_text_size, 1000);
+        assert_eq!(config.limits.body_limit(), 2000);
+        assert_eq!(config.server, ServerConfig::default());
+    }
+
+    #[test]
+    fn unknown_file_settings_are_rejected() {
+        assert!(toml::from_str::<Config>("[server]\nprot = 80\n").is_err());
+    }
+
+    #[test]
+    fn redacted_toml_hides_api_key() {
+        let mut config = Config::default();
+        config.auth.api_key = Some("secret".to_string());
+
+        let rendered = config
+            .to_redacted_toml()
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        assert!(!rendered.contains("secret"));
+        assert!(rendered.contains("<redacted>"));
+    }
+}
@@ -0,0 +1,385 @@
+//! House-style rules defined by operators.
+//!
+//! Rules are read from the TOML file named by `lint.custom_rules` and run
+//! alongside Harper's curated rules. Each rule matches a literal phrase, a
+//! sequence of words or a regular expression, and reports its own message,
+//! replacement and severity under the rule id `custom:<name>`.
+
+use crate::{ConfigError, LintConfig};
+use harper_core::Span;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, fs, path::Path, sync::Arc};
+
+/// Prefix of the rule ids of custom rules.
+pub const CUSTOM_RULE_PREFIX: &str = "custom:";
+
+/// How serious a custom rule's matches are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Must be fixed.
+    Error,
+    /// Should be fixed.
+    #[default]
+    Warning,
+    /// Worth a look.
+    Info,
+}
+
+impl Severity {
+    /// Lowercase name used in responses.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+        }
+    }
+}
+
+/// The custom rules in effect, compiled and ready to run.
+///
+/// Cloning is cheap; all clones share the same compiled rules.
+#[derive(Clone, Default)]
+pub struct CustomRules {
+    rules: Arc<[CustomRule]>,
+}
+
+struct CustomRule {
+    id: String,
+    regex: Regex,
+    message: String,
+    replacement: Option<String>,
+    /// Whether `replacement` may refer to capture groups of `regex`.
+    expand: bool,
+    severity: Severity,
+}
+
+impl fmt::Debug for CustomRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<_> = self.rules.iter().map(|rule| &rule.id).collect();
+        f.debug_struct("CustomRules").field("rules", &ids).finish()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    name: String,
+    #[serde(default)]
+    phrase: Option<String>,
+    #[serde(default)]
+    words: Option<Vec<String>>,
+    #[serde(default)]
+    pattern: Option<String>,
+    message: String,
+    #[serde(default)]
+    replacement: Option<String>,
+    #[serde(default)]
+    severity: Severity,
+    #[serde(default)]
+    case_sensitive: bool,
+}
+
+/// A match of a custom rule, with its span in characters.
+pub(crate) struct CustomLint<'a> {
+    pub(crate) span: Span,
+    pub(crate) rule_id: &'a str,
+    pub(crate) message: &'a str,
+    pub(crate) replacement: Option<String>,
+    pub(crate) severity: Severity,
+}
+
+impl CustomRules {
+    /// Loads the rules file configured in `config`, or no rules when none
+    /// is configured.
+    pub fn load(config: &LintConfig) -> Result<Self, ConfigError> {
+        config
+            .custom_rules
+            .as_deref()
+            .map_or_else(|| Ok(Self::default()), Self::from_file)
+    }
+
+    /// Reads a rules file.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let error = |message: String| ConfigError::CustomRules {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let contents = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        Self::from_toml(&contents).map_err(error)
+    }
+
+    /// Parses rules file TOML.
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        let file: RulesFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+        let mut names = HashSet::new();
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|entry| {
+                if entry.name.is_empty() || entry.name.contains(char::is_whitespace) {
+                    return Err(format!(
+                        "rule name {:?} must be non-empty without spaces",
+                        entry.name
+                    ));
+                }
+                if !names.insert(entry.name.clone()) {
+                    return Err(format!("duplicate rule name {:?}", entry.name));
+                }
+                compile(entry)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Number of rules.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Ids and messages of the rules, in file order.
+    pub(crate) fn descriptions(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.rules
+            .iter()
+            .map(|rule| (rule.id.as_str(), rule.message.as_str()))
+    }
+
+    /// Runs every rule over `text`, with spans in characters of `text`.
+    /// Matches that already read as the replacement are skipped.
+    pub(crate) fn lint<'a>(&'a self, text: &str) -> Vec<CustomLint<'a>> {
+        let mut lints = Vec::new();
+        for rule in self.rules.iter() {
+            // Matches come in order, so characters are counted from the
+            // previous match rather than from the start.
+            let (mut byte, mut char) = (0, 0);
+            for captures in rule.regex.captures_iter(text) {
+                let Some(found) = captures.get(0).filter(|m| !m.is_empty()) else {
+                    continue;
+                };
+                let replacement = rule.replacement.as_ref().map(|replacement| {
+                    if rule.expand {
+                        let mut expanded = String::new();
+                        captures.expand(replacement, &mut expanded);
+                        expanded
+                    } else {
+                        replacement.clone()
+                    }
+                });
+                if replacement.as_deref() == Some(found.as_str()) {
+                    continue;
+                }
+
+                char += text[byte..found.start()].chars().count();
+                byte = found.start();
+                let start = char;
+                let length = found.as_str().chars().count();
+                lints.push(CustomLint {
+                    span: Span::new(start, start + length),
+                    rule_id: &rule.id,
+                    message: &rule.message,
+                    replacement,
+                    severity: rule.severity,
+                });
+            }
+        }
+        lints
+    }
+}
+
+/// Compiles the pattern of `entry`, which must have exactly one of
+/// `phrase`, `words` and `pattern`.
+fn compile(entry: RuleEntry) -> Result<CustomRule, String> {
+    let name = &entry.name;
+    let (source, expand) = match (&entry.phrase, &entry.words, &entry.pattern) {
+        (Some(phrase), None, None) if !phrase.trim().is_empty() => (literal(phrase), false),
+        (None, Some(words), None) if !words.is_empty() => {
+            let words = words.iter().map(|w| w.trim()).collect::<Vec<_>>();
+            if words
+                .iter()
+                .any(|w| w.is_empty() || w.contains(char::is_whitespace))
+            {
+                return Err(format!(
+                    "rule {name:?}: words must be single, non-empty words"
+                ));
+            }
+            (literal(&words.join(" ")), false)
+        }
+        (None, None, Some(pattern)) => (pattern.clone(), true),
+        (None, None, None) => {
+            return Err(format!(
+                "rule {name:?} needs one of phrase, words or pattern"
+            ))
+        }
+        (Some(_), None, None) | (None, Some(_), None) => {
+            return Err(format!("rule {name:?} has an empty phrase or word list"))
+        }
+        _ => {
+            return Err(format!(
+                "rule {name:?} must have only one of phrase, words or pattern"
+            ))
+        }
+    };
+
+    let regex = RegexBuilder::new(&source)
+        .case_insensitive(!entry.case_sensitive)
+        .build()
+        .map_err(|e| format!("rule {name:?}: invalid pattern: {e}"))?;
+    if entry.message.trim().is_empty() {
+        return Err(format!("rule {name:?}: message must not be empty"));
+    }
+
+    Ok(CustomRule {
+        id: format!("{CUSTOM_RULE_PREFIX}{name}"),
+        regex,
+        message: entry.message,
+        replacement: entry.replacement,
+        expand,
+        severity: entry.severity,
+    })
+}
+
+/// A pattern matching `phrase` as written, except that any run of
+/// whitespace matches any other, and only at word boundaries.
+fn literal(phrase: &str) -> String {
+    let body = phrase
+        .split_whitespace()
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"\s+");
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let start = if is_word(phrase.trim().chars().next()) {
+        r"\b"
+    } else {
+        ""
+    };
+    let end = if is_word(phrase.trim().chars().next_back()) {
+        r"\b"
+    } else {
+        ""
+    };
+    format!("{start}{body}{end}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> CustomRules {
+        CustomRules::from_toml(toml).unwrap_or_else(|e| unreachable!("{e}"))
+    }
+
+    fn found(rules: &CustomRules, text: &str) -> Vec<(String, Option<String>)> {
+        rules
+            .lint(text)
+            .into_iter()
+            .map(|lint| {
+                let chars: String = text
+                    .chars()
+                    .skip(lint.span.start)
+                    .take(lint.span.len())
+                    .collect();
+                (chars, lint.replacement)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn phrases_match_whole_words_in_any_case_but_the_replacement() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            name = "github"
+            phrase = "GitHub"
+            message = "Write GitHub with a capital H."
+            replacement = "GitHub"
+            "#,
+        );
+        assert_eq!(
+            found(
+                &rules,
+                "Ünïcode on github, GITHUB and GitHub, not githubber."
+            ),
+            [
+                ("github".to_string(), Some("GitHub".to_string())),
+                ("GITHUB".to_string(), Some("GitHub".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn words_span_line_breaks_and_patterns_expand_captures() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            name = "end-of-day"
+            words = ["at", "the", "end", "of", "the", "day"]
+            message = "Cliché."
+            severity = "info"
+
+            [[rules]]
+            name = "utilize"
+            pattern = '\butili([sz])e\b'
+            message = "Prefer use."
+            replacement = "use (not utili${1}e)"
+            case_sensitive = true
+            "#,
+        );
+        let text = "We utilize it at the end\nof the day. Utilize.";
+        assert_eq!(
+            found(&rules, text),
+            [
+                ("at the end\nof the day".to_string(), None),
+                ("utilize".to_string(), Some("use (not utilize)".to_string())),
+            ]
+        );
+        assert_eq!(rules.lint(text)[0].rule_id, "custom:end-of-day");
+        assert_eq!(rules.lint(text)[0].severity, Severity::Info);
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for (toml, expected) in [
+            (
+                "[[rules]]\nname = \"a\"\nmessage = \"m\"",
+                "needs one of phrase, words or pattern",
+            ),
+            (
+                "[[rules]]\nname = \"a\"\nphrase = \"x\"\npattern = \"x\"\nmessage = \"m\"",
+                "only one of",
+            ),
+            (
+                "[[rules]]\nname = \"a\"\npattern = \"(\"\nmessage = \"m\"",
+                "invalid pattern",
+            ),
+            (
+                "[[rules]]\nname = \"a\"\nphrase = \"x\"\nmessage = \"m\"\n\
+                 [[rules]]\nname = \"a\"\nphrase = \"y\"\nmessage = \"m\"",
+                "duplicate rule name",
+            ),
+            (
+                "[[rules]]\nname = \"a\"\nphrase = \"x\"\nmessage = \"m\"\nseverity = \"fatal\"",
+                "unknown variant",
+            ),
+        ] {
+            match CustomRules::from_toml(toml) {
+                Err(e) => assert!(e.contains(expected), "{e}"),
+                Ok(rules) => unreachable!("accepted {rules:?}"),
+            }
+        }
+    }
+}
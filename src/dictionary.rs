@@ -0,0 +1,75 @@
+//! Spelling dictionary with site-specific word lists.
+//!
+//! Word lists are plain text files with one word per line. Blank lines and
+//! lines starting with `#` are ignored. Their words are merged on top of
+//! Harper's curated dictionary, so they are never reported as misspelled.
+
+use crate::ConfigError;
+use harper_core::{
+    spell::{Dictionary, FstDictionary, MergedDictionary, MutableDictionary},
+    WordMetadata,
+};
+use std::{fs, path::PathBuf, sync::Arc};
+
+/// Builds the curated dictionary extended with the words in `word_lists`.
+pub fn load_dictionary(word_lists: &[PathBuf]) -> Result<Arc<MergedDictionary>, ConfigError> {
+    let mut custom = MutableDictionary::new();
+
+    for path in word_lists {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(source) => {
+                return Err(ConfigError::WordList {
+                    path: path.clone(),
+                    source,
+                })
+            }
+        };
+
+        custom.extend_words(
+            parse_word_list(&contents)
+                .map(|word| (word.chars().collect::<Vec<_>>(), WordMetadata::default())),
+        );
+    }
+
+    Ok(merge(custom))
+}
+
+/// The curated dictionary without any custom words.
+pub fn curated_dictionary() -> Arc<MergedDictionary> {
+    merge(MutableDictionary::new())
+}
+
+fn merge(custom: MutableDictionary) -> Arc<MergedDictionary> {
+    let mut dictionary = MergedDictionary::new();
+    dictionary.add_dictionary(FstDictionary::curated());
+    if custom.word_count() > 0 {
+        dictionary.add_dictionary(Arc::new(custom));
+    }
+    Arc::new(dictionary)
+}
+
+/// Words listed in a word list file.
+fn parse_word_list(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_lists_skip_comments_and_blank_lines() {
+        let words: Vec<_> = parse_word_list("# product names\nZorblax\n\n  Quuxly  \n").collect();
+        assert_eq!(words, ["Zorblax", "Quuxly"]);
+    }
+
+    #[test]
+    fn missing_word_list_fails() {
+        let err = load_dictionary(&[PathBuf::from("/nonexistent/words.txt")]);
+        assert!(matches!(err, Err(ConfigError::WordList { .. })));
+    }
+}
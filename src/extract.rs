@@ -0,0 +1,182 @@
+//! Request extractors that reject with [`AppError`] instead of plain text.
+
+use crate::{AppError, AppState};
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap, StatusCode},
+};
+use metrics::counter;
+use serde::de::DeserializeOwned;
+use serde_json::error::Category;
+use zeroize::Zeroize;
+
+/// Invalid field message in privacy mode, in place of serde's.
+const REDACTED_FIELD_ERROR: &str = "value does not match the expected type";
+
+/// JSON body extractor mapping every rejection to an [`AppError`].
+///
+/// Unlike [`axum::Json`], failures carry a machine-readable code and, for
+/// deserialization errors, the path of the offending field. In privacy mode
+/// those errors do not quote the offending value. The body is zeroized once
+/// parsed, unless the connection still holds a reference to its buffer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiJson<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest<AppState> for ApiJson<T> {
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        if !has_json_content_type(req.headers()) {
+            counter!("api.errors", "type" => "unsupported_media_type").increment(1);
+            return Err(AppError::UnsupportedMediaType);
+        }
+
+        let bytes = Bytes::from_request(req, state).await.map_err(|rejection| {
+            if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                counter!("api.errors", "type" => "payload_too_large").increment(1);
+                AppError::BodyTooLarge {
+                    limit: state.limits.body_limit(),
+                }
+            } else {
+                AppError::InvalidJson {
+                    message: rejection.body_text(),
+                    field: None,
+                }
+            }
+        })?;
+
+        let parsed = {
+            let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+            serde_path_to_error::deserialize(deserializer)
+        };
+        wipe(bytes);
+        parsed.map(ApiJson).map_err(|err| {
+            counter!("api.errors", "type" => "invalid_json").increment(1);
+            json_error(&err, state.privacy)
+        })
+    }
+}
+
+/// Zeroizes a request body we hold the only reference to.
+fn wipe(body: Bytes) {
+    if let Ok(mut body) = body.try_into_mut() {
+        body.zeroize();
+    }
+}
+
+fn has_json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    essence == "application/json"
+        || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
+
+/// Maps a deserialization error to an [`AppError`]. Syntax errors only
+/// give a position, but data errors can quote the rejected value, so with
+/// `privacy` set their message is replaced.
+fn json_error(err: &serde_path_to_error::Error<serde_json::Error>, privacy: bool) -> AppError {
+    let inner = err.inner();
+    let path = err.path().to_string();
+    let path = (path != ".").then_some(path);
+
+    if inner.classify() != Category::Data {
+        return AppError::InvalidJson {
+            message: inner.to_string(),
+            field: path,
+        };
+    }
+
+    let message = inner.to_string();
+    if let Some(name) = missing_field_name(&message) {
+        let field = path.map_or_else(|| name.to_string(), |parent| format!("{parent}.{name}"));
+        return AppError::MissingField { field };
+    }
+
+    AppError::InvalidField {
+        message: if privacy {
+            REDACTED_FIELD_ERROR.to_string()
+        } else {
+            message
+        },
+        field: path.unwrap_or_default(),
+    }
+}
+
+/// Extracts `name` from serde's "missing field `name`" message.
+fn missing_field_name(message: &str) -> Option<&str> {
+    message.strip_prefix("missing field `")?.split('`').next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn accepts_json_content_types() {
+        for value in [
+            "application/json",
+            "application/json; charset=utf-8",
+            "Application/JSON",
+            "application/merge-patch+json",
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(value));
+            assert!(has_json_content_type(&headers), "{value} should be JSON");
+        }
+    }
+
+    #[test]
+    fn rejects_other_content_types() {
+        let mut headers = HeaderMap::new();
+        assert!(!has_json_content_type(&headers));
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert!(!has_json_content_type(&headers));
+    }
+
+    fn field_error(json: &str, privacy: bool) -> AppError {
+        let deserializer = &mut serde_json::Deserializer::from_str(json);
+        match serde_path_to_error::deserialize::<_, crate::CheckRequest>(deserializer) {
+            Ok(_) => unreachable!("{json} should not parse"),
+            Err(err) => json_error(&err, privacy),
+        }
+    }
+
+    #[test]
+    fn privacy_keeps_values_out_of_field_errors() {
+        let json = r#"{"text": "fine", "dialect": "Confidential"}"#;
+        let AppError::InvalidField { message, .. } = field_error(json, false) else {
+            unreachable!()
+        };
+        assert!(message.contains("Confidential"), "{message}");
+
+        let AppError::InvalidField { message, field } = field_error(json, true) else {
+            unreachable!()
+        };
+        assert_eq!(message, REDACTED_FIELD_ERROR);
+        assert_eq!(field, "dialect");
+    }
+
+    #[test]
+    fn reads_missing_field_name() {
+        assert_eq!(
+            missing_field_name("missing field `text` at line 1"),
+            Some("text")
+        );
+        assert_eq!(missing_field_name("invalid type"), None);
+    }
+}
@@ -0,0 +1,338 @@
+//! Feedback from writers on the matches they were shown.
+//!
+//! Each report names the rule behind a match, a verdict on it and,
+//! optionally, a redacted snippet of context. Reports are appended to a
+//! local file as one JSON line each and counted per rule, so the share of
+//! a rule's matches reported as false positives can be read back without
+//! rescanning the file. The counts are rebuilt from the file on startup.
+
+use crate::ConfigError;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// What a writer thought of a match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// The match flagged text that was correct.
+    FalsePositive,
+    /// The issue was real but the suggested replacements were wrong.
+    BadSuggestion,
+    /// The match was right.
+    Accepted,
+}
+
+impl Verdict {
+    /// Name used in the API and metric labels.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::FalsePositive => "false_positive",
+            Self::BadSuggestion => "bad_suggestion",
+            Self::Accepted => "accepted",
+        }
+    }
+}
+
+/// One report, as stored in the feedback file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    /// When the report was made, as an RFC 3339 UTC timestamp.
+    pub time: String,
+    /// Key name or token subject of the reporter.
+    pub key: String,
+    /// Id of the rule behind the match.
+    pub rule: String,
+    /// The reporter's verdict.
+    pub verdict: Verdict,
+    /// Redacted text around the match, if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+/// Reports received for one rule.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleFeedback {
+    /// Id of the rule.
+    pub rule: String,
+    /// Reports with every verdict.
+    pub reports: u64,
+    /// Reports of false positives.
+    #[serde(rename = "falsePositives")]
+    pub false_positives: u64,
+    /// Reports of bad suggestions.
+    #[serde(rename = "badSuggestions")]
+    pub bad_suggestions: u64,
+    /// Reports accepting the match.
+    pub accepted: u64,
+    /// Share of reports that were false positives, from 0 to 1.
+    #[serde(rename = "falsePositiveRate")]
+    pub false_positive_rate: f64,
+}
+
+/// Stores feedback reports and their per-rule counts.
+///
+/// Cloning the store is cheap; all clones share the same counts and file.
+#[derive(Clone)]
+pub struct FeedbackStore {
+    inner: Arc<StoreInner>,
+}
+
+struct StoreInner {
+    counts: Mutex<BTreeMap<String, Counts>>,
+    file: Option<Mutex<Writer>>,
+}
+
+struct Writer {
+    path: PathBuf,
+    file: File,
+}
+
+#[derive(Default)]
+struct Counts {
+    false_positives: u64,
+    bad_suggestions: u64,
+    accepted: u64,
+}
+
+impl Counts {
+    const fn add(&mut self, verdict: Verdict) {
+        match verdict {
+            Verdict::FalsePositive => self.false_positives += 1,
+            Verdict::BadSuggestion => self.bad_suggestions += 1,
+            Verdict::Accepted => self.accepted += 1,
+        }
+    }
+}
+
+impl fmt::Debug for FeedbackStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.inner.file.as_ref().map(|writer| {
+            writer
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .path
+                .clone()
+        });
+        f.debug_struct("FeedbackStore")
+            .field("file", &path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FeedbackStore {
+    /// Creates a store that is not saved to disk.
+    pub fn in_memory() -> Self {
+        Self::with_counts(BTreeMap::new(), None)
+    }
+
+    /// Opens the store saved in `path`, counting the reports already there.
+    ///
+    /// A last line cut short by a crash is removed; any other line that is
+    /// not a report fails the open.
+    pub fn open(path: &Path) -> Result<Self, ConfigError> {
+        let error = |message: String| ConfigError::Feedback {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(error(e.to_string())),
+        };
+        let complete = contents.rfind('\n').map_or("", |end| &contents[..=end]);
+
+        let mut counts = BTreeMap::<String, Counts>::new();
+        for (index, line) in complete.lines().enumerate() {
+            let record: FeedbackRecord = serde_json::from_str(line)
+                .map_err(|e| error(format!("line {}: {e}", index + 1)))?;
+            counts.entry(record.rule).or_default().add(record.verdict);
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| error(e.to_string()))?;
+        if complete.len() < contents.len() {
+            file.set_len(complete.len() as u64)
+                .map_err(|e| error(e.to_string()))?;
+        }
+        let writer = Writer {
+            path: path.to_path_buf(),
+            file,
+        };
+        Ok(Self::with_counts(counts, Some(writer)))
+    }
+
+    fn with_counts(counts: BTreeMap<String, Counts>, writer: Option<Writer>) -> Self {
+        Self {
+            inner: Arc::new(StoreInner {
+                counts: Mutex::new(counts),
+                file: writer.map(Mutex::new),
+            }),
+        }
+    }
+
+    /// Appends `record` to the file, if any, and counts it.
+    ///
+    /// Blocking. A report that cannot be written is not counted, so the
+    /// caller can retry it without it counting twice.
+    pub fn record(&self, record: &FeedbackRecord) -> io::Result<()> {
+        if let Some(writer) = &self.inner.file {
+            let mut line = serde_json::to_vec(record)?;
+            line.push(b'\n');
+            writer
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .file
+                .write_all(&line)?;
+        }
+
+        self.inner
+            .counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(record.rule.clone())
+            .or_default()
+            .add(record.verdict);
+        Ok(())
+    }
+
+    /// Reports received per rule, highest false-positive rate first.
+    pub fn summary(&self) -> Vec<RuleFeedback> {
+        let counts = self
+            .inner
+            .counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut rules: Vec<RuleFeedback> = counts
+            .iter()
+            .map(|(rule, counts)| {
+                let reports = counts.false_positives + counts.bad_suggestions + counts.accepted;
+                RuleFeedback {
+                    rule: rule.clone(),
+                    reports,
+                    false_positives: counts.false_positives,
+                    bad_suggestions: counts.bad_suggestions,
+                    accepted: counts.accepted,
+                    false_positive_rate: counts.false_positives as f64 / reports as f64,
+                }
+            })
+            .collect();
+        drop(counts);
+
+        rules.sort_by(|a, b| {
+            b.false_positive_rate
+                .total_cmp(&a.false_positive_rate)
+                .then(b.reports.cmp(&a.reports))
+        });
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(rule: &str, verdict: Verdict) -> FeedbackRecord {
+        FeedbackRecord {
+            time: "2024-03-01T09:30:00.000Z".to_string(),
+            key: "default".to_string(),
+            rule: rule.to_string(),
+            verdict,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn reopened_store_counts_saved_reports_and_drops_a_cut_line() {
+        let path =
+            std::env::temp_dir().join(format!("grammar-api-feedback-{}.jsonl", std::process::id()));
+        fs::remove_file(&path).ok();
+
+        let store = FeedbackStore::open(&path).unwrap_or_else(|e| unreachable!("{e}"));
+        for verdict in [Verdict::FalsePositive, Verdict::Accepted] {
+            store
+                .record(&report("Spelling", verdict))
+                .unwrap_or_else(|e| unreachable!("{e}"));
+        }
+        drop(store);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        file.write_all(b"{\"time\":\"2024")
+            .unwrap_or_else(|e| unreachable!("{e}"));
+
+        let reopened = FeedbackStore::open(&path).unwrap_or_else(|e| unreachable!("{e}"));
+        reopened
+            .record(&report("Spelling", Verdict::FalsePositive))
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        let summary = reopened.summary();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].reports, 3);
+        assert_eq!(summary[0].false_positives, 2);
+
+        drop(reopened);
+        let again = FeedbackStore::open(&path).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(again.summary()[0].reports, 3);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn unsaved_reports_are_not_counted() {
+        let path = std::env::temp_dir().join(format!(
+            "grammar-api-feedback-readonly-{}.jsonl",
+            std::process::id()
+        ));
+        fs::write(&path, "").unwrap_or_else(|e| unreachable!("{e}"));
+        let writer = Writer {
+            path: path.clone(),
+            file: File::open(&path).unwrap_or_else(|e| unreachable!("{e}")),
+        };
+        let store = FeedbackStore::with_counts(BTreeMap::new(), Some(writer));
+
+        assert!(store
+            .record(&report("Spelling", Verdict::FalsePositive))
+            .is_err());
+        assert!(store.summary().is_empty());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn summary_ranks_rules_by_false_positive_rate() {
+        let store = FeedbackStore::in_memory();
+        for (rule, verdict) in [
+            ("Spelling", Verdict::Accepted),
+            ("Spelling", Verdict::FalsePositive),
+            ("Capitalization", Verdict::FalsePositive),
+            ("Repetition", Verdict::BadSuggestion),
+        ] {
+            store
+                .record(&report(rule, verdict))
+                .unwrap_or_else(|e| unreachable!("{e}"));
+        }
+
+        let rates: Vec<(String, f64)> = store
+            .summary()
+            .into_iter()
+            .map(|rule| (rule.rule, rule.false_positive_rate))
+            .collect();
+        assert_eq!(
+            rates,
+            [
+                ("Capitalization".to_string(), 1.0),
+                ("Spelling".to_string(), 0.5),
+                ("Repetition".to_string(), 0.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! gRPC interface to the checker, defined in `proto/grammar.proto`.
+//!
+//! Checks run through the same pipeline as `POST /v1/check` and count
+//! against the same quotas. Requests pass the HTTP API's auth and rate
+//! limit middleware; [`status_middleware`] reports the errors those raise
+//! as gRPC statuses.
+
+use crate::{
+    privacy::Redacted, record_check, run_check, telemetry, AppError, AppState, CheckRequest,
+    CheckResponse, ErrorInfo, Identity, Match,
+};
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use harper_core::Dialect;
+use metrics::counter;
+use proto::grammar_checker_server::{GrammarChecker, GrammarCheckerServer, SERVICE_NAME};
+use std::fmt;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{metadata::MetadataMap, Code, Status, Streaming};
+use tracing::{info_span, Instrument};
+
+/// Messages generated from `proto/grammar.proto`.
+#[allow(
+    missing_docs,
+    unused_qualifications,
+    clippy::all,
+    clippy::pedantic,
+    clippy::nursery
+)]
+pub mod proto {
+    tonic::include_proto!("grammar.v1");
+}
+
+impl fmt::Debug for proto::CheckRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CheckRequest")
+            .field("id", &self.id)
+            .field("text", &Redacted(&self.text))
+            .field("dialect", &self.dialect)
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+/// Method that needs no scope beyond authentication.
+const LIST_RULES: &str = "ListRules";
+
+/// Results buffered per stream before the client must read them.
+const STREAM_BUFFER: usize = 16;
+
+/// Route matching every method of the service.
+pub(crate) fn route() -> String {
+    format!("/{SERVICE_NAME}/{{*method}}")
+}
+
+/// The `endpoint` label of the checking method at `path`, if it is one.
+pub(crate) fn check_endpoint(path: &str) -> Option<&'static str> {
+    let method = path
+        .strip_prefix('/')?
+        .strip_prefix(SERVICE_NAME)?
+        .strip_prefix('/')?;
+    match method {
+        "Check" => Some("grpc_check"),
+        "CheckBatch" => Some("grpc_check_batch"),
+        "CheckStream" => Some("grpc_check_stream"),
+        _ => None,
+    }
+}
+
+/// Whether `path` is a method that checks text.
+pub(crate) fn is_check_method(path: &str) -> bool {
+    path.strip_prefix('/')
+        .and_then(|p| p.strip_prefix(SERVICE_NAME))
+        .and_then(|p| p.strip_prefix('/'))
+        .is_some_and(|method| method != LIST_RULES)
+}
+
+/// The gRPC service, as a router for [`route`].
+pub(crate) fn service(state: AppState) -> Router {
+    let body_limit = state.limits.body_limit();
+    let server =
+        GrammarCheckerServer::new(GrpcService { state }).max_decoding_message_size(body_limit);
+    tonic::service::Routes::new(server)
+        .prepare()
+        .into_axum_router()
+}
+
+/// Turns error responses from the HTTP middleware into gRPC statuses,
+/// keeping their `Retry-After` and `X-RateLimit-*` headers.
+pub(crate) async fn status_middleware(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let Some(status) = status_from(&response) else {
+        return response;
+    };
+
+    let mut grpc = status.into_http::<Body>();
+    grpc.headers_mut()
+        .extend(forwarded_headers(response.headers()));
+    grpc
+}
+
+/// The gRPC status for an error response, or `None` for other responses.
+fn status_from(response: &Response) -> Option<Status> {
+    let info = response.extensions().get::<ErrorInfo>()?;
+    let code = match response.status() {
+        StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+        StatusCode::FORBIDDEN => Code::PermissionDenied,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => Code::Unimplemented,
+        StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+        StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+        status if status.is_client_error() => Code::InvalidArgument,
+        _ => Code::Internal,
+    };
+
+    let mut metadata = MetadataMap::from_headers(forwarded_headers(response.headers()));
+    if let Ok(value) = info.code.parse() {
+        metadata.insert("error-code", value);
+    }
+    Some(Status::with_metadata(code, info.message.clone(), metadata))
+}
+
+/// Headers of an error response that are meaningful to gRPC clients.
+fn forwarded_headers(headers: &HeaderMap) -> HeaderMap {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            *name == header::RETRY_AFTER || name.as_str().starts_with("x-ratelimit-")
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// The gRPC status for `error`.
+fn status(error: AppError) -> Status {
+    status_from(&error.into_response())
+        .unwrap_or_else(|| Status::internal("Internal error while checking text"))
+}
+
+#[derive(Debug, Clone)]
+struct GrpcService {
+    state: AppState,
+}
+
+impl GrpcService {
+    /// Runs one check of a batch or stream, reporting failure in the
+    /// result rather than failing the call.
+    async fn check_one(
+        &self,
+        caller: Option<&Identity>,
+        request: proto::CheckRequest,
+        endpoint: &'static str,
+    ) -> proto::CheckResult {
+        let id = request.id.clone();
+        let outcome = match self.run(caller, request, endpoint).await {
+            Ok(response) => proto::check_result::Outcome::Response(response),
+            Err(status) => proto::check_result::Outcome::Error(proto::Error {
+                code: status
+                    .metadata()
+                    .get("error-code")
+                    .and_then(|code| code.to_str().ok())
+                    .unwrap_or("INTERNAL_ERROR")
+                    .to_string(),
+                message: status.message().to_string(),
+            }),
+        };
+        proto::CheckResult {
+            id,
+            outcome: Some(outcome),
+        }
+    }
+
+    /// Checks the text of `request` for `caller`.
+    async fn run(
+        &self,
+        caller: Option<&Identity>,
+        request: proto::CheckRequest,
+        endpoint: &'static str,
+    ) -> Result<proto::CheckResponse, Status> {
+        let payload = CheckRequest {
+            text: request.text,
+            dialect: dialect(request.dialect).map_err(status)?,
+            timeout_ms: (request.timeout_ms > 0).then_some(request.timeout_ms),
+        };
+        let response = run_check(&self.state, caller, payload, endpoint)
+            .await
+            .map_err(status)?;
+
+        let _span = info_span!(
+            "serialize",
+            matches = telemetry::count(response.matches.len())
+        )
+        .entered();
+        Ok(response.into())
+    }
+}
+
+#[tonic::async_trait]
+impl GrammarChecker for GrpcService {
+    async fn check(
+        &self,
+        request: tonic::Request<proto::CheckRequest>,
+    ) -> Result<tonic::Response<proto::CheckResponse>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let text_length = request.text.len();
+        let response = self.run(caller.as_ref(), request, "grpc_check").await?;
+        record_check(text_length, response.matches.len());
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn check_batch(
+        &self,
+        request: tonic::Request<proto::CheckBatchRequest>,
+    ) -> Result<tonic::Response<proto::CheckBatchResponse>, Status> {
+        let caller = caller(&request);
+        let requests = request.into_inner().requests;
+        // The whole batch passes the rate limiter as one call, so its size
+        // is capped before any text is checked.
+        let max = self.state.limits.max_batch_size;
+        if requests.len() > max {
+            return Err(status(AppError::InvalidField {
+                message: format!(
+                    "batch of {} texts exceeds the limit of {max}",
+                    requests.len()
+                ),
+                field: "requests".to_string(),
+            }));
+        }
+        let mut results = Vec::new();
+        for request in requests {
+            results.push(
+                self.check_one(caller.as_ref(), request, "grpc_check_batch")
+                    .await,
+            );
+        }
+        Ok(tonic::Response::new(proto::CheckBatchResponse { results }))
+    }
+
+    async fn list_rules(
+        &self,
+        request: tonic::Request<proto::ListRulesRequest>,
+    ) -> Result<tonic::Response<proto::ListRulesResponse>, Status> {
+        counter!("api.requests", "endpoint" => "grpc_list_rules").increment(1);
+
+        let dialect = dialect(request.into_inner().dialect)
+            .map_err(status)?
+            .unwrap_or(Dialect::American);
+        let linters = self.state.linters.clone();
+        let custom = self.state.custom_rules.clone();
+        let rules = self
+            .state
+            .executor
+            .run(move || {
+                let linter = linters.checkout(dialect);
+                let mut rules: Vec<_> = linter
+                    .all_descriptions()
+                    .into_iter()
+                    .map(|(name, description)| proto::RuleInfo {
+                        name: name.to_string(),
+                        description: description.to_string(),
+                        enabled: linter.config.is_rule_enabled(name),
+                    })
+                    .collect();
+                rules.sort_by(|a, b| a.name.cmp(&b.name));
+                rules.extend(custom.descriptions().map(|(id, message)| proto::RuleInfo {
+                    name: id.to_string(),
+                    description: message.to_string(),
+                    enabled: true,
+                }));
+                rules
+            })
+            .await
+            .map_err(|e| status(e.into()))?;
+
+        Ok(tonic::Response::new(proto::ListRulesResponse { rules }))
+    }
+
+    type CheckStreamStream = ReceiverStream<Result<proto::CheckResult, Status>>;
+
+    async fn check_stream(
+        &self,
+        request: tonic::Request<Streaming<proto::CheckRequest>>,
+    ) -> Result<tonic::Response<Self::CheckStreamStream>, Status> {
+        let caller = caller(&request);
+        let mut requests = request.into_inner();
+        let (results, stream) = mpsc::channel(STREAM_BUFFER);
+        let service = self.clone();
+
+        let task = async move {
+            loop {
+                let request = match requests.message().await {
+                    Ok(Some(request)) => request,
+                    Ok(None) => break,
+                    Err(status) => {
+                        results.send(Err(status)).await.ok();
+                        break;
+                    }
+                };
+                let result = service
+                    .check_one(caller.as_ref(), request, "grpc_check_stream")
+                    .await;
+                // The client hung up.
+                if results.send(Ok(result)).await.is_err() {
+                    break;
+                }
+            }
+        };
+        tokio::spawn(task.in_current_span());
+
+        Ok(tonic::Response::new(ReceiverStream::new(stream)))
+    }
+}
+
+/// The caller identified by the auth middleware, if any.
+fn caller<T>(request: &tonic::Request<T>) -> Option<Identity> {
+    request.extensions().get::<Identity>().cloned()
+}
+
+/// The dialect a request asks for, or `None` for the default.
+fn dialect(value: i32) -> Result<Option<Dialect>, AppError> {
+    match proto::Dialect::try_from(value) {
+        Ok(proto::Dialect::Unspecified) => Ok(None),
+        Ok(proto::Dialect::American) => Ok(Some(Dialect::American)),
+        Ok(proto::Dialect::British) => Ok(Some(Dialect::British)),
+        Ok(proto::Dialect::Canadian) => Ok(Some(Dialect::Canadian)),
+        Ok(proto::Dialect::Australian) => Ok(Some(Dialect::Australian)),
+        Err(_) => {
+            counter!("api.errors", "type" => "invalid_field").increment(1);
+            Err(AppError::InvalidField {
+                message: format!("unknown dialect {value}"),
+                field: "dialect".to_string(),
+            })
+        }
+    }
+}
+
+impl From<CheckResponse> for proto::CheckResponse {
+    fn from(response: CheckResponse) -> Self {
+        Self {
+            matches: response.matches.into_iter().map(Into::into).collect(),
+            partial: response.partial,
+            reason: response.reason.unwrap_or_default(),
+            processing_time_ms: u64::try_from(response.metrics.processing_time_ms)
+                .unwrap_or(u64::MAX),
+        }
+    }
+}
+
+impl From<Match> for proto::Match {
+    fn from(m: Match) -> Self {
+        Self {
+            message: m.message,
+            offset: m.offset as u64,
+            length: m.length as u64,
+            replacements: m.replacements,
+            rule: Some(proto::Rule {
+                id: m.rule.id,
+                category: m.rule.category,
+                severity: m
+                    .rule
+                    .severity
+                    .map(|severity| severity.as_str().to_string())
+                    .unwrap_or_default(),
+            }),
+            context: Some(proto::Context {
+                text: m.context.text,
+                offset: m.context.offset as u64,
+                length: m.context.length as u64,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_check_methods_need_check_scope() {
+        assert!(is_check_method("/grammar.v1.GrammarChecker/Check"));
+        assert!(is_check_method("/grammar.v1.GrammarChecker/CheckStream"));
+        assert!(!is_check_method("/grammar.v1.GrammarChecker/ListRules"));
+        assert!(!is_check_method("/v1/check"));
+    }
+
+    #[test]
+    fn app_errors_map_to_grpc_codes() {
+        let unauthorized = status(AppError::Unauthorized);
+        assert_eq!(unauthorized.code(), Code::Unauthenticated);
+        assert_eq!(
+            unauthorized
+                .metadata()
+                .get("error-code")
+                .and_then(|v| v.to_str().ok()),
+            Some("UNAUTHORIZED")
+        );
+
+        let limited = status(AppError::RateLimited {
+            retry_after_secs: 3,
+        });
+        assert_eq!(limited.code(), Code::ResourceExhausted);
+        assert_eq!(
+            limited
+                .metadata()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok()),
+            Some("3")
+        );
+
+        assert_eq!(
+            status(AppError::PayloadTooLarge { limit: 1 }).code(),
+            Code::InvalidArgument
+        );
+        assert_eq!(status(AppError::Overloaded).code(), Code::Unavailable);
+    }
+}
@@ -0,0 +1,323 @@
+//! Liveness and readiness probes.
+//!
+//! `/livez` answers as long as the process can serve requests at all.
+//! `/readyz` (and `/health`) also fail while the dictionary is loading or
+//! empty, the last configuration reload failed, the lint queue is full or
+//! shutdown has begun, so load balancers stop sending new work. Add
+//! `?verbose` to get a JSON body with each check and build details.
+//!
+//! Listeners are bound before the dictionary loads and answer through a
+//! [`Startup`] router until it has, so probes see the load.
+
+use crate::{AppError, AppState};
+use axum::{
+    extract::{Query, Request, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+use tower::{service_fn, ServiceExt};
+
+/// Harper release the server is built against, as resolved in
+/// `Cargo.lock`.
+const HARPER_VERSION: &str = env!("HARPER_VERSION");
+
+/// Process health shared across configuration reloads.
+///
+/// Cloning is cheap; all clones share the same state.
+#[derive(Clone)]
+pub struct Health {
+    inner: Arc<HealthInner>,
+}
+
+struct HealthInner {
+    started: Instant,
+    shutting_down: AtomicBool,
+    config_error: Mutex<Option<String>>,
+}
+
+impl fmt::Debug for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Health")
+            .field("uptime", &self.uptime())
+            .field("shutting_down", &self.is_shutting_down())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Health {
+    /// Starts tracking health from now.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(HealthInner {
+                started: Instant::now(),
+                shutting_down: AtomicBool::new(false),
+                config_error: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Time since the process started.
+    pub fn uptime(&self) -> Duration {
+        self.inner.started.elapsed()
+    }
+
+    /// Marks the process as shutting down; readiness fails from now on.
+    pub fn start_shutdown(&self) {
+        self.inner.shutting_down.store(true, Ordering::Release);
+    }
+
+    /// Whether shutdown has begun.
+    pub fn is_shutting_down(&self) -> bool {
+        self.inner.shutting_down.load(Ordering::Acquire)
+    }
+
+    /// Records the outcome of the last configuration reload; readiness
+    /// fails while it is an error.
+    pub fn set_config_error(&self, error: Option<String>) {
+        *self
+            .inner
+            .config_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = error;
+    }
+
+    fn config_error(&self) -> Option<String> {
+        self.inner
+            .config_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Serves a listener bound before the application has loaded: probes
+/// report the dictionary as loading and other requests get a `503`, until
+/// [`ready`](Self::ready) hands the listener to the application.
+///
+/// Cloning is cheap; all clones share the same state.
+#[derive(Debug, Clone)]
+pub struct Startup {
+    health: Health,
+    app: Arc<OnceLock<Router>>,
+}
+
+impl Startup {
+    /// Answers for the application that will report `health`.
+    pub fn new(health: Health) -> Self {
+        Self {
+            health,
+            app: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// A router that answers while loading and forwards every request to
+    /// the application once it is ready.
+    pub fn router(&self) -> Router {
+        let startup = self.clone();
+        Router::new().fallback_service(service_fn(move |request: Request| {
+            let app = startup.app.get().cloned();
+            let health = startup.health.clone();
+            async move {
+                match app {
+                    Some(app) => app.oneshot(request).await,
+                    None => Ok(starting(&health, &request)),
+                }
+            }
+        }))
+    }
+
+    /// Forwards requests to `app` from now on.
+    pub fn ready(&self, app: Router) {
+        self.app.set(app).ok();
+    }
+}
+
+/// The answer to `request` while the application is loading.
+fn starting(health: &Health, request: &Request) -> Response {
+    let query = Query::<ProbeQuery>::try_from_uri(request.uri())
+        .map(|Query(query)| query)
+        .unwrap_or_default();
+    let checks = Checks::starting(health);
+    match request.uri().path() {
+        "/livez" => live(&query, health, 0, checks),
+        "/readyz" | "/health" => ready(&query, health, 0, checks),
+        _ => AppError::Starting.into_response(),
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub(crate) struct ProbeQuery {
+    verbose: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HealthReport {
+    status: &'static str,
+    version: &'static str,
+    harper_version: &'static str,
+    uptime_secs: u64,
+    dictionary_words: usize,
+    checks: Checks,
+}
+
+#[derive(Serialize)]
+struct Checks {
+    dictionary: Check,
+    config: Check,
+    queue: Check,
+    shutdown: Check,
+}
+
+/// One readiness check: `"ok"` or why it is failing.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Check {
+    Ok(&'static str),
+    Failing(String),
+}
+
+impl Check {
+    fn from(failure: Option<String>) -> Self {
+        failure.map_or(Self::Ok("ok"), Self::Failing)
+    }
+}
+
+impl Checks {
+    fn run(state: &AppState) -> Self {
+        Self {
+            dictionary: Check::from(
+                (state.dictionary_words == 0).then(|| "dictionary is empty".to_string()),
+            ),
+            config: Check::from(
+                state
+                    .health
+                    .config_error()
+                    .map(|e| format!("last reload failed: {e}")),
+            ),
+            queue: Check::from(
+                state
+                    .executor
+                    .is_saturated()
+                    .then(|| "lint queue is full".to_string()),
+            ),
+            shutdown: Check::from(
+                state
+                    .health
+                    .is_shutting_down()
+                    .then(|| "shutting down".to_string()),
+            ),
+        }
+    }
+
+    /// Checks while the application is still loading.
+    fn starting(health: &Health) -> Self {
+        Self {
+            dictionary: Check::Failing("dictionary is loading".to_string()),
+            config: Check::Ok("ok"),
+            queue: Check::Ok("ok"),
+            shutdown: Check::from(
+                health
+                    .is_shutting_down()
+                    .then(|| "shutting down".to_string()),
+            ),
+        }
+    }
+
+    fn first_failure(&self) -> Option<&str> {
+        [&self.dictionary, &self.config, &self.queue, &self.shutdown]
+            .into_iter()
+            .find_map(|check| match check {
+                Check::Ok(_) => None,
+                Check::Failing(reason) => Some(reason.as_str()),
+            })
+    }
+}
+
+/// Liveness: the process is up and serving requests.
+pub(crate) async fn livez(
+    State(state): State<AppState>,
+    Query(query): Query<ProbeQuery>,
+) -> Response {
+    counter!("api.requests", "endpoint" => "livez").increment(1);
+    live(
+        &query,
+        &state.health,
+        state.dictionary_words,
+        Checks::run(&state),
+    )
+}
+
+/// Readiness: the process should be sent new requests.
+pub(crate) async fn readyz(
+    State(state): State<AppState>,
+    Query(query): Query<ProbeQuery>,
+) -> Response {
+    counter!("api.requests", "endpoint" => "readyz").increment(1);
+    ready(
+        &query,
+        &state.health,
+        state.dictionary_words,
+        Checks::run(&state),
+    )
+}
+
+fn live(query: &ProbeQuery, health: &Health, dictionary_words: usize, checks: Checks) -> Response {
+    if query.verbose.is_some() {
+        return Json(report(health, dictionary_words, "ok", checks)).into_response();
+    }
+    "ok".into_response()
+}
+
+fn ready(query: &ProbeQuery, health: &Health, dictionary_words: usize, checks: Checks) -> Response {
+    let failure = checks.first_failure().map(str::to_string);
+    let (status, label) = match failure {
+        None => (StatusCode::OK, "ok"),
+        Some(_) => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+    };
+
+    if query.verbose.is_some() {
+        return (
+            status,
+            Json(report(health, dictionary_words, label, checks)),
+        )
+            .into_response();
+    }
+    match failure {
+        Some(reason) => (status, format!("unavailable: {reason}")).into_response(),
+        None => (status, label).into_response(),
+    }
+}
+
+fn report(
+    health: &Health,
+    dictionary_words: usize,
+    status: &'static str,
+    checks: Checks,
+) -> HealthReport {
+    HealthReport {
+        status,
+        version: env!("CARGO_PKG_VERSION"),
+        harper_version: HARPER_VERSION,
+        uptime_secs: health.uptime().as_secs(),
+        dictionary_words,
+        checks,
+    }
+}
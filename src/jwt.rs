@@ -0,0 +1,345 @@
+//! JWT bearer token verification.
+//!
+//! Tokens are signed with HS256 using a shared secret, or with RS256 or
+//! ES256 using public keys from a local JWKS file. Verified claims are
+//! mapped to the same [`Identity`] used for API keys.
+
+use crate::{ConfigError, Credential, Identity, JwtConfig};
+use jsonwebtoken::{
+    decode, decode_header,
+    errors::ErrorKind,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use serde_json::{Map, Value};
+use std::{fmt, fs, path::Path};
+
+/// Why a token was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtError {
+    /// The token's `exp` has passed.
+    Expired,
+    /// The token is malformed, has a bad signature or fails a claim check.
+    Invalid,
+}
+
+/// Verifies JWTs against the configured keys and claim requirements.
+pub struct JwtVerifier {
+    keys: Vec<VerifyingKey>,
+    validation: Validation,
+    scopes_claim: String,
+    tenant_claim: String,
+}
+
+struct VerifyingKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+impl fmt::Debug for JwtVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<_> = self
+            .keys
+            .iter()
+            .map(|k| (k.kid.as_deref(), k.algorithm))
+            .collect();
+        f.debug_struct("JwtVerifier")
+            .field("keys", &keys)
+            .field("audience", &self.validation.aud)
+            .field("issuer", &self.validation.iss)
+            .finish_non_exhaustive()
+    }
+}
+
+impl JwtVerifier {
+    /// Builds a verifier from `config`, reading the JWKS file if set.
+    pub fn load(config: &JwtConfig) -> Result<Self, ConfigError> {
+        let mut keys = Vec::new();
+
+        if let Some(secret) = &config.secret {
+            keys.push(VerifyingKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+            });
+        }
+
+        if let Some(path) = &config.jwks_file {
+            keys.extend(load_jwks(path)?);
+        }
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = config.leeway_secs;
+        validation.validate_nbf = true;
+        let mut required = vec!["exp", "sub"];
+        if !config.audience.is_empty() {
+            validation.set_audience(&config.audience);
+            required.push("aud");
+        } else {
+            validation.validate_aud = false;
+        }
+        if !config.issuer.is_empty() {
+            validation.set_issuer(&config.issuer);
+            required.push("iss");
+        }
+        validation.set_required_spec_claims(&required);
+
+        Ok(Self {
+            keys,
+            validation,
+            scopes_claim: config.scopes_claim.clone(),
+            tenant_claim: config.tenant_claim.clone(),
+        })
+    }
+
+    /// Verifies `token` and maps its claims to an identity.
+    pub fn verify(&self, token: &str) -> Result<Identity, JwtError> {
+        let header = decode_header(token).map_err(|_| JwtError::Invalid)?;
+        let candidates = self.keys.iter().filter(|key| {
+            key.algorithm == header.alg
+                && match (&header.kid, &key.kid) {
+                    (Some(wanted), Some(kid)) => wanted == kid,
+                    _ => true,
+                }
+        });
+
+        let mut validation = self.validation.clone();
+        validation.algorithms = vec![header.alg];
+
+        for key in candidates {
+            match decode::<Map<String, Value>>(token, &key.key, &validation) {
+                Ok(data) => return self.identity(&data.claims),
+                Err(e) if *e.kind() == ErrorKind::InvalidSignature => {}
+                Err(e) if *e.kind() == ErrorKind::ExpiredSignature => {
+                    return Err(JwtError::Expired)
+                }
+                Err(_) => return Err(JwtError::Invalid),
+            }
+        }
+
+        Err(JwtError::Invalid)
+    }
+
+    fn identity(&self, claims: &Map<String, Value>) -> Result<Identity, JwtError> {
+        let name = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or(JwtError::Invalid)?
+            .to_string();
+        let tenant = claims
+            .get(&self.tenant_claim)
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        // Scopes are either an OAuth-style space-separated string or an array.
+        let scopes = match claims.get(&self.scopes_claim) {
+            Some(Value::String(scopes)) => scopes.split_whitespace().map(str::to_string).collect(),
+            Some(Value::Array(scopes)) => scopes
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Identity {
+            name,
+            tenant,
+            scopes,
+            credential: Credential::Jwt,
+        })
+    }
+}
+
+/// Whether `token` has the three dot-separated parts of a JWT.
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+fn load_jwks(path: &Path) -> Result<Vec<VerifyingKey>, ConfigError> {
+    let error = |message: String| ConfigError::Jwks {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let contents = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+    let set: JwkSet = serde_json::from_str(&contents).map_err(|e| error(e.to_string()))?;
+
+    set.keys
+        .iter()
+        .map(|jwk| {
+            let kid = jwk.common.key_id.clone();
+            let algorithm = jwk_algorithm(jwk).ok_or_else(|| {
+                error(format!(
+                    "key {:?}: only HS256, RS256 and ES256 keys are supported",
+                    kid.as_deref().unwrap_or_default()
+                ))
+            })?;
+            let key = DecodingKey::from_jwk(jwk).map_err(|e| error(e.to_string()))?;
+            Ok(VerifyingKey {
+                kid,
+                algorithm,
+                key,
+            })
+        })
+        .collect()
+}
+
+/// The signing algorithm a JWK is used with, declared or implied by its type.
+fn jwk_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    let implied = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Algorithm::RS256,
+        AlgorithmParameters::EllipticCurve(params) if params.curve == EllipticCurve::P256 => {
+            Algorithm::ES256
+        }
+        AlgorithmParameters::OctetKey(_) => Algorithm::HS256,
+        _ => return None,
+    };
+
+    match jwk.common.key_algorithm {
+        None => Some(implied),
+        Some(KeyAlgorithm::RS256) if implied == Algorithm::RS256 => Some(implied),
+        Some(KeyAlgorithm::ES256) if implied == Algorithm::ES256 => Some(implied),
+        Some(KeyAlgorithm::HS256) if implied == Algorithm::HS256 => Some(implied),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &str = "test-secret";
+
+    fn config() -> JwtConfig {
+        JwtConfig {
+            secret: Some(SECRET.to_string()),
+            audience: vec!["grammar-api".to_string()],
+            issuer: vec!["https://issuer.example".to_string()],
+            ..JwtConfig::default()
+        }
+    }
+
+    fn verifier(config: &JwtConfig) -> JwtVerifier {
+        JwtVerifier::load(config).unwrap_or_else(|e| unreachable!("{e}"))
+    }
+
+    fn token(claims: &Value) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"))
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "user-1",
+            "tenant": "acme",
+            "scope": "check usage",
+            "aud": "grammar-api",
+            "iss": "https://issuer.example",
+            "exp": get_current_timestamp() + 600,
+        })
+    }
+
+    #[test]
+    fn maps_claims_to_identity() {
+        let identity = verifier(&config()).verify(&token(&claims()));
+
+        assert_eq!(
+            identity,
+            Ok(Identity {
+                name: "user-1".to_string(),
+                tenant: Some("acme".to_string()),
+                scopes: vec!["check".to_string(), "usage".to_string()],
+                credential: Credential::Jwt,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_expired_and_not_yet_valid_tokens() {
+        let verifier = verifier(&config());
+
+        let mut expired = claims();
+        expired["exp"] = json!(get_current_timestamp() - 600);
+        assert_eq!(verifier.verify(&token(&expired)), Err(JwtError::Expired));
+
+        let mut early = claims();
+        early["nbf"] = json!(get_current_timestamp() + 600);
+        assert_eq!(verifier.verify(&token(&early)), Err(JwtError::Invalid));
+    }
+
+    #[test]
+    fn rejects_wrong_audience_issuer_and_secret() {
+        let verifier = verifier(&config());
+
+        let mut audience = claims();
+        audience["aud"] = json!("someone-else");
+        assert_eq!(verifier.verify(&token(&audience)), Err(JwtError::Invalid));
+
+        let mut issuer = claims();
+        issuer["iss"] = json!("https://evil.example");
+        assert_eq!(verifier.verify(&token(&issuer)), Err(JwtError::Invalid));
+
+        let forged = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(),
+            &EncodingKey::from_secret(b"other-secret"),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(verifier.verify(&forged), Err(JwtError::Invalid));
+    }
+
+    #[test]
+    fn verifies_es256_tokens_from_jwks() {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+        use ring::{
+            rand::SystemRandom,
+            signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+        };
+
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        // Uncompressed point: 0x04 || x || y.
+        let point = pair.public_key().as_ref();
+        let jwks = json!({ "keys": [{
+            "kty": "EC",
+            "crv": "P-256",
+            "kid": "ec-1",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+        }]});
+
+        let path = std::env::temp_dir().join(format!("{}-jwks.json", std::process::id()));
+        fs::write(&path, jwks.to_string()).unwrap_or_else(|e| unreachable!("{e}"));
+        let verifier = verifier(&JwtConfig {
+            jwks_file: Some(path.clone()),
+            ..JwtConfig::default()
+        });
+        // The key set is read when the verifier is built.
+        fs::remove_file(&path).ok();
+
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("ec-1".to_string());
+        let signed = encode(
+            &header,
+            &json!({ "sub": "svc", "exp": get_current_timestamp() + 600 }),
+            &EncodingKey::from_ec_der(pkcs8.as_ref()),
+        )
+        .unwrap_or_else(|e| unreachable!("{e}"));
+
+        assert_eq!(
+            verifier.verify(&signed).map(|i| i.name),
+            Ok("svc".to_string())
+        );
+    }
+}
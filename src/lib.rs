@@ -4,63 +4,204 @@
 //! checking using the Harper library.
 
 use axum::{
-    extract::{Request, State},
+    extract::{DefaultBodyLimit, MatchedPath, Request, State},
     http::{header, Method, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 use harper_core::{
-    linting::{LintGroup, Linter},
+    linting::{LintKind, Linter},
     parsers::PlainEnglish,
-    spell::FstDictionary,
+    spell::{Dictionary, MergedDictionary},
     Dialect, Document, Span,
 };
-use metrics::{counter, histogram};
-use metrics_exporter_prometheus::PrometheusHandle;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use metrics_util::layers::FanoutBuilder;
 use serde::{Deserialize, Serialize};
-use std::{env, fmt, sync::Arc, time::Instant};
-use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tower::util::option_layer;
 use tower_http::{
     cors::CorsLayer,
     request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     trace::TraceLayer,
 };
 use tracing::info_span;
+use zeroize::Zeroize;
+
+pub mod audit;
+mod auth;
+mod calendar;
+mod config;
+mod custom_rules;
+mod dictionary;
+mod extract;
+mod feedback;
+mod grpc;
+mod health;
+mod jwt;
+mod lint_executor;
+mod linter_pool;
+mod listener;
+pub mod privacy;
+mod rate_limit;
+mod reload;
+mod segments;
+pub mod systemd;
+mod telemetry;
+mod tls;
+mod usage;
+
+pub use auth::{hash_secret, Credential, Identity, KeyError, KeyStore, SCOPE_CHECK};
+pub use config::{
+    AdminConfig, AuditConfig, AuthConfig, Config, ConfigError, ConfigOverrides, CorsConfig,
+    DictionaryConfig, FeedbackConfig, GrpcConfig, JwtConfig, Limits, LintConfig, LogConfig,
+    LogFormat, MetricsConfig, PrivacyConfig, QuotaConfig, QuotaLimits, RateLimitConfig,
+    RateLimitKey, RateLimitOverride, ServerConfig, TelemetryConfig, TlsConfig, MAX_TEXT_SIZE,
+};
+pub use custom_rules::{CustomRules, Severity, CUSTOM_RULE_PREFIX};
+pub use dictionary::{curated_dictionary, load_dictionary};
+pub use extract::ApiJson;
+pub use feedback::{FeedbackRecord, FeedbackStore, RuleFeedback, Verdict};
+pub use grpc::proto;
+pub use health::{Health, Startup};
+pub use jwt::{JwtError, JwtVerifier};
+pub use lint_executor::{ExecutorError, LintExecutor};
+pub use linter_pool::{LinterPool, PooledLinter};
+#[cfg(unix)]
+pub use listener::UnixPeer;
+pub use listener::{ListenAddress, ServerListener};
+pub use rate_limit::RateLimiter;
+pub use reload::ReloadableApp;
+pub use telemetry::{Telemetry, TelemetryError};
+pub use tls::{ClientCertificate, ConnectionService, TlsListener, TlsMakeService, TlsTerminator};
+pub use usage::{KeyUsage, PeriodUsage, QuotaExceeded, QuotaPeriod, UsageTracker};
+
+use audit::{AuditLog, AuditScope};
+use custom_rules::CustomLint;
+use privacy::Redacted;
+use rate_limit::{failed_auth_middleware, rate_limit_middleware};
+use rayon::prelude::*;
+use segments::{split_paragraphs, split_sections, Segment};
+
+/// Target size of the sections large documents are split into for
+/// parallel linting, in characters.
+const SECTION_CHARS: usize = 16 * 1024;
+
+/// Reason reported when a check is cut short by its deadline.
+const DEADLINE_EXCEEDED: &str = "DEADLINE_EXCEEDED";
+
+/// Reason reported when matches are truncated to the configured maximum.
+const TOO_MANY_MATCHES: &str = "TOO_MANY_MATCHES";
+
+/// Usage key for requests made while authentication is disabled.
+const ANONYMOUS_KEY: &str = "anonymous";
+
+/// Histograms whose buckets are `metrics.duration_buckets_ms`.
+const DURATION_HISTOGRAMS: [&str; 2] = ["api.request_duration_ms", "lint.queue_wait_ms"];
 
-/// Maximum allowed text size in bytes (100KB).
-pub const MAX_TEXT_SIZE: usize = 100 * 1024;
+/// Histogram whose buckets are `metrics.text_size_buckets_bytes`.
+const TEXT_SIZE_HISTOGRAM: &str = "api.text_size_bytes";
 
-/// Default rate limit: requests per second per IP.
-const DEFAULT_RATE_LIMIT_PER_SECOND: u64 = 10;
+/// Harper's lint kinds, whose names are the ids of its rules in matches.
+const LINT_KINDS: [LintKind; 10] = [
+    LintKind::Spelling,
+    LintKind::Capitalization,
+    LintKind::Style,
+    LintKind::Formatting,
+    LintKind::Repetition,
+    LintKind::Enhancement,
+    LintKind::Readability,
+    LintKind::WordChoice,
+    LintKind::Miscellaneous,
+    LintKind::Punctuation,
+];
 
-/// Default rate limit burst size.
-const DEFAULT_RATE_LIMIT_BURST: u32 = 30;
+/// Seconds clients are asked to wait when the lint queue is full.
+const RETRY_AFTER_SECS: u64 = 1;
 
 /// Application state shared across handlers.
 #[derive(Clone)]
 pub struct AppState {
-    dictionary: Arc<FstDictionary>,
-    api_key: Option<String>,
+    linters: LinterPool,
+    custom_rules: CustomRules,
+    executor: LintExecutor,
+    check_timeout: Duration,
+    limits: Limits,
+    keys: Option<Arc<KeyStore>>,
+    jwt: Option<Arc<JwtVerifier>>,
+    usage: UsageTracker,
+    quota: Arc<QuotaConfig>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    health: Health,
+    dictionary_words: usize,
     metrics_handle: PrometheusHandle,
+    privacy: bool,
+    audit: AuditLog,
+    feedback: FeedbackStore,
+    max_feedback_context: usize,
 }
 
 impl fmt::Debug for AppState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("AppState")
-            .field("dictionary", &"<FstDictionary>")
-            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .field("linters", &self.linters)
+            .field("custom_rules", &self.custom_rules)
+            .field("executor", &self.executor)
+            .field("check_timeout", &self.check_timeout)
+            .field("limits", &self.limits)
+            .field("keys", &self.keys)
+            .field("jwt", &self.jwt)
+            .field("usage", &self.usage)
+            .field("quota", &self.quota)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("health", &self.health)
+            .field("dictionary_words", &self.dictionary_words)
             .field("metrics_handle", &"<PrometheusHandle>")
+            .field("privacy", &self.privacy)
+            .field("audit", &self.audit)
+            .field("feedback", &self.feedback)
+            .field("max_feedback_context", &self.max_feedback_context)
             .finish()
     }
 }
 
 /// Request payload for the check endpoint.
-#[derive(Debug, Deserialize)]
+///
+/// The text is zeroized when the request is dropped.
+#[derive(Deserialize)]
 pub struct CheckRequest {
     /// The text to check for grammar and spelling errors.
     text: String,
+    /// English dialect to check against (defaults to American).
+    #[serde(default)]
+    dialect: Option<Dialect>,
+    /// Deadline for this check in milliseconds, capped at the server default.
+    #[serde(default, rename = "timeoutMs")]
+    timeout_ms: Option<u64>,
+}
+
+impl fmt::Debug for CheckRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CheckRequest")
+            .field("text", &Redacted(&self.text))
+            .field("dialect", &self.dialect)
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+impl Drop for CheckRequest {
+    fn drop(&mut self) {
+        self.text.zeroize();
+    }
 }
 
 /// Response from the check endpoint.
@@ -68,6 +209,11 @@ pub struct CheckRequest {
 pub struct CheckResponse {
     /// List of detected issues.
     matches: Vec<Match>,
+    /// Whether checking stopped early, so `matches` covers only part of the text.
+    partial: bool,
+    /// Why checking stopped early, when `partial` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
     /// Processing metrics.
     metrics: Metrics,
 }
@@ -94,8 +240,11 @@ pub struct Match {
 pub struct Rule {
     /// Unique identifier for the rule.
     id: String,
-    /// Category of the rule (spelling or grammar).
+    /// Category of the rule (spelling, grammar, or style for custom rules).
     category: String,
+    /// Severity set by a custom rule; Harper's rules have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<Severity>,
 }
 
 /// Context surrounding a detected issue.
@@ -124,41 +273,279 @@ pub struct ApiError {
     error: String,
     /// Machine-readable error code.
     code: String,
+    /// The configured limit that was exceeded, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+    /// Path of the request field the error refers to, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
 }
 
 /// Application errors.
 #[derive(Debug)]
 pub enum AppError {
-    /// Request payload exceeds maximum size.
-    PayloadTooLarge,
+    /// Text exceeds the maximum size in bytes.
+    PayloadTooLarge {
+        /// The configured text limit.
+        limit: usize,
+    },
+    /// Request body exceeds the maximum size in bytes.
+    BodyTooLarge {
+        /// The configured body limit.
+        limit: usize,
+    },
+    /// The request body is not valid JSON.
+    InvalidJson {
+        /// Parser error message.
+        message: String,
+        /// Path of the field being parsed, if known.
+        field: Option<String>,
+    },
+    /// A required field is missing from the request body.
+    MissingField {
+        /// Path of the missing field.
+        field: String,
+    },
+    /// A field in the request body has the wrong type or value.
+    InvalidField {
+        /// Deserialization error message.
+        message: String,
+        /// Path of the invalid field.
+        field: String,
+    },
+    /// The request body is not declared as JSON.
+    UnsupportedMediaType,
+    /// No route matches the request path.
+    NotFound,
+    /// The route exists but does not accept the request method.
+    MethodNotAllowed,
     /// Invalid or missing API key.
     Unauthorized,
+    /// The API key does not grant the scope the route requires.
+    Forbidden,
+    /// The key has used up a usage quota.
+    QuotaExceeded {
+        /// The period whose quota ran out.
+        period: QuotaPeriod,
+        /// The quota that was reached.
+        limit: u64,
+        /// Seconds until the quota resets.
+        retry_after_secs: u64,
+    },
+    /// The client exceeded its request rate.
+    RateLimited {
+        /// Seconds until another request is allowed.
+        retry_after_secs: u64,
+    },
+    /// The lint queue is full; the client should retry later.
+    Overloaded,
+    /// The server is still loading; the client should retry later.
+    Starting,
+    /// Linting failed unexpectedly.
+    Internal,
+    /// Feedback could not be saved; the client should retry.
+    FeedbackNotSaved,
+}
+
+impl From<ExecutorError> for AppError {
+    fn from(err: ExecutorError) -> Self {
+        match err {
+            ExecutorError::QueueFull => Self::Overloaded,
+            ExecutorError::Failed => Self::Internal,
+        }
+    }
+}
+
+impl From<QuotaExceeded> for AppError {
+    fn from(err: QuotaExceeded) -> Self {
+        Self::QuotaExceeded {
+            period: err.period,
+            limit: err.limit,
+            retry_after_secs: err.retry_after.as_secs(),
+        }
+    }
+}
+
+impl AppError {
+    /// Machine-readable code reported in the error body.
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::PayloadTooLarge { .. } | Self::BodyTooLarge { .. } => "PAYLOAD_TOO_LARGE",
+            Self::InvalidJson { .. } => "INVALID_JSON",
+            Self::MissingField { .. } => "MISSING_FIELD",
+            Self::InvalidField { .. } => "INVALID_FIELD",
+            Self::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            Self::NotFound => "NOT_FOUND",
+            Self::MethodNotAllowed => "METHOD_NOT_ALLOWED",
+            Self::Unauthorized => "UNAUTHORIZED",
+            Self::Forbidden => "FORBIDDEN",
+            Self::QuotaExceeded { .. } => "QUOTA_EXCEEDED",
+            Self::RateLimited { .. } => "RATE_LIMITED",
+            Self::Overloaded => "OVERLOADED",
+            Self::Starting => "STARTING",
+            Self::Internal | Self::FeedbackNotSaved => "INTERNAL_ERROR",
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error, code) = match self {
-            Self::PayloadTooLarge => (
-                StatusCode::PAYLOAD_TOO_LARGE,
-                format!("Text exceeds maximum size of {MAX_TEXT_SIZE} bytes"),
-                "PAYLOAD_TOO_LARGE".to_string(),
+        let mut limit = None;
+        let mut field = None;
+        let mut retry_after = None;
+
+        let code = self.code();
+        let (status, error) = match self {
+            Self::PayloadTooLarge { limit: max } => {
+                limit = Some(max);
+                (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Text exceeds maximum size of {max} bytes"),
+                )
+            }
+            Self::BodyTooLarge { limit: max } => {
+                limit = Some(max);
+                (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Request body exceeds maximum size of {max} bytes"),
+                )
+            }
+            Self::InvalidJson {
+                message,
+                field: path,
+            } => {
+                field = path;
+                (StatusCode::BAD_REQUEST, format!("Invalid JSON: {message}"))
+            }
+            Self::MissingField { field: path } => {
+                let error = format!("Missing required field `{path}`");
+                field = Some(path);
+                (StatusCode::UNPROCESSABLE_ENTITY, error)
+            }
+            Self::InvalidField {
+                message,
+                field: path,
+            } => {
+                field = Some(path);
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Invalid field: {message}"),
+                )
+            }
+            Self::UnsupportedMediaType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected request with `Content-Type: application/json`".to_string(),
+            ),
+            Self::NotFound => (
+                StatusCode::NOT_FOUND,
+                "No route matches the request path".to_string(),
+            ),
+            Self::MethodNotAllowed => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for this route".to_string(),
             ),
             Self::Unauthorized => (
                 StatusCode::UNAUTHORIZED,
                 "Invalid or missing API key".to_string(),
-                "UNAUTHORIZED".to_string(),
             ),
+            Self::Forbidden => (
+                StatusCode::FORBIDDEN,
+                "API key is not allowed to use this endpoint".to_string(),
+            ),
+            Self::QuotaExceeded {
+                period,
+                limit: max,
+                retry_after_secs,
+            } => {
+                limit = usize::try_from(max).ok();
+                retry_after = Some(retry_after_secs);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    format!("{} usage quota of {max} exceeded", period.as_str()),
+                )
+            }
+            Self::RateLimited { retry_after_secs } => {
+                retry_after = Some(retry_after_secs);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "Too many requests, please slow down".to_string(),
+                )
+            }
+            Self::Overloaded => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Server is busy, please retry later".to_string(),
+            ),
+            Self::Starting => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Server is starting, please retry later".to_string(),
+            ),
+            Self::Internal => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error while checking text".to_string(),
+            ),
+            Self::FeedbackNotSaved => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Feedback could not be saved, please retry".to_string(),
+            ),
+        };
+
+        let info = ErrorInfo {
+            code,
+            message: error.clone(),
+        };
+        let body = Json(ApiError {
+            error,
+            code: code.to_string(),
+            limit,
+            field,
+        });
+
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            retry_after = Some(RETRY_AFTER_SECS);
+        }
+
+        let mut response = if let Some(secs) = retry_after {
+            let retry_after = [(header::RETRY_AFTER, secs.to_string())];
+            (status, retry_after, body).into_response()
+        } else {
+            (status, body).into_response()
         };
+        response.extensions_mut().insert(info);
+        response
+    }
+}
 
-        (status, Json(ApiError { error, code })).into_response()
+/// Code and message of an error response, kept in its extensions so the
+/// gRPC service can report the error in its own format.
+#[derive(Debug, Clone)]
+struct ErrorInfo {
+    code: &'static str,
+    message: String,
+}
+
+/// Byte index of the character at `char_idx` in `text`, seeking from
+/// `section` rather than from the start of the text.
+fn byte_index(text: &str, section: &Segment<'_>, char_idx: usize) -> usize {
+    if char_idx >= section.char_offset {
+        text[section.byte_offset..]
+            .char_indices()
+            .nth(char_idx - section.char_offset)
+            .map_or(text.len(), |(i, _)| section.byte_offset + i)
+    } else {
+        text[..section.byte_offset]
+            .char_indices()
+            .rev()
+            .nth(section.char_offset - char_idx - 1)
+            .map_or(0, |(i, _)| i)
     }
 }
 
-fn get_context(text: &str, span: Span) -> Context {
+fn get_context(text: &str, section: &Segment<'_>, span: Span) -> Context {
     let start = span.start.saturating_sub(20);
-    let end = (span.end + 20).min(text.len());
+    let end = span.end + 20;
 
-    let context_text: String = text.chars().skip(start).take(end - start).collect();
+    let start_byte = byte_index(text, section, start);
+    let context_text: String = text[start_byte..].chars().take(end - start).collect();
 
     Context {
         text: context_text,
@@ -167,134 +554,738 @@ fn get_context(text: &str, span: Span) -> Context {
     }
 }
 
+fn lint_to_match(text: &str, section: &Segment<'_>, lint: harper_core::linting::Lint) -> Match {
+    let span = lint.span;
+    let suggestions: Vec<String> = lint
+        .suggestions
+        .into_iter()
+        .filter_map(|s| {
+            if let harper_core::linting::Suggestion::ReplaceWith(chars) = s {
+                Some(chars.into_iter().collect())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let category = if lint.lint_kind.to_string().to_lowercase().contains("spell") {
+        "spelling"
+    } else {
+        "grammar"
+    };
+
+    Match {
+        message: lint.message,
+        offset: span.start,
+        length: span.len(),
+        replacements: suggestions,
+        rule: Rule {
+            id: lint.lint_kind.to_string(),
+            category: category.to_string(),
+            severity: None,
+        },
+        context: get_context(text, section, span),
+    }
+}
+
+fn custom_lint_to_match(text: &str, section: &Segment<'_>, lint: CustomLint<'_>) -> Match {
+    let mut span = lint.span;
+    span.push_by(section.char_offset);
+    Match {
+        message: lint.message.to_string(),
+        offset: span.start,
+        length: span.len(),
+        replacements: lint.replacement.into_iter().collect(),
+        rule: Rule {
+            id: lint.rule_id.to_string(),
+            category: "style".to_string(),
+            severity: Some(lint.severity),
+        },
+        context: get_context(text, section, span),
+    }
+}
+
+/// Matches found in a text, possibly cut short by a deadline.
+struct LintOutcome {
+    matches: Vec<Match>,
+    partial_reason: Option<&'static str>,
+    /// Characters linted before the deadline; for a section, the length
+    /// of the prefix it got through.
+    checked_chars: usize,
+}
+
+/// Lints one section paragraph by paragraph with a pooled linter,
+/// stopping before the first paragraph for which `expired` holds.
+fn lint_section(
+    linters: &LinterPool,
+    text: &str,
+    section: &Segment<'_>,
+    dialect: Dialect,
+    expired: &impl Fn() -> bool,
+    parent: &tracing::Span,
+) -> LintOutcome {
+    let mut matches = Vec::new();
+    let mut linter = None;
+
+    for paragraph in split_paragraphs(section.text) {
+        if expired() {
+            return LintOutcome {
+                matches,
+                partial_reason: Some(DEADLINE_EXCEEDED),
+                checked_chars: paragraph.char_offset,
+            };
+        }
+        let paragraph = Segment {
+            text: paragraph.text,
+            char_offset: section.char_offset + paragraph.char_offset,
+            byte_offset: section.byte_offset + paragraph.byte_offset,
+        };
+
+        let text_length = telemetry::count(paragraph.text.len());
+        let document = info_span!(parent: parent, "parse", text_length)
+            .in_scope(|| Document::new(paragraph.text, &PlainEnglish, linters.dictionary()));
+        let span = info_span!(parent: parent, "lint", text_length, matches = tracing::field::Empty);
+        let lints = span.in_scope(|| {
+            let linter = linter.get_or_insert_with(|| linters.checkout(dialect));
+            linter.lint(&document)
+        });
+        span.record("matches", telemetry::count(lints.len()));
+
+        matches.extend(lints.into_iter().map(|mut lint| {
+            lint.span.push_by(paragraph.char_offset);
+            lint_to_match(text, &paragraph, lint)
+        }));
+    }
+
+    LintOutcome {
+        matches,
+        partial_reason: None,
+        checked_chars: section.text.chars().count(),
+    }
+}
+
+/// Lints `text` in sections, in parallel when there is more than one,
+/// stopping each section once `deadline` passes, then runs the custom
+/// rules once over the whole text, so their patterns can span sections
+/// and paragraphs. Blocking; run through [`LintExecutor`].
+fn lint_text(
+    linters: &LinterPool,
+    custom: &CustomRules,
+    text: &str,
+    dialect: Dialect,
+    deadline: Instant,
+) -> LintOutcome {
+    let sections = split_sections(text, SECTION_CHARS);
+    // Rayon's workers do not inherit the current span.
+    let parent = tracing::Span::current();
+    let expired = || Instant::now() >= deadline;
+    let lint =
+        |section: &Segment<'_>| lint_section(linters, text, section, dialect, &expired, &parent);
+
+    let results: Vec<LintOutcome> = if sections.len() > 1 {
+        sections.par_iter().map(lint).collect()
+    } else {
+        sections.iter().map(lint).collect()
+    };
+
+    let mut partial = results.iter().any(|r| r.partial_reason.is_some());
+    let checked_chars = checked_chars(&sections, &results);
+    let mut matches: Vec<Match> = results.into_iter().flat_map(|r| r.matches).collect();
+
+    if !custom.is_empty() {
+        if expired() {
+            partial = true;
+        } else {
+            let whole = Segment {
+                text,
+                char_offset: 0,
+                byte_offset: 0,
+            };
+            let text_length = telemetry::count(text.len());
+            let span = info_span!(parent: &parent, "custom_rules", text_length, matches = tracing::field::Empty);
+            let lints = span.in_scope(|| custom.lint(text));
+            span.record("matches", telemetry::count(lints.len()));
+            matches.extend(
+                lints
+                    .into_iter()
+                    .map(|lint| custom_lint_to_match(text, &whole, lint)),
+            );
+        }
+    }
+
+    // Sections overlap where a paragraph was cut, so the same lint can be
+    // reported by both neighbours.
+    matches.sort_by(|a, b| {
+        (a.offset, a.length, &a.rule.id, &a.message)
+            .cmp(&(b.offset, b.length, &b.rule.id, &b.message))
+    });
+    matches.dedup_by(|a, b| {
+        a.offset == b.offset
+            && a.length == b.length
+            && a.rule.id == b.rule.id
+            && a.message == b.message
+    });
+
+    LintOutcome {
+        matches,
+        partial_reason: partial.then_some(DEADLINE_EXCEEDED),
+        checked_chars,
+    }
+}
+
+/// Characters covered by the prefixes `results` got through of their
+/// `sections`, counting the overlap between neighbours once.
+fn checked_chars(sections: &[Segment<'_>], results: &[LintOutcome]) -> usize {
+    let mut total = 0;
+    let mut covered_to = 0;
+    for (section, result) in sections.iter().zip(results) {
+        let start = section.char_offset.max(covered_to);
+        let end = section.char_offset + result.checked_chars;
+        total += end.saturating_sub(start);
+        covered_to = covered_to.max(end);
+    }
+    total
+}
+
 async fn check_text(
     State(state): State<AppState>,
-    Json(payload): Json<CheckRequest>,
-) -> Result<Json<CheckResponse>, AppError> {
+    identity: Option<Extension<Identity>>,
+    scope: Option<Extension<AuditScope>>,
+    ApiJson(payload): ApiJson<CheckRequest>,
+) -> Result<Response, AppError> {
+    let text_length = payload.text.len();
+    // The check records itself, failed or not.
+    if let Some(scope) = scope {
+        scope.mark_recorded();
+    }
+    let response = run_check(&state, identity.as_deref(), payload, "check").await?;
+    record_check(text_length, response.matches.len());
+
+    let _span = info_span!(
+        "serialize",
+        matches = telemetry::count(response.matches.len())
+    )
+    .entered();
+    Ok(Json(response).into_response())
+}
+
+/// Checks `payload` for `caller`, counting it against the caller's quota
+/// and recording it in the audit log. Shared by the HTTP and gRPC APIs;
+/// `endpoint` labels the metrics.
+async fn run_check(
+    state: &AppState,
+    caller: Option<&Identity>,
+    payload: CheckRequest,
+    endpoint: &'static str,
+) -> Result<CheckResponse, AppError> {
+    let audit = state
+        .audit
+        .entry(&caller_name(caller), endpoint, &payload.text);
+    let result = check(state, caller, payload, endpoint).await;
+    if let Some(audit) = audit {
+        match &result {
+            Ok(response) => {
+                audit
+                    .finish(
+                        if response.partial { "partial" } else { "ok" },
+                        Some(response.matches.len()),
+                    )
+                    .await;
+            }
+            Err(error) => audit.finish(error.code(), None).await,
+        }
+    }
+    result
+}
+
+async fn check(
+    state: &AppState,
+    caller: Option<&Identity>,
+    payload: CheckRequest,
+    endpoint: &'static str,
+) -> Result<CheckResponse, AppError> {
     let start = Instant::now();
+    let key = usage_key(caller);
+    histogram!(TEXT_SIZE_HISTOGRAM, "endpoint" => endpoint).record(payload.text.len() as f64);
 
     // Validate input size
-    if payload.text.len() > MAX_TEXT_SIZE {
+    if payload.text.len() > state.limits.max_text_size {
         counter!("api.errors", "type" => "payload_too_large").increment(1);
-        return Err(AppError::PayloadTooLarge);
+        return Err(AppError::PayloadTooLarge {
+            limit: state.limits.max_text_size,
+        });
     }
 
-    let parser = PlainEnglish;
-    let document = Document::new_curated(&payload.text, &parser);
+    let characters = payload.text.chars().count() as u64;
+    let recorded = SystemTime::now();
+    if let Err(exceeded) =
+        state
+            .usage
+            .record(&key, characters, &quota_limits(state, caller), recorded)
+    {
+        counter!("api.errors", "type" => "quota_exceeded", "period" => exceeded.period.as_str())
+            .increment(1);
+        return Err(exceeded.into());
+    }
 
-    let mut linter = LintGroup::new_curated(state.dictionary.clone(), Dialect::American);
-    let lints = linter.lint(&document);
+    let timeout = payload.timeout_ms.map_or(state.check_timeout, |ms| {
+        Duration::from_millis(ms).min(state.check_timeout)
+    });
+    let deadline = start + timeout;
 
-    let matches: Vec<Match> = lints
-        .into_iter()
-        .map(|lint| {
-            let span = lint.span;
-            let suggestions: Vec<String> = lint
-                .suggestions
-                .into_iter()
-                .filter_map(|s| {
-                    if let harper_core::linting::Suggestion::ReplaceWith(chars) = s {
-                        Some(chars.into_iter().collect())
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+    let linters = state.linters.clone();
+    let custom = state.custom_rules.clone();
+    let dialect = payload.dialect.unwrap_or(Dialect::American);
+    let outcome = match state
+        .executor
+        .run(move || lint_text(&linters, &custom, &payload.text, dialect, deadline))
+        .await
+    {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            // Nothing was checked, so the request does not count.
+            state.usage.refund(&key, 1, characters, recorded);
+            return Err(e.into());
+        }
+    };
+    if outcome.partial_reason.is_some() {
+        // Only the characters checked before the deadline count.
+        let unchecked = characters.saturating_sub(outcome.checked_chars as u64);
+        state.usage.refund(&key, 0, unchecked, recorded);
+    }
+    let mut matches = outcome.matches;
+    let mut partial_reason = outcome.partial_reason;
 
-            let category = if lint.lint_kind.to_string().to_lowercase().contains("spell") {
-                "spelling"
-            } else {
-                "grammar"
-            };
+    if matches.len() > state.limits.max_matches {
+        matches.truncate(state.limits.max_matches);
+        partial_reason.get_or_insert(TOO_MANY_MATCHES);
+    }
 
-            Match {
-                message: lint.message,
-                offset: span.start,
-                length: span.len(),
-                replacements: suggestions,
-                rule: Rule {
-                    id: lint.lint_kind.to_string(),
-                    category: category.to_string(),
-                },
-                context: get_context(&payload.text, span),
-            }
-        })
-        .collect();
+    if let Some(reason) = partial_reason {
+        counter!("api.partial_results", "reason" => reason).increment(1);
+    }
 
     let elapsed = start.elapsed();
     let elapsed_ms = elapsed.as_millis();
 
     // Record metrics
-    counter!("api.requests", "endpoint" => "check").increment(1);
-    histogram!("api.request_duration_ms", "endpoint" => "check").record(elapsed_ms as f64);
+    let (key, tenant) = caller_labels(caller);
+    counter!("api.requests", "endpoint" => endpoint, "key" => key, "tenant" => tenant).increment(1);
+    histogram!("api.request_duration_ms", "endpoint" => endpoint).record(elapsed_ms as f64);
     counter!("api.matches_found").increment(matches.len() as u64);
+    record_rule_matches(&matches);
 
-    let response = CheckResponse {
+    Ok(CheckResponse {
         matches,
+        partial: partial_reason.is_some(),
+        reason: partial_reason.map(str::to_string),
         metrics: Metrics {
             processing_time_ms: elapsed_ms,
         },
+    })
+}
+
+/// Usage key of `caller`: its key name or token subject, prefixed by the
+/// kind of credential.
+fn usage_key(caller: Option<&Identity>) -> String {
+    caller.map_or_else(|| ANONYMOUS_KEY.to_string(), Identity::usage_key)
+}
+
+/// Name of `caller` in usage responses, audit and feedback records.
+fn caller_name(caller: Option<&Identity>) -> String {
+    caller.map_or_else(|| ANONYMOUS_KEY.to_string(), |i| i.name.clone())
+}
+
+/// Quota limits of `caller`: API keys by name, other callers by their
+/// prefixed usage key.
+fn quota_limits(state: &AppState, caller: Option<&Identity>) -> QuotaLimits {
+    match caller {
+        Some(identity) if identity.credential == Credential::ApiKey => {
+            state.quota.limits_for(&identity.name)
+        }
+        _ => state.quota.limits_for(&usage_key(caller)),
+    }
+}
+
+/// The `key` and `tenant` metric labels of `caller`, both bounded by
+/// configuration rather than by the number of callers.
+fn caller_labels(caller: Option<&Identity>) -> (String, String) {
+    caller.map_or_else(
+        || (ANONYMOUS_KEY.to_string(), String::new()),
+        |i| {
+            (
+                i.metric_label().to_string(),
+                i.tenant.clone().unwrap_or_default(),
+            )
+        },
+    )
+}
+
+/// Counts `matches` by rule and category in `api.rule_matches`.
+fn record_rule_matches(matches: &[Match]) {
+    let mut counts: BTreeMap<(&str, &str), u64> = BTreeMap::new();
+    for m in matches {
+        *counts.entry((&m.rule.id, &m.rule.category)).or_default() += 1;
+    }
+    for ((rule, category), count) in counts {
+        counter!(
+            "api.rule_matches",
+            "rule" => rule.to_string(),
+            "category" => category.to_string()
+        )
+        .increment(count);
+    }
+}
+
+/// Response from the usage endpoint.
+#[derive(Debug, Serialize)]
+pub struct UsageResponse {
+    /// Key or token subject the usage belongs to.
+    key: String,
+    /// Usage in the current UTC day.
+    daily: UsageReport,
+    /// Usage in the current calendar month.
+    monthly: UsageReport,
+}
+
+/// Usage and limits for one quota period.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    /// The period, as `YYYY-MM-DD` or `YYYY-MM`.
+    period: String,
+    /// Check requests made.
+    requests: u64,
+    /// Characters checked.
+    characters: u64,
+    /// Request quota, or `null` when unlimited.
+    #[serde(rename = "requestLimit")]
+    request_limit: Option<u64>,
+    /// Character quota, or `null` when unlimited.
+    #[serde(rename = "characterLimit")]
+    character_limit: Option<u64>,
+}
+
+async fn get_usage(
+    State(state): State<AppState>,
+    identity: Option<Extension<Identity>>,
+) -> Json<UsageResponse> {
+    counter!("api.requests", "endpoint" => "usage").increment(1);
+
+    let caller = identity.as_deref();
+    let usage = state.usage.usage(&usage_key(caller), SystemTime::now());
+    let limits = quota_limits(&state, caller);
+
+    Json(UsageResponse {
+        daily: UsageReport {
+            period: usage.daily.period,
+            requests: usage.daily.requests,
+            characters: usage.daily.characters,
+            request_limit: limits.daily_requests,
+            character_limit: limits.daily_characters,
+        },
+        monthly: UsageReport {
+            period: usage.monthly.period,
+            requests: usage.monthly.requests,
+            characters: usage.monthly.characters,
+            request_limit: limits.monthly_requests,
+            character_limit: limits.monthly_characters,
+        },
+        key: caller_name(caller),
+    })
+}
+
+/// Whether `id` names a rule that matches can come from: one of Harper's
+/// lint kinds or a loaded custom rule.
+fn is_known_rule(state: &AppState, id: &str) -> bool {
+    LINT_KINDS.iter().any(|kind| kind.to_string() == id)
+        || state
+            .custom_rules
+            .descriptions()
+            .any(|(rule, _)| rule == id)
+}
+
+/// Request payload for the feedback endpoint.
+#[derive(Debug, Deserialize)]
+pub struct FeedbackRequest {
+    /// Id of the rule behind the match, as in `rule.id` of the match.
+    rule: String,
+    /// What the writer thought of the match.
+    verdict: Verdict,
+    /// Text around the match, redacted by the client.
+    #[serde(default)]
+    context: Option<String>,
+}
+
+async fn post_feedback(
+    State(state): State<AppState>,
+    identity: Option<Extension<Identity>>,
+    ApiJson(payload): ApiJson<FeedbackRequest>,
+) -> Result<StatusCode, AppError> {
+    counter!("api.requests", "endpoint" => "feedback").increment(1);
+
+    let FeedbackRequest {
+        rule,
+        verdict,
+        context,
+    } = payload;
+    let invalid = |field: &str, message: String| {
+        counter!("api.errors", "type" => "invalid_field").increment(1);
+        AppError::InvalidField {
+            message,
+            field: field.to_string(),
+        }
     };
+    if !is_known_rule(&state, &rule) {
+        return Err(invalid(
+            "rule",
+            "rule must be the id of a Harper rule or a loaded custom rule".to_string(),
+        ));
+    }
+    let max_context = state.max_feedback_context;
+    if context
+        .as_ref()
+        .is_some_and(|context| context.chars().count() > max_context)
+    {
+        return Err(invalid(
+            "context",
+            format!("context must be at most {max_context} characters"),
+        ));
+    }
 
-    Ok(Json(response))
+    let record = FeedbackRecord {
+        time: calendar::timestamp(SystemTime::now()),
+        key: caller_name(identity.as_deref()),
+        rule,
+        verdict,
+        // Context is submitted text, which privacy mode does not keep.
+        context: context.filter(|_| !state.privacy),
+    };
+    if let Err(e) = state.feedback.record(&record) {
+        counter!("feedback.errors").increment(1);
+        tracing::error!("Failed to save feedback: {}", e);
+        return Err(AppError::FeedbackNotSaved);
+    }
+    counter!("api.feedback", "verdict" => verdict.as_str()).increment(1);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Response from the feedback summary endpoint.
+#[derive(Debug, Serialize)]
+pub struct FeedbackSummaryResponse {
+    /// Reports received for all rules.
+    reports: u64,
+    /// Reports per rule, highest false-positive rate first.
+    rules: Vec<RuleFeedback>,
+}
+
+async fn get_feedback_summary(State(state): State<AppState>) -> Json<FeedbackSummaryResponse> {
+    counter!("api.requests", "endpoint" => "feedback_summary").increment(1);
+
+    let rules = state.feedback.summary();
+    Json(FeedbackSummaryResponse {
+        reports: rules.iter().map(|rule| rule.reports).sum(),
+        rules,
+    })
+}
+
+async fn not_found() -> AppError {
+    counter!("api.errors", "type" => "not_found").increment(1);
+    AppError::NotFound
 }
 
-async fn health() -> &'static str {
-    counter!("api.requests", "endpoint" => "health").increment(1);
-    "ok"
+async fn method_not_allowed() -> AppError {
+    counter!("api.errors", "type" => "method_not_allowed").increment(1);
+    AppError::MethodNotAllowed
 }
 
 async fn metrics_handler(State(state): State<AppState>) -> String {
     state.metrics_handle.render()
 }
 
-async fn auth_middleware(
+/// Counts responses in `api.responses` by route and status, including
+/// rejections by auth and rate limiting, and requests being handled in
+/// `api.requests_in_flight`.
+async fn track_responses(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let _in_flight = InFlight::start();
+    let response = next.run(request).await;
+    counter!(
+        "api.responses",
+        "route" => route,
+        "status" => response.status().as_str().to_string()
+    )
+    .increment(1);
+    response
+}
+
+/// Counts a request in `api.requests_in_flight` until dropped, so requests
+/// whose client went away are counted out too.
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        gauge!("api.requests_in_flight").increment(1.0);
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        gauge!("api.requests_in_flight").decrement(1.0);
+    }
+}
+
+/// Rejects requests whose declared `Content-Length` exceeds the body limit
+/// before any of the body is read.
+async fn body_limit_middleware(
     State(state): State<AppState>,
     request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    // Skip auth for health and metrics endpoints
-    let path = request.uri().path();
-    if path == "/health" || path == "/metrics" {
-        return Ok(next.run(request).await);
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.parse::<usize>().ok());
+
+    if content_length.is_some_and(|len| len > state.limits.body_limit()) {
+        counter!("api.errors", "type" => "payload_too_large").increment(1);
+        return Err(AppError::BodyTooLarge {
+            limit: state.limits.body_limit(),
+        });
     }
 
-    // If no API key is configured, allow all requests
-    let Some(expected_key) = &state.api_key else {
+    Ok(next.run(request).await)
+}
+
+async fn auth_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let path = request.uri().path();
+    let auth_configured = state.keys.is_some() || state.jwt.is_some();
+    let certificate = request
+        .extensions()
+        .get::<ClientCertificate>()
+        .map(|c| c.identity.clone());
+
+    // If neither API keys, JWTs nor client certificates are in use, allow
+    // all requests
+    if !auth_configured && certificate.is_none() {
         return Ok(next.run(request).await);
-    };
+    }
 
-    // Check for API key in header
-    let auth_header = request
+    // Check for a bearer token in the header
+    let token = request
         .headers()
         .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok());
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .filter(|_| auth_configured);
 
-    match auth_header {
-        Some(header_val) if header_val.starts_with("Bearer ") => {
-            let provided_key = &header_val[7..];
-            if provided_key == expected_key {
-                Ok(next.run(request).await)
-            } else {
-                counter!("api.errors", "type" => "unauthorized").increment(1);
-                Err(AppError::Unauthorized)
-            }
+    // A bearer token takes precedence over the client certificate.
+    let identity = match (token.map(|token| authenticate(&state, token)), certificate) {
+        (Some(Ok(identity)), _) | (None, Some(identity)) => identity,
+        (Some(Err(reason)), _) => {
+            counter!("api.errors", "type" => "unauthorized", "reason" => reason).increment(1);
+            return Err(AppError::Unauthorized);
         }
-        _ => {
-            counter!("api.errors", "type" => "unauthorized").increment(1);
-            Err(AppError::Unauthorized)
+        (None, None) => {
+            counter!("api.errors", "type" => "unauthorized", "reason" => "missing").increment(1);
+            return Err(AppError::Unauthorized);
         }
+    };
+
+    if let Some(audit) = request.extensions().get::<AuditScope>() {
+        audit.set_caller(&identity.name);
     }
+    if required_scope(path).is_some_and(|scope| !identity.has_scope(scope)) {
+        let (key, tenant) = caller_labels(Some(&identity));
+        counter!("api.errors", "type" => "forbidden", "key" => key, "tenant" => tenant)
+            .increment(1);
+        return Err(AppError::Forbidden);
+    }
+
+    tracing::Span::current().record("key", identity.name.as_str());
+    request.extensions_mut().insert(identity);
+    Ok(next.run(request).await)
 }
 
-fn build_cors_layer() -> CorsLayer {
-    let allowed_origins = env::var("CORS_ORIGINS").unwrap_or_default();
+/// Records check and feedback requests that end without a check being
+/// recorded: those turned away by auth, rate limits or validation, and
+/// feedback. Runs outside all of them.
+async fn audit_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let endpoint = match request.uri().path() {
+        "/v1/check" => Some("check"),
+        "/v1/feedback" => Some("feedback"),
+        path => grpc::check_endpoint(path),
+    };
+    let Some(endpoint) = endpoint.filter(|_| state.audit.is_enabled()) else {
+        return next.run(request).await;
+    };
+
+    let scope = AuditScope::default();
+    request.extensions_mut().insert(scope.clone());
+    let response = next.run(request).await;
+
+    // Checks record themselves; streamed ones after the response is sent.
+    let status = response.status();
+    if scope.is_recorded() || (status.is_success() && endpoint != "feedback") {
+        return response;
+    }
+    let outcome = match response.extensions().get::<ErrorInfo>() {
+        Some(info) => info.code,
+        None if status.is_success() => "ok",
+        None => status.as_str(),
+    };
+    let caller = scope.caller().unwrap_or_else(|| ANONYMOUS_KEY.to_string());
+    if let Some(entry) = state.audit.request_entry(&caller, endpoint) {
+        entry.finish(outcome, None).await;
+    }
+    response
+}
+
+/// Resolves the caller for a bearer `token`, trying it as a JWT first
+/// when JWTs are enabled, or returns the reason it was rejected.
+fn authenticate(state: &AppState, token: &str) -> Result<Identity, &'static str> {
+    if let Some(jwt) = state.jwt.as_ref().filter(|_| jwt::looks_like_jwt(token)) {
+        return jwt.verify(token).map_err(|e| match e {
+            JwtError::Expired => "expired",
+            JwtError::Invalid => "invalid",
+        });
+    }
+
+    let keys = state.keys.as_ref().ok_or("invalid")?;
+    keys.authenticate(token, SystemTime::now())
+        .cloned()
+        .map_err(|e| match e {
+            KeyError::Expired => "expired",
+            KeyError::Unknown => "invalid",
+        })
+}
+
+/// Scope a key needs to call the route at `path`, if any.
+fn required_scope(path: &str) -> Option<&'static str> {
+    (path == "/v1/check" || path == "/v1/feedback" || grpc::is_check_method(path))
+        .then_some(SCOPE_CHECK)
+}
 
-    if allowed_origins.is_empty() || allowed_origins == "*" {
+fn build_cors_layer(config: &CorsConfig) -> CorsLayer {
+    if config.is_permissive() {
         CorsLayer::permissive()
     } else {
-        let origins: Vec<_> = allowed_origins
-            .split(',')
+        let origins: Vec<_> = config
+            .origins
+            .iter()
             .filter_map(|s| s.trim().parse().ok())
             .collect();
 
@@ -305,124 +1296,445 @@ fn build_cors_layer() -> CorsLayer {
     }
 }
 
-fn get_rate_limit_config() -> (u64, u32) {
-    let rps: u64 = env::var("RATE_LIMIT_PER_SECOND")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(DEFAULT_RATE_LIMIT_PER_SECOND);
-
-    let burst: u32 = env::var("RATE_LIMIT_BURST")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(DEFAULT_RATE_LIMIT_BURST);
-
-    (rps, burst)
-}
-
 /// Global metrics handle for sharing across app instances.
 static METRICS_HANDLE: std::sync::OnceLock<PrometheusHandle> = std::sync::OnceLock::new();
 
-fn get_or_init_metrics() -> PrometheusHandle {
+fn get_or_init_metrics(config: &MetricsConfig) -> PrometheusHandle {
     METRICS_HANDLE
-        .get_or_init(|| {
-            metrics_exporter_prometheus::PrometheusBuilder::new()
-                .install_recorder()
-                .unwrap_or_else(|_| {
-                    // Recorder already installed, just build a new handle
-                    metrics_exporter_prometheus::PrometheusBuilder::new()
-                        .build_recorder()
-                        .handle()
-                })
-        })
+        .get_or_init(|| install_recorder(None, config))
         .clone()
 }
 
-/// Creates the application router with all middleware configured.
-pub fn create_app() -> Router {
-    let enable_rate_limiting = env::var("DISABLE_RATE_LIMITING")
-        .map(|v| v != "true" && v != "1")
-        .unwrap_or(true);
-    create_app_internal(enable_rate_limiting)
+/// Installs the global metrics recorder, exporting to `otlp` as well as
+/// Prometheus. Returns `false` if metrics were already set up.
+pub(crate) fn init_metrics_with(otlp: telemetry::OtlpRecorder, config: &MetricsConfig) -> bool {
+    let mut installed = false;
+    METRICS_HANDLE.get_or_init(|| {
+        installed = true;
+        install_recorder(Some(otlp), config)
+    });
+    installed
 }
 
-/// Creates the application router for testing (without rate limiting).
-pub fn create_app_for_testing() -> Router {
-    create_app_internal(false)
+fn install_recorder(
+    otlp: Option<telemetry::OtlpRecorder>,
+    config: &MetricsConfig,
+) -> PrometheusHandle {
+    let prometheus = histogram_buckets(config)
+        .into_iter()
+        .try_fold(PrometheusBuilder::new(), |builder, (name, buckets)| {
+            builder.set_buckets_for_metric(Matcher::Full(name.to_string()), &buckets)
+        })
+        .unwrap_or_else(|e| {
+            tracing::warn!("Invalid histogram buckets, reporting summaries: {}", e);
+            PrometheusBuilder::new()
+        })
+        .build_recorder();
+    let handle = prometheus.handle();
+    let installed = match otlp {
+        Some(otlp) => metrics::set_global_recorder(
+            FanoutBuilder::default()
+                .add_recorder(prometheus)
+                .add_recorder(otlp)
+                .build(),
+        )
+        .is_ok(),
+        None => metrics::set_global_recorder(prometheus).is_ok(),
+    };
+    if !installed {
+        // Another recorder is installed; the handle renders nothing.
+        tracing::debug!("Metrics recorder already installed");
+    }
+    handle
+}
+
+/// Configured buckets of each histogram, by metric name. Fixed once the
+/// recorder is installed.
+pub(crate) fn histogram_buckets(config: &MetricsConfig) -> HashMap<&'static str, Vec<f64>> {
+    let bounds = |buckets: &[u64]| buckets.iter().map(|&b| b as f64).collect::<Vec<_>>();
+    let mut buckets: HashMap<_, _> = DURATION_HISTOGRAMS
+        .into_iter()
+        .map(|name| (name, bounds(&config.duration_buckets_ms)))
+        .collect();
+    buckets.insert(TEXT_SIZE_HISTOGRAM, bounds(&config.text_size_buckets_bytes));
+    buckets
 }
 
-fn create_app_internal(enable_rate_limiting: bool) -> Router {
-    let metrics_handle = get_or_init_metrics();
+/// Creates the application router from a validated configuration.
+///
+/// Fails if a custom word list, the custom rules, the key store or saved
+/// usage cannot be read.
+pub fn create_app(config: &Config) -> Result<Router, ConfigError> {
+    create_routers(config).map(|routers| routers.public)
+}
 
-    let dictionary = FstDictionary::curated();
-    let api_key = env::var("API_KEY").ok().filter(|k| !k.is_empty());
+/// The routers served on the main and admin listeners.
+#[derive(Debug, Clone)]
+pub struct AppRouters {
+    /// The check APIs, plus health and metrics when no admin listener is
+    /// configured and the gRPC service when it shares the main port.
+    pub public: Router,
+    /// Health, metrics and admin endpoints, without authentication.
+    pub admin: Router,
+    /// The gRPC service, for a dedicated gRPC listener.
+    pub grpc: Router,
+}
 
-    let state = AppState {
+/// Like [`create_app`], also returning the admin router.
+pub fn create_routers(config: &Config) -> Result<AppRouters, ConfigError> {
+    create_routers_with_usage(
+        config,
+        open_usage(config)?,
+        Health::new(),
+        AuditLog::open(&config.audit)?,
+        open_feedback(config)?,
+        new_executor(&config.lint),
+        new_rate_limiter(&config.rate_limit),
+    )
+}
+
+/// Creates the lint executor configured by `config`.
+pub fn new_executor(config: &LintConfig) -> LintExecutor {
+    LintExecutor::new(config.max_concurrency, config.queue_depth)
+}
+
+/// Creates the rate limiter configured by `config`, or `None` when rate
+/// limiting is disabled.
+pub fn new_rate_limiter(config: &RateLimitConfig) -> Option<Arc<RateLimiter>> {
+    config.enabled.then(|| Arc::new(RateLimiter::new(config)))
+}
+
+/// Opens the usage tracker configured by `config`.
+pub fn open_usage(config: &Config) -> Result<UsageTracker, ConfigError> {
+    config
+        .quota
+        .state_file
+        .as_deref()
+        .map_or_else(|| Ok(UsageTracker::in_memory()), UsageTracker::open)
+}
+
+/// Opens the feedback store configured by `config`.
+pub fn open_feedback(config: &Config) -> Result<FeedbackStore, ConfigError> {
+    config
+        .feedback
+        .file
+        .as_deref()
+        .map_or_else(|| Ok(FeedbackStore::in_memory()), FeedbackStore::open)
+}
+
+/// Like [`create_routers`], sharing state kept across reloads.
+///
+/// Usage is counted in `usage`, readiness reported from `health`, checks
+/// recorded in `audit` and feedback in `feedback`. Checks run through
+/// `executor` and requests are limited by `rate_limiter`, so work and
+/// spent rate limits from before a reload still count.
+pub fn create_routers_with_usage(
+    config: &Config,
+    usage: UsageTracker,
+    health: Health,
+    audit: AuditLog,
+    feedback: FeedbackStore,
+    executor: LintExecutor,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> Result<AppRouters, ConfigError> {
+    let dictionary = load_dictionary(&config.dictionary.word_lists)?;
+    let custom_rules = CustomRules::load(&config.lint)?;
+    let keys = KeyStore::load(&config.auth)?;
+    let jwt = config
+        .auth
+        .jwt
+        .as_ref()
+        .map(JwtVerifier::load)
+        .transpose()?;
+    Ok(build_app(
+        config,
         dictionary,
-        api_key,
+        custom_rules,
+        keys,
+        jwt,
+        usage,
+        health,
+        audit,
+        feedback,
+        executor,
+        rate_limiter,
+    ))
+}
+
+fn build_app(
+    config: &Config,
+    dictionary: Arc<MergedDictionary>,
+    custom_rules: CustomRules,
+    keys: Option<KeyStore>,
+    jwt: Option<JwtVerifier>,
+    usage: UsageTracker,
+    health: Health,
+    audit: AuditLog,
+    feedback: FeedbackStore,
+    executor: LintExecutor,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> AppRouters {
+    let metrics_handle = get_or_init_metrics(&config.metrics);
+    let dictionary_words = dictionary.word_count();
+
+    let limits = config.limits;
+    // Each running check may fan out across the rayon pool as well.
+    let max_idle = executor.max_concurrency() + rayon::current_num_threads();
+    let linters = if config.privacy.enabled {
+        LinterPool::single_use(dictionary, &[Dialect::American], max_idle)
+    } else {
+        LinterPool::new(dictionary, &[Dialect::American], max_idle)
+    };
+
+    let state = AppState {
+        linters,
+        custom_rules,
+        executor,
+        check_timeout: Duration::from_millis(config.lint.timeout_ms),
+        limits,
+        keys: keys.map(Arc::new),
+        jwt: jwt.map(Arc::new),
+        usage,
+        quota: Arc::new(config.quota.clone()),
+        rate_limiter,
+        health,
+        dictionary_words,
         metrics_handle,
+        privacy: config.privacy.enabled,
+        audit,
+        feedback,
+        max_feedback_context: config.feedback.max_context_chars,
     };
 
-    let cors = build_cors_layer();
+    let cors = build_cors_layer(&config.cors);
 
-    let x_request_id = http::HeaderName::from_static("x-request-id");
+    let rate_limit = middleware::from_fn_with_state(state.clone(), rate_limit_middleware);
+    // Limiting by key needs the identity resolved by auth, so it runs
+    // inside auth, with failed auth limited by address outside it;
+    // limiting by address runs first, before any other work.
+    let (inner_rate_limit, outer_rate_limit) = if config.rate_limit.key == RateLimitKey::ApiKey {
+        (Some(rate_limit), None)
+    } else {
+        (None, Some(rate_limit))
+    };
+    let failed_auth = inner_rate_limit
+        .is_some()
+        .then(|| middleware::from_fn_with_state(state.clone(), failed_auth_middleware));
 
-    let router = Router::new()
+    let auth = middleware::from_fn_with_state(state.clone(), auth_middleware);
+    let audit = middleware::from_fn_with_state(state.clone(), audit_middleware);
+
+    let api = Router::new()
         .route("/v1/check", post(check_text))
-        .route("/health", get(health))
-        .route("/metrics", get(metrics_handler))
+        .route("/v1/usage", get(get_usage))
+        .route("/v1/feedback", post(post_feedback))
+        .route("/v1/feedback/summary", get(get_feedback_summary))
+        .fallback(not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        .layer(DefaultBodyLimit::max(limits.body_limit()))
+        .layer(option_layer(inner_rate_limit.clone()))
+        .layer(auth.clone())
+        .layer(option_layer(failed_auth.clone()))
         .layer(middleware::from_fn_with_state(
             state.clone(),
-            auth_middleware,
-        ));
+            body_limit_middleware,
+        ))
+        .layer(option_layer(outer_rate_limit.clone()))
+        .layer(audit.clone());
 
-    let router = if enable_rate_limiting {
-        let (rps, burst) = get_rate_limit_config();
-        let governor_conf = GovernorConfigBuilder::default()
-            .per_second(rps)
-            .burst_size(burst)
-            .finish()
-            .unwrap_or_else(|| {
-                GovernorConfigBuilder::default()
-                    .per_second(DEFAULT_RATE_LIMIT_PER_SECOND)
-                    .burst_size(DEFAULT_RATE_LIMIT_BURST)
-                    .finish()
-                    .unwrap_or_else(|| unreachable!())
-            });
-        let rate_limiter = GovernorLayer::new(governor_conf);
-        router.layer(rate_limiter)
+    // The same auth and rate limiting as the HTTP API, with their errors
+    // reported as gRPC statuses.
+    let grpc = Router::new()
+        .route_service(&grpc::route(), grpc::service(state.clone()))
+        .layer(option_layer(inner_rate_limit))
+        .layer(auth)
+        .layer(option_layer(failed_auth))
+        .layer(option_layer(outer_rate_limit))
+        .layer(audit)
+        .layer(middleware::from_fn(grpc::status_middleware));
+
+    // Without an admin listener, health and metrics are served next to the
+    // API, outside its auth and rate limiting.
+    let mut public = if config.server.admin.is_some() {
+        api
     } else {
-        router
+        admin_routes().merge(api)
     };
+    if config
+        .server
+        .grpc
+        .as_ref()
+        .is_some_and(|g| g.port.is_none())
+    {
+        public = public.merge(grpc.clone());
+    }
+
+    let admin = admin_routes()
+        .fallback(not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        .layer(middleware::from_fn(track_responses))
+        .with_state(state.clone());
+
+    let privacy = state.privacy;
+    let grpc = request_layers(grpc, privacy).with_state(state.clone());
+    let public = request_layers(public, privacy)
+        .layer(cors)
+        .with_state(state);
 
+    AppRouters {
+        public,
+        admin,
+        grpc,
+    }
+}
+
+/// Adds request IDs and a tracing span to every request. With `privacy`
+/// set, the span records the request path without its query string.
+fn request_layers(router: Router<AppState>, privacy: bool) -> Router<AppState> {
+    let x_request_id = http::HeaderName::from_static("x-request-id");
+
+    // The last layer runs first, so the ID is set before the span reads it.
     router
+        .layer(middleware::from_fn(track_responses))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(move |request: &Request<_>| {
+                    let request_id = request
+                        .headers()
+                        .get("x-request-id")
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or("unknown");
+                    let route = route(request);
+                    let uri: &dyn fmt::Display = if privacy {
+                        &request.uri().path()
+                    } else {
+                        request.uri()
+                    };
+                    let span = info_span!(
+                        "request",
+                        method = %request.method(),
+                        uri = %uri,
+                        route,
+                        request_id = %request_id,
+                        key = tracing::field::Empty,
+                        text_length = tracing::field::Empty,
+                        matches = tracing::field::Empty,
+                        otel.name = tracing::field::Empty,
+                        otel.kind = tracing::field::Empty,
+                    );
+                    telemetry::export_span(&span, request, route);
+                    span
+                })
+                .on_response(
+                    |response: &Response, latency: Duration, _: &tracing::Span| {
+                        tracing::info!(
+                            status = response.status().as_u16(),
+                            latency_ms = latency.as_millis() as u64,
+                            "Request completed"
+                        );
+                    },
+                ),
+        )
         .layer(PropagateRequestIdLayer::new(x_request_id.clone()))
-        .layer(SetRequestIdLayer::new(
-            x_request_id.clone(),
-            MakeRequestUuid,
-        ))
-        .layer(TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
-            let request_id = request
-                .headers()
-                .get("x-request-id")
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or("unknown");
-            info_span!(
-                "request",
-                method = %request.method(),
-                uri = %request.uri(),
-                request_id = %request_id,
-            )
-        }))
-        .layer(cors)
-        .with_state(state)
+        .layer(SetRequestIdLayer::new(x_request_id, MakeRequestUuid))
+}
+
+/// The route `request` matched, or its path for gRPC methods and requests
+/// that matched no route.
+fn route<B>(request: &Request<B>) -> &str {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .filter(|route| !route.contains("{*"))
+        .unwrap_or_else(|| request.uri().path())
+}
+
+/// Adds the size of a completed check to the request span, for the
+/// completion log line.
+fn record_check(text_length: usize, matches: usize) {
+    let span = tracing::Span::current();
+    span.record("text_length", telemetry::count(text_length));
+    span.record("matches", telemetry::count(matches));
+}
+
+/// Health, metrics and admin endpoints.
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/livez", get(health::livez))
+        .route("/readyz", get(health::readyz))
+        .route("/health", get(health::readyz))
+        .route("/metrics", get(metrics_handler))
+}
+
+/// Creates the application router for testing (default configuration
+/// without rate limiting).
+pub fn create_app_for_testing() -> Router {
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    build_app(
+        &config,
+        curated_dictionary(),
+        CustomRules::default(),
+        None,
+        None,
+        UsageTracker::in_memory(),
+        Health::new(),
+        AuditLog::disabled(),
+        FeedbackStore::in_memory(),
+        new_executor(&config.lint),
+        None,
+    )
+    .public
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[test]
     fn test_max_text_size_constant() {
-        assert_eq!(MAX_TEXT_SIZE, 100 * 1024);
+        assert_eq!(MAX_TEXT_SIZE, 4 * 1024 * 1024);
+    }
+
+    #[test]
+    fn deadline_stops_a_section_between_paragraphs() {
+        let linters = LinterPool::new(curated_dictionary(), &[Dialect::American], 1);
+        let paragraph = "Thsi sentense has typos in it.";
+        let text = [paragraph; 3].join("\n\n");
+        let section = Segment {
+            text: &text,
+            char_offset: 0,
+            byte_offset: 0,
+        };
+        let lint = |expired: &dyn Fn() -> bool| {
+            lint_section(
+                &linters,
+                &text,
+                &section,
+                Dialect::American,
+                &expired,
+                &tracing::Span::none(),
+            )
+        };
+
+        let full = lint(&|| false);
+        assert_eq!(full.partial_reason, None);
+        assert_eq!(full.checked_chars, text.chars().count());
+
+        // The deadline passes once the first paragraph has been linted, so
+        // only its matches are reported.
+        let checks = AtomicUsize::new(0);
+        let outcome = lint(&|| checks.fetch_add(1, Ordering::Relaxed) > 0);
+        assert_eq!(outcome.partial_reason, Some(DEADLINE_EXCEEDED));
+        assert_eq!(outcome.matches.len(), full.matches.len() / 3);
+        assert!(!outcome.matches.is_empty());
+        assert!(outcome
+            .matches
+            .iter()
+            .all(|m| m.offset < paragraph.chars().count()));
+        assert!(outcome.checked_chars > paragraph.chars().count());
+        assert!(outcome.checked_chars < text.chars().count());
     }
 }
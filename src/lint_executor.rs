@@ -0,0 +1,204 @@
+//! Bounded execution of lint jobs on the blocking thread pool.
+//!
+//! Linting is CPU-bound and can take hundreds of milliseconds for large
+//! inputs, so it must not run on the async workers. Jobs run through
+//! [`tokio::task::spawn_blocking`], at most `max_concurrency` at a time,
+//! with up to `queue_depth` further jobs waiting for a slot.
+
+use metrics::{counter, gauge, histogram};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Reasons a lint job could not produce a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutorError {
+    /// All workers are busy and the wait queue is full.
+    QueueFull,
+    /// The job panicked or was cancelled.
+    Failed,
+}
+
+/// Runs lint jobs on the blocking pool with bounded concurrency.
+///
+/// Cloning the executor is cheap; all clones share the same limits.
+#[derive(Clone)]
+pub struct LintExecutor {
+    inner: Arc<ExecutorInner>,
+}
+
+struct ExecutorInner {
+    permits: Arc<Semaphore>,
+    queued: AtomicUsize,
+    max_concurrency: usize,
+    queue_depth: usize,
+}
+
+impl fmt::Debug for LintExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LintExecutor")
+            .field("max_concurrency", &self.inner.max_concurrency)
+            .field("queue_depth", &self.inner.queue_depth)
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+impl LintExecutor {
+    /// Creates an executor running at most `max_concurrency` jobs at once and
+    /// holding at most `queue_depth` waiting jobs.
+    pub fn new(max_concurrency: usize, queue_depth: usize) -> Self {
+        let max_concurrency = max_concurrency.max(1);
+        Self {
+            inner: Arc::new(ExecutorInner {
+                permits: Arc::new(Semaphore::new(max_concurrency)),
+                queued: AtomicUsize::new(0),
+                max_concurrency,
+                queue_depth,
+            }),
+        }
+    }
+
+    /// Most jobs run at once.
+    pub fn max_concurrency(&self) -> usize {
+        self.inner.max_concurrency
+    }
+
+    /// Most jobs held waiting for a worker.
+    pub fn queue_depth(&self) -> usize {
+        self.inner.queue_depth
+    }
+
+    /// Number of jobs currently waiting for a worker.
+    pub fn queued(&self) -> usize {
+        self.inner.queued.load(Ordering::Relaxed)
+    }
+
+    /// Whether every worker is busy and the wait queue is full, so new
+    /// jobs are rejected.
+    pub fn is_saturated(&self) -> bool {
+        self.queued() >= self.inner.queue_depth && self.running() >= self.inner.max_concurrency
+    }
+
+    /// Number of jobs currently running.
+    pub fn running(&self) -> usize {
+        self.inner.max_concurrency - self.inner.permits.available_permits()
+    }
+
+    /// Runs `job` on the blocking pool once a worker slot is free.
+    pub async fn run<F, T>(&self, job: F) -> Result<T, ExecutorError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let wait_start = Instant::now();
+
+        let permit = match self.inner.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => self.wait_for_permit().await?,
+        };
+
+        histogram!("lint.queue_wait_ms").record(wait_start.elapsed().as_secs_f64() * 1000.0);
+
+        // Keep lint spans inside the request span.
+        let span = tracing::Span::current();
+        let running = Running::start(permit);
+        tokio::task::spawn_blocking(move || {
+            let _running = running;
+            span.in_scope(job)
+        })
+        .await
+        .map_err(|_| {
+            counter!("api.errors", "type" => "lint_failed").increment(1);
+            ExecutorError::Failed
+        })
+    }
+
+    async fn wait_for_permit(&self) -> Result<OwnedSemaphorePermit, ExecutorError> {
+        let slot = self.reserve_slot()?;
+        let permit = self.inner.permits.clone().acquire_owned().await;
+        drop(slot);
+        permit.map_err(|_| ExecutorError::Failed)
+    }
+
+    fn reserve_slot(&self) -> Result<QueueSlot<'_>, ExecutorError> {
+        let queued = self.inner.queued.fetch_add(1, Ordering::AcqRel);
+        if queued >= self.inner.queue_depth {
+            self.inner.queued.fetch_sub(1, Ordering::AcqRel);
+            counter!("api.errors", "type" => "queue_full").increment(1);
+            return Err(ExecutorError::QueueFull);
+        }
+
+        gauge!("lint.queue_depth").set((queued + 1) as f64);
+        Ok(QueueSlot { inner: &self.inner })
+    }
+}
+
+/// A worker slot held by a running job, counted in `lint.running` until
+/// dropped.
+struct Running {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Running {
+    fn start(permit: OwnedSemaphorePermit) -> Self {
+        gauge!("lint.running").increment(1.0);
+        Self { _permit: permit }
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        gauge!("lint.running").decrement(1.0);
+    }
+}
+
+/// A reserved place in the wait queue, released when dropped.
+struct QueueSlot<'a> {
+    inner: &'a ExecutorInner,
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        let remaining = self.inner.queued.fetch_sub(1, Ordering::AcqRel) - 1;
+        gauge!("lint.queue_depth").set(remaining as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[tokio::test]
+    async fn runs_job_and_returns_result() {
+        let executor = LintExecutor::new(1, 0);
+        assert_eq!(executor.run(|| 2 + 2).await, Ok(4));
+    }
+
+    #[tokio::test]
+    async fn rejects_when_queue_is_full() {
+        let executor = LintExecutor::new(1, 0);
+        let (release, blocked) = mpsc::channel::<()>();
+
+        let busy = executor.clone();
+        let running = tokio::spawn(async move { busy.run(move || blocked.recv().is_ok()).await });
+
+        while executor.running() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(executor.is_saturated());
+        assert_eq!(executor.run(|| ()).await, Err(ExecutorError::QueueFull));
+
+        release.send(()).ok();
+        assert!(matches!(running.await, Ok(Ok(true))));
+        assert!(!executor.is_saturated());
+    }
+}
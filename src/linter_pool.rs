@@ -0,0 +1,239 @@
+//! Pool of pre-built Harper linters.
+//!
+//! Building a curated [`LintGroup`] initializes every rule, which dominates
+//! the latency of short checks. The pool keeps idle linters per dialect and
+//! hands them out for the duration of a single check.
+//!
+//! Harper caches lint results by the text they were found in, so a linter
+//! that has been used holds fragments of that text. A [single-use] pool
+//! discards linters after one check and builds replacements in the
+//! background instead.
+//!
+//! [single-use]: LinterPool::single_use
+
+use harper_core::{linting::LintGroup, spell::MergedDictionary, Dialect};
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// A shared pool of reusable linters, keyed by dialect.
+///
+/// Cloning the pool is cheap; all clones share the same idle linters.
+#[derive(Clone)]
+pub struct LinterPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    dictionary: Arc<MergedDictionary>,
+    idle: Mutex<HashMap<Dialect, Vec<LintGroup>>>,
+    max_idle: usize,
+    single_use: bool,
+}
+
+impl fmt::Debug for LinterPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LinterPool")
+            .field("dictionary", &"<MergedDictionary>")
+            .field("max_idle", &self.inner.max_idle)
+            .field("single_use", &self.inner.single_use)
+            .finish()
+    }
+}
+
+impl LinterPool {
+    /// Creates a pool holding at most `max_idle` idle linters per dialect,
+    /// pre-building that many for each of the `warm` dialects.
+    pub fn new(dictionary: Arc<MergedDictionary>, warm: &[Dialect], max_idle: usize) -> Self {
+        Self::build(dictionary, warm, max_idle, false)
+    }
+
+    /// Like [`new`](Self::new), but linters are never reused: each is
+    /// dropped after one check, together with the text Harper cached in
+    /// it, and a fresh one is built on the rayon pool to take its place.
+    pub fn single_use(
+        dictionary: Arc<MergedDictionary>,
+        warm: &[Dialect],
+        max_idle: usize,
+    ) -> Self {
+        Self::build(dictionary, warm, max_idle, true)
+    }
+
+    fn build(
+        dictionary: Arc<MergedDictionary>,
+        warm: &[Dialect],
+        max_idle: usize,
+        single_use: bool,
+    ) -> Self {
+        let max_idle = max_idle.max(1);
+        let idle = warm
+            .iter()
+            .map(|&dialect| {
+                let linters = (0..max_idle)
+                    .map(|_| LintGroup::new_curated(dictionary.clone(), dialect))
+                    .collect();
+                (dialect, linters)
+            })
+            .collect();
+
+        Self {
+            inner: Arc::new(PoolInner {
+                dictionary,
+                idle: Mutex::new(idle),
+                max_idle,
+                single_use,
+            }),
+        }
+    }
+
+    /// Checks out a linter for `dialect`, building a new one if none are idle.
+    ///
+    /// The linter is returned to the pool when the guard is dropped.
+    pub fn checkout(&self, dialect: Dialect) -> PooledLinter {
+        let pooled = self
+            .inner
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(&dialect)
+            .and_then(Vec::pop);
+
+        let linter = pooled
+            .unwrap_or_else(|| LintGroup::new_curated(self.inner.dictionary.clone(), dialect));
+
+        PooledLinter {
+            pool: self.inner.clone(),
+            dialect,
+            linter: Some(linter),
+        }
+    }
+
+    /// The dictionary shared by every linter in the pool.
+    pub fn dictionary(&self) -> &MergedDictionary {
+        &self.inner.dictionary
+    }
+
+    /// Number of idle linters currently held for `dialect`.
+    pub fn idle_count(&self, dialect: Dialect) -> usize {
+        self.inner
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&dialect)
+            .map_or(0, Vec::len)
+    }
+}
+
+/// A linter checked out from a [`LinterPool`].
+pub struct PooledLinter {
+    pool: Arc<PoolInner>,
+    dialect: Dialect,
+    linter: Option<LintGroup>,
+}
+
+impl fmt::Debug for PooledLinter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledLinter")
+            .field("dialect", &self.dialect)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deref for PooledLinter {
+    type Target = LintGroup;
+
+    fn deref(&self) -> &LintGroup {
+        self.linter.as_ref().unwrap_or_else(|| unreachable!())
+    }
+}
+
+impl DerefMut for PooledLinter {
+    fn deref_mut(&mut self) -> &mut LintGroup {
+        self.linter.as_mut().unwrap_or_else(|| unreachable!())
+    }
+}
+
+impl PoolInner {
+    /// Keeps `linter` for reuse unless `dialect` already has enough idle.
+    fn put(&self, dialect: Dialect, linter: LintGroup) {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        let slot = idle.entry(dialect).or_default();
+        if slot.len() < self.max_idle {
+            slot.push(linter);
+        }
+    }
+}
+
+impl Drop for PooledLinter {
+    fn drop(&mut self) {
+        let Some(linter) = self.linter.take() else {
+            return;
+        };
+
+        if !self.pool.single_use {
+            self.pool.put(self.dialect, linter);
+            return;
+        }
+
+        drop(linter);
+        let pool = self.pool.clone();
+        let dialect = self.dialect;
+        rayon::spawn(move || {
+            let fresh = LintGroup::new_curated(pool.dictionary.clone(), dialect);
+            pool.put(dialect, fresh);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curated_dictionary;
+
+    #[test]
+    fn checkout_returns_linter_to_pool() {
+        let pool = LinterPool::new(curated_dictionary(), &[Dialect::American], 2);
+        assert_eq!(pool.idle_count(Dialect::American), 2);
+
+        let linter = pool.checkout(Dialect::American);
+        assert_eq!(pool.idle_count(Dialect::American), 1);
+
+        drop(linter);
+        assert_eq!(pool.idle_count(Dialect::American), 2);
+    }
+
+    #[test]
+    fn idle_linters_are_capped() {
+        let pool = LinterPool::new(curated_dictionary(), &[], 1);
+
+        let first = pool.checkout(Dialect::British);
+        let second = pool.checkout(Dialect::British);
+        drop(first);
+        drop(second);
+
+        assert_eq!(pool.idle_count(Dialect::British), 1);
+    }
+
+    #[test]
+    fn single_use_pool_replaces_used_linters() {
+        let pool = LinterPool::single_use(curated_dictionary(), &[Dialect::American], 1);
+
+        // Mark the linter so a reused one can be told apart from a fresh one.
+        let mut used = pool.checkout(Dialect::American);
+        let rule = used.iter_keys().next().unwrap_or_default().to_string();
+        let enabled = used.config.is_rule_enabled(&rule);
+        used.config.set_rule_enabled(&rule, !enabled);
+        drop(used);
+
+        let start = std::time::Instant::now();
+        while pool.idle_count(Dialect::American) == 0 {
+            assert!(start.elapsed().as_secs() < 30, "no replacement was built");
+            std::thread::yield_now();
+        }
+        let fresh = pool.checkout(Dialect::American);
+        assert_eq!(fresh.config.is_rule_enabled(&rule), enabled);
+    }
+}
@@ -0,0 +1,206 @@
+//! Listening sockets for the API and admin servers.
+//!
+//! A server can listen on any number of TCP addresses and Unix domain
+//! sockets at once, all serving the same router and state.
+
+use crate::TlsTerminator;
+use axum::Router;
+use std::{fmt, future::Future, io, net::SocketAddr, path::PathBuf, str::FromStr};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use {
+    axum::{extract::connect_info::Connected, serve::IncomingStream},
+    tokio::net::UnixListener,
+};
+
+/// Prefix marking a Unix socket path in listen addresses.
+const UNIX_PREFIX: &str = "unix:";
+
+/// The peer of a Unix socket connection, from its credentials.
+///
+/// Attached to requests as [`ConnectInfo`](axum::extract::ConnectInfo),
+/// like the peer address of TCP connections, so Unix socket clients can be
+/// told apart.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixPeer {
+    /// User id of the connecting process, if the platform reports it.
+    pub uid: Option<u32>,
+    /// Id of the connecting process, if the platform reports it.
+    pub pid: Option<i32>,
+}
+
+#[cfg(unix)]
+impl Connected<IncomingStream<'_, UnixListener>> for UnixPeer {
+    fn connect_info(stream: IncomingStream<'_, UnixListener>) -> Self {
+        let credentials = stream.io().peer_cred().ok();
+        Self {
+            uid: credentials.map(|c| c.uid()),
+            pid: credentials.and_then(|c| c.pid()),
+        }
+    }
+}
+
+/// Where a server listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    /// A TCP `host:port`.
+    Tcp(String),
+    /// A Unix domain socket path.
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(path) = s.strip_prefix(UNIX_PREFIX) {
+            if path.is_empty() {
+                return Err("unix: address needs a socket path".to_string());
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        match s.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(Self::Tcp(s.to_string()))
+            }
+            _ => Err(format!(
+                "invalid address {s:?}; expected host:port or unix:/path"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => f.write_str(addr),
+            Self::Unix(path) => write!(f, "{UNIX_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+/// A bound listening socket.
+#[derive(Debug)]
+pub enum ServerListener {
+    /// A TCP socket.
+    Tcp(TcpListener),
+    /// A Unix domain socket.
+    #[cfg(unix)]
+    Unix {
+        /// The bound socket.
+        listener: UnixListener,
+        /// Socket file to remove when the server stops; `None` for sockets
+        /// owned by the service manager.
+        path: Option<PathBuf>,
+    },
+}
+
+impl ServerListener {
+    /// Binds `address`, giving Unix sockets the permissions `socket_mode`.
+    ///
+    /// A socket file left behind by an earlier run is replaced; any other
+    /// file at the path is an error.
+    pub async fn bind(address: &ListenAddress, socket_mode: Option<u32>) -> io::Result<Self> {
+        match address {
+            ListenAddress::Tcp(addr) => TcpListener::bind(addr).await.map(Self::Tcp),
+            #[cfg(unix)]
+            ListenAddress::Unix(path) => {
+                use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+                match std::fs::symlink_metadata(path) {
+                    Ok(metadata) if metadata.file_type().is_socket() => {
+                        std::fs::remove_file(path)?;
+                    }
+                    Ok(_) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            format!("{} exists and is not a socket", path.display()),
+                        ))
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+
+                let listener = UnixListener::bind(path)?;
+                if let Some(mode) = socket_mode {
+                    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+                }
+                Ok(Self::Unix {
+                    listener,
+                    path: Some(path.clone()),
+                })
+            }
+            #[cfg(not(unix))]
+            ListenAddress::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are only supported on Unix",
+            )),
+        }
+    }
+
+    /// Serves `router` until `shutdown` resolves and open requests finish.
+    ///
+    /// TCP connections are served over TLS when `tls` is set and carry the
+    /// peer address; Unix socket connections are always plain HTTP and
+    /// carry the peer's [`UnixPeer`] credentials.
+    pub async fn serve(
+        self,
+        router: Router,
+        tls: Option<TlsTerminator>,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> io::Result<()> {
+        match (self, tls) {
+            (Self::Tcp(listener), Some(tls)) => {
+                let service = tls.make_service(router);
+                let listener = tls.listen(listener)?;
+                drop(tls);
+                axum::serve(listener, service)
+                    .with_graceful_shutdown(shutdown)
+                    .await
+            }
+            (Self::Tcp(listener), None) => {
+                let service = router.into_make_service_with_connect_info::<SocketAddr>();
+                axum::serve(listener, service)
+                    .with_graceful_shutdown(shutdown)
+                    .await
+            }
+            #[cfg(unix)]
+            (Self::Unix { listener, path }, _) => {
+                let service = router.into_make_service_with_connect_info::<UnixPeer>();
+                let result = axum::serve(listener, service)
+                    .with_graceful_shutdown(shutdown)
+                    .await;
+                if let Some(path) = path {
+                    std::fs::remove_file(path).ok();
+                }
+                result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tcp_and_unix_addresses() {
+        assert_eq!(
+            "[::]:8080".parse(),
+            Ok(ListenAddress::Tcp("[::]:8080".to_string()))
+        );
+        assert_eq!(
+            "unix:/run/api.sock".parse(),
+            Ok(ListenAddress::Unix(PathBuf::from("/run/api.sock")))
+        );
+        assert!("localhost".parse::<ListenAddress>().is_err());
+        assert!("unix:".parse::<ListenAddress>().is_err());
+        assert_eq!(
+            ListenAddress::Unix(PathBuf::from("/a.sock")).to_string(),
+            "unix:/a.sock"
+        );
+    }
+}
@@ -1,44 +1,411 @@
 //! Grammar API server binary.
 
-use grammar_api::create_app;
-use std::env;
-use tokio::net::TcpListener;
+use clap::{Parser, Subcommand};
+use grammar_api::{
+    audit, privacy,
+    systemd::{self, ActivatedListeners},
+    Config, ConfigError, ConfigOverrides, Health, ListenAddress, ReloadableApp, ServerListener,
+    Startup, Telemetry, TlsConfig, TlsTerminator, UsageTracker,
+};
+use metrics::counter;
+use std::{io, path::PathBuf, time::Duration};
 use tokio::signal;
+use tokio::sync::watch;
+
+/// Grammar and spelling checking API server.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    overrides: ConfigOverrides,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the server (default).
+    Serve,
+    /// Inspect the configuration.
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Inspect the audit log.
+    Audit {
+        #[command(subcommand)]
+        action: AuditCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration as TOML.
+    Print,
+}
+
+#[derive(Debug, Subcommand)]
+enum AuditCommand {
+    /// Check the audit log and its rotated files for tampering.
+    Verify {
+        /// Audit log to check [default: audit.file].
+        path: Option<PathBuf>,
+    },
+}
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
 
-    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
-    let addr = format!("{}:{}", host, port);
+    let config = match Config::load(&cli.overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Configuration error: {e}");
+            std::process::exit(2);
+        }
+    };
 
-    tracing::info!("Loading dictionary...");
-    let app = create_app();
-    tracing::info!("Server ready");
+    match cli.command {
+        Some(Command::Config {
+            action: ConfigCommand::Print,
+        }) => print_config(&config),
+        Some(Command::Audit {
+            action: AuditCommand::Verify { path },
+        }) => verify_audit_log(path.or(config.audit.file), config.audit.chain_key),
+        Some(Command::Serve) | None => serve(config, cli.overrides).await,
+    }
+}
+
+fn print_config(config: &Config) {
+    match config.to_redacted_toml() {
+        Ok(rendered) => print!("{rendered}"),
+        Err(e) => {
+            eprintln!("Failed to render configuration: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn verify_audit_log(path: Option<PathBuf>, chain_key: Option<String>) {
+    let Some(path) = path else {
+        eprintln!("No audit log configured; pass its path or set audit.file");
+        std::process::exit(2);
+    };
+    let Some(chain_key) = chain_key else {
+        eprintln!("No chain key configured; set audit.chain_key or AUDIT_CHAIN_KEY");
+        std::process::exit(2);
+    };
+
+    match audit::verify(&path, &chain_key) {
+        Ok(summary) => {
+            println!(
+                "{}: {} records in {} file(s), chain intact",
+                path.display(),
+                summary.records,
+                summary.files
+            );
+            if let Some(first) = summary.first_seq.filter(|_| !summary.from_start) {
+                println!("Records before #{first} were rotated out");
+            }
+            if let (Some(seq), Some(hash)) = (summary.last_seq, &summary.last_hash) {
+                println!("Head is #{seq} ({hash}); compare with the last audit_head_seq reported");
+            }
+        }
+        Err(e) => {
+            eprintln!("Audit log verification failed: {e}");
+            std::process::exit(1);
+        }
+    }
+}
 
-    tracing::info!("Starting server on {}", addr);
+async fn serve(config: Config, overrides: ConfigOverrides) {
+    let telemetry = match Telemetry::init(&config) {
+        Ok(telemetry) => telemetry,
+        Err(e) => {
+            eprintln!("Failed to set up telemetry: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(endpoint) = &config.telemetry.otlp_endpoint {
+        tracing::info!("Exporting telemetry to {}", endpoint);
+    }
+    if let Some(path) = &config.audit.file {
+        tracing::info!("Recording checks in audit log {}", path.display());
+    }
+    if let Some(path) = &config.feedback.file {
+        tracing::info!("Saving feedback to {}", path.display());
+    }
+    if config.privacy.enabled {
+        privacy::install_panic_hook();
+        tracing::info!("Privacy mode enabled");
+    }
 
-    let listener = match TcpListener::bind(&addr).await {
-        Ok(l) => l,
+    let activated = match ActivatedListeners::from_env() {
+        Ok(activated) => activated,
         Err(e) => {
-            tracing::error!("Failed to bind to {}: {}", addr, e);
+            tracing::error!("Failed to use sockets from systemd: {}", e);
             std::process::exit(1);
         }
     };
 
-    if let Err(e) = axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-    {
-        tracing::error!("Server error: {}", e);
-        std::process::exit(1);
+    let socket_mode = config.server.socket_mode();
+    let listeners = if activated.is_empty() {
+        let mut listeners = Vec::new();
+        for address in config.server.listen_addresses() {
+            listeners.push(bind(&address, socket_mode).await);
+            tracing::info!("Listening on {}", address);
+        }
+        listeners
+    } else {
+        tracing::info!(
+            "Listening on {} socket(s) from systemd",
+            activated.api.len()
+        );
+        activated.api
+    };
+
+    let admin = match (activated.admin, &config.server.admin) {
+        (Some(listener), _) => {
+            tracing::info!("Admin server listening on socket from systemd");
+            Some(listener)
+        }
+        (None, Some(admin)) => {
+            let address = admin.address();
+            let listener = bind(&address, socket_mode).await;
+            tracing::info!("Admin server listening on {}", address);
+            Some(listener)
+        }
+        (None, None) => None,
+    };
+
+    let grpc = match config.server.grpc.as_ref().and_then(|g| g.port) {
+        Some(port) => {
+            let address = ListenAddress::Tcp(format!("{}:{}", config.server.host, port));
+            let listener = bind(&address, socket_mode).await;
+            tracing::info!("gRPC server listening on {}", address);
+            Some(listener)
+        }
+        None => None,
+    };
+
+    let tls = config.server.tls.as_ref().map(load_tls);
+
+    // Listeners serve from here on, so probes can see the dictionary
+    // loading; each startup router hands over once the app is built.
+    let health = Health::new();
+    let public = Startup::new(health.clone());
+    let admin_startup = Startup::new(health.clone());
+    let grpc_startup = Startup::new(health.clone());
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let mut servers: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| {
+            tokio::spawn(listener.serve(
+                public.router(),
+                tls.clone(),
+                shutdown(shutdown_rx.clone()),
+            ))
+        })
+        .collect();
+    if let Some(admin) = admin {
+        servers.push(tokio::spawn(admin.serve(
+            admin_startup.router(),
+            None,
+            shutdown(shutdown_rx.clone()),
+        )));
     }
+    if let Some(grpc) = grpc {
+        servers.push(tokio::spawn(grpc.serve(
+            grpc_startup.router(),
+            tls.clone(),
+            shutdown(shutdown_rx.clone()),
+        )));
+    }
+    drop(tls);
+
+    tracing::info!("Loading dictionary...");
+    let load_config = config.clone();
+    let loaded =
+        tokio::task::spawn_blocking(move || ReloadableApp::with_health(&load_config, health)).await;
+    let app = match loaded {
+        Ok(Ok(app)) => app,
+        Ok(Err(e)) => {
+            tracing::error!("Failed to start: {}", e);
+            std::process::exit(2);
+        }
+        Err(e) => {
+            tracing::error!("Failed to start: {}", e);
+            std::process::exit(2);
+        }
+    };
+    public.ready(app.router());
+    admin_startup.ready(app.admin_router());
+    grpc_startup.ready(app.grpc_router());
 
+    tokio::spawn(flush_usage_periodically(
+        app.usage().clone(),
+        Duration::from_secs(config.quota.flush_interval_secs),
+    ));
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup(app.clone(), overrides, config.clone()));
+    #[cfg(not(unix))]
+    drop(overrides);
+
+    let health = app.health().clone();
+    let drain_delay = Duration::from_secs(config.server.drain_delay_secs);
+    tokio::spawn(async move {
+        shutdown_signal(health).await;
+        // Listeners, the admin one included, keep serving while readiness
+        // fails, so load balancers see the probe fail before they close.
+        if !drain_delay.is_zero() {
+            tracing::info!(
+                "Draining for {}s before closing listeners",
+                drain_delay.as_secs()
+            );
+            tokio::time::sleep(drain_delay).await;
+        }
+        shutdown_tx.send_replace(true);
+    });
+
+    systemd::notify_ready();
+    tracing::info!("Server ready");
+    tokio::spawn(systemd::watchdog());
+
+    let mut failed = false;
+    for server in servers {
+        let result = server.await.map_err(io::Error::other).and_then(|r| r);
+        if let Err(e) = result {
+            tracing::error!("Server error: {}", e);
+            failed = true;
+        }
+    }
+
+    flush_usage(app.usage().clone()).await;
+    if let Err(e) = tokio::task::spawn_blocking(move || telemetry.shutdown()).await {
+        tracing::error!("Failed to flush telemetry: {}", e);
+    }
+    if failed {
+        std::process::exit(1);
+    }
     tracing::info!("Server shutdown complete");
 }
 
-async fn shutdown_signal() {
+/// Binds `address`, exiting if it is unavailable.
+async fn bind(address: &ListenAddress, socket_mode: Option<u32>) -> ServerListener {
+    let result = ServerListener::bind(address, socket_mode).await;
+    result.unwrap_or_else(|e| {
+        tracing::error!("Failed to bind to {}: {}", address, e);
+        std::process::exit(1);
+    })
+}
+
+/// Loads the TLS certificates and starts watching them for changes.
+fn load_tls(config: &TlsConfig) -> TlsTerminator {
+    let terminator = match TlsTerminator::load(config) {
+        Ok(terminator) => terminator,
+        Err(e) => {
+            tracing::error!("Failed to load TLS certificates: {}", e);
+            std::process::exit(2);
+        }
+    };
+    tokio::spawn(terminator.clone().watch());
+    terminator
+}
+
+/// Resolves once shutdown has been requested.
+async fn shutdown(mut requested: watch::Receiver<bool>) {
+    // An error means the sender is gone, which only happens on shutdown.
+    requested.wait_for(|&requested| requested).await.ok();
+}
+
+/// Prunes usage counters of ended periods and saves the rest every
+/// `interval`.
+async fn flush_usage_periodically(usage: UsageTracker, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        flush_usage(usage.clone()).await;
+    }
+}
+
+async fn flush_usage(usage: UsageTracker) {
+    match tokio::task::spawn_blocking(move || usage.flush()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::error!("Failed to save usage counters: {}", e),
+        Err(e) => tracing::error!("Failed to save usage counters: {}", e),
+    }
+}
+
+/// Reloads configuration and dictionaries on every SIGHUP.
+///
+/// A reload that fails leaves the running configuration in place. Listener,
+/// logging and telemetry settings cannot change without a restart.
+#[cfg(unix)]
+async fn reload_on_hangup(app: ReloadableApp, overrides: ConfigOverrides, startup: Config) {
+    let mut hangup = match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        Ok(signal) => signal,
+        Err(e) => {
+            tracing::error!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        tracing::info!("Received SIGHUP, reloading configuration...");
+
+        let reload_app = app.clone();
+        let reload_overrides = overrides.clone();
+        let privacy = startup.privacy;
+        let result = tokio::task::spawn_blocking(move || {
+            let mut config = Config::load(&reload_overrides)?;
+            // Privacy mode holds for the life of the process.
+            let requested = std::mem::replace(&mut config.privacy, privacy);
+            reload_app.reload(&config)?;
+            Ok::<_, ConfigError>((config, requested))
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result.map_err(|e| e.to_string()));
+
+        match result {
+            Ok((config, requested_privacy)) => {
+                app.health().set_config_error(None);
+                if config.server != startup.server {
+                    tracing::warn!("Listener settings changed; restart to apply them");
+                }
+                if config.log != startup.log
+                    || config.telemetry != startup.telemetry
+                    || config.metrics != startup.metrics
+                {
+                    tracing::warn!("Logging or telemetry settings changed; restart to apply them");
+                }
+                if config.audit != startup.audit {
+                    tracing::warn!("Audit log settings changed; restart to apply them");
+                }
+                if config.feedback.file != startup.feedback.file {
+                    tracing::warn!("Feedback file changed; restart to apply it");
+                }
+                if requested_privacy != startup.privacy {
+                    tracing::warn!("Privacy mode changed; restart to apply it");
+                }
+                counter!("config.reloads", "result" => "success").increment(1);
+                tracing::info!("Configuration reloaded");
+            }
+            Err(e) => {
+                app.health().set_config_error(Some(e.clone()));
+                counter!("config.reloads", "result" => "failure").increment(1);
+                tracing::error!("Configuration reload failed, keeping previous: {}", e);
+            }
+        }
+    }
+}
+
+/// Resolves on Ctrl+C or SIGTERM, failing readiness straight away so load
+/// balancers stop routing here during the drain delay that follows.
+async fn shutdown_signal(health: Health) {
     let ctrl_c = async {
         if let Err(e) = signal::ctrl_c().await {
             tracing::error!("Failed to install Ctrl+C handler: {}", e);
@@ -68,4 +435,6 @@ async fn shutdown_signal() {
             tracing::info!("Received SIGTERM, shutting down...");
         }
     }
+    health.start_shutdown();
+    systemd::notify_stopping();
 }
@@ -0,0 +1,43 @@
+//! Privacy mode, which keeps submitted text out of everything the server
+//! produces other than the check response itself.
+//!
+//! With [`PrivacyConfig::enabled`](crate::PrivacyConfig::enabled) set,
+//! request spans carry the path without the query string, deserialization
+//! errors name the offending field without quoting its value, panic
+//! messages are replaced by their location, and linters are
+//! [single-use](crate::LinterPool::single_use) so Harper's result cache
+//! never outlives a check. Whatever the setting, request text is zeroized
+//! when dropped and never appears in `Debug` output.
+
+use std::{fmt, panic};
+
+/// Formats text as its length only, for `Debug` impls of requests.
+pub(crate) struct Redacted<'a>(pub(crate) &'a str);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} bytes>", self.0.len())
+    }
+}
+
+/// Replaces the panic hook with one that logs where a panic happened but
+/// not its message, which may quote the text being checked (slicing a
+/// string at a bad index does).
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map_or_else(|| "unknown".to_string(), ToString::to_string);
+        tracing::error!(%location, "Thread panicked; message withheld in privacy mode");
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_shows_only_the_length() {
+        assert_eq!(format!("{:?}", Redacted("top secret")), "<10 bytes>");
+    }
+}
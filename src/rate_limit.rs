@@ -0,0 +1,462 @@
+//! Per-client request rate limiting.
+//!
+//! Requests are grouped by a configurable key: the peer IP, the client IP
+//! reported by a trusted proxy, or the caller's usage key, which keeps API
+//! keys, token subjects and certificate names apart. Clients on Unix
+//! sockets have no IP and are grouped by user id instead, as `uid:<uid>`.
+//! Each group gets a token bucket refilled at `per_second` and holding up
+//! to `burst` requests, unless overridden for that API key or address.
+//! Every response carries the caller's `X-RateLimit-*` headers.
+//!
+//! Limiting by key happens after auth, so requests that fail auth never
+//! reach it. They are limited by peer address instead: each 401 takes a
+//! token from the peer's failed-auth bucket, and a peer whose bucket runs
+//! out is turned away before auth until it refills.
+
+#[cfg(unix)]
+use crate::UnixPeer;
+use crate::{AppError, AppState, Identity, RateLimitConfig, RateLimitKey};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
+    DefaultKeyedRateLimiter, Quota,
+};
+use ipnet::IpNet;
+use metrics::counter;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+/// Header giving the caller's burst size.
+const LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+
+/// Header giving the requests the caller can make right now.
+const REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+
+/// Header giving the seconds until the caller's bucket is full again.
+const RESET_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Requests between sweeps of idle buckets.
+const SWEEP_INTERVAL: u64 = 4096;
+
+/// Group used when no client address is known.
+const UNKNOWN_CLIENT: &str = "unknown";
+
+type Limiter = DefaultKeyedRateLimiter<String, StateInformationMiddleware>;
+
+/// Rate limiters for all clients, built from [`RateLimitConfig`].
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    key: RateLimitKey,
+    client_ip_header: HeaderName,
+    trusted_proxies: Vec<IpNet>,
+    default: Limiter,
+    overrides: HashMap<String, Limiter>,
+    requests: AtomicU64,
+    /// Failed auth attempts by peer address, when limiting by key.
+    failed_auth: Limiter,
+    /// Peers out of failed-auth tokens, and when they may try again.
+    blocked: Mutex<HashMap<String, Instant>>,
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("key", &self.key)
+            .field("client_ip_header", &self.client_ip_header)
+            .field("trusted_proxies", &self.trusted_proxies)
+            .field("overrides", &self.overrides.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The outcome of a rate limit check, as reported in response headers.
+struct Decision {
+    limit: u32,
+    remaining: u32,
+    reset: Duration,
+    allowed: bool,
+}
+
+impl RateLimiter {
+    /// Builds limiters from a validated configuration.
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let overrides = config
+            .keys
+            .iter()
+            .map(|(key, limits)| {
+                let quota = quota(limits.per_second, limits.burst);
+                (
+                    key.clone(),
+                    governor::RateLimiter::keyed(quota).with_middleware(),
+                )
+            })
+            .collect();
+
+        Self {
+            config: config.clone(),
+            key: config.key,
+            client_ip_header: HeaderName::try_from(config.client_ip_header.as_str())
+                .unwrap_or_else(|_| HeaderName::from_static("x-forwarded-for")),
+            trusted_proxies: config
+                .trusted_proxies
+                .iter()
+                .filter_map(|cidr| parse_cidr(cidr))
+                .collect(),
+            default: governor::RateLimiter::keyed(quota(config.per_second, config.burst))
+                .with_middleware(),
+            overrides,
+            requests: AtomicU64::new(0),
+            failed_auth: governor::RateLimiter::keyed(quota(config.per_second, config.burst))
+                .with_middleware(),
+            blocked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The settings the limiters were built from.
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// The rate limit group a request belongs to.
+    fn client_key(&self, request: &Request) -> String {
+        if self.key == RateLimitKey::ApiKey {
+            if let Some(identity) = request.extensions().get::<Identity>() {
+                return identity.usage_key();
+            }
+        }
+        self.address_key(request)
+    }
+
+    /// The rate limit group of a request's client address.
+    fn address_key(&self, request: &Request) -> String {
+        #[cfg(unix)]
+        if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<UnixPeer>>() {
+            return peer
+                .uid
+                .map_or_else(|| UNKNOWN_CLIENT.to_string(), |uid| format!("uid:{uid}"));
+        }
+
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        let ip = match (self.key, peer) {
+            (RateLimitKey::ClientIpHeader, Some(peer)) => {
+                self.forwarded_client(request.headers(), peer)
+            }
+            (_, peer) => peer,
+        };
+
+        ip.map_or_else(|| UNKNOWN_CLIENT.to_string(), |ip| ip.to_string())
+    }
+
+    /// The client IP reported by trusted proxies in front of `peer`.
+    ///
+    /// Addresses in the header are read from the nearest proxy backwards,
+    /// skipping trusted proxies, so clients cannot spoof their address by
+    /// sending the header themselves.
+    fn forwarded_client(&self, headers: &HeaderMap, peer: IpAddr) -> Option<IpAddr> {
+        if !self.is_trusted(peer) {
+            return Some(peer);
+        }
+
+        let hops: Vec<IpAddr> = headers
+            .get_all(&self.client_ip_header)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|hop| hop.trim().parse().ok())
+            .collect();
+
+        hops.iter()
+            .rev()
+            .find(|ip| !self.is_trusted(**ip))
+            .or_else(|| hops.first())
+            .copied()
+            .or(Some(peer))
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+
+    /// How long `client` must wait after running out of failed-auth
+    /// tokens, or `None` when it may try.
+    fn blocked_for(&self, client: &str) -> Option<Duration> {
+        let blocked = self.blocked.lock().unwrap_or_else(PoisonError::into_inner);
+        blocked
+            .get(client)
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|wait| !wait.is_zero())
+    }
+
+    /// Takes a token from `client`'s failed-auth bucket, blocking it once
+    /// the bucket is empty.
+    fn record_failed_auth(&self, client: &str) {
+        let mut blocked = self.blocked.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        blocked.retain(|_, until| *until > now);
+        if let Err(not_until) = self.failed_auth.check_key(&client.to_string()) {
+            let wait = not_until.wait_time_from(DefaultClock::default().now());
+            blocked.insert(client.to_string(), now + wait);
+        }
+    }
+
+    /// The `keys` entry that can override `client`'s limits: the key name
+    /// for API keys and the address for address groups. Token subjects and
+    /// certificate names get none, so they cannot claim a key's limits.
+    fn override_name(client: &str) -> Option<&str> {
+        if let Some(name) = client.strip_prefix("key:") {
+            return Some(name);
+        }
+        let is_caller = client.starts_with("jwt:") || client.starts_with("cert:");
+        (!is_caller).then_some(client)
+    }
+
+    fn check(&self, client: &str) -> Decision {
+        let limiter = Self::override_name(client)
+            .and_then(|name| self.overrides.get(name))
+            .unwrap_or(&self.default);
+        let key = client.to_string();
+
+        if self
+            .requests
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(SWEEP_INTERVAL)
+        {
+            self.default.retain_recent();
+            self.overrides.values().for_each(Limiter::retain_recent);
+            self.failed_auth.retain_recent();
+        }
+
+        match limiter.check_key(&key) {
+            Ok(snapshot) => {
+                let quota = snapshot.quota();
+                let remaining = snapshot.remaining_burst_capacity();
+                Decision {
+                    limit: quota.burst_size().get(),
+                    remaining,
+                    reset: quota.replenish_interval()
+                        * (quota.burst_size().get().saturating_sub(remaining)),
+                    allowed: true,
+                }
+            }
+            Err(not_until) => {
+                let quota = not_until.quota();
+                Decision {
+                    limit: quota.burst_size().get(),
+                    remaining: 0,
+                    reset: not_until.wait_time_from(DefaultClock::default().now()),
+                    allowed: false,
+                }
+            }
+        }
+    }
+}
+
+/// Rejects requests over their client's rate limit and adds
+/// `X-RateLimit-*` headers to every response.
+pub(crate) async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+
+    let decision = limiter.check(&limiter.client_key(&request));
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        counter!("api.errors", "type" => "rate_limited").increment(1);
+        AppError::RateLimited {
+            retry_after_secs: ceil_secs(decision.reset),
+        }
+        .into_response()
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(LIMIT_HEADER, HeaderValue::from(decision.limit));
+    headers.insert(REMAINING_HEADER, HeaderValue::from(decision.remaining));
+    headers.insert(RESET_HEADER, HeaderValue::from(ceil_secs(decision.reset)));
+    response
+}
+
+/// Limits requests that fail auth by peer address when limiting by key,
+/// rejecting peers out of failed-auth tokens before auth runs.
+pub(crate) async fn failed_auth_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+
+    let client = limiter.address_key(&request);
+    if let Some(wait) = limiter.blocked_for(&client) {
+        counter!("api.errors", "type" => "rate_limited").increment(1);
+        return AppError::RateLimited {
+            retry_after_secs: ceil_secs(wait),
+        }
+        .into_response();
+    }
+
+    let response = next.run(request).await;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        limiter.record_failed_auth(&client);
+    }
+    response
+}
+
+fn quota(per_second: u64, burst: u32) -> Quota {
+    let per_second =
+        NonZeroU32::new(u32::try_from(per_second).unwrap_or(u32::MAX)).unwrap_or(NonZeroU32::MIN);
+    let burst = NonZeroU32::new(burst).unwrap_or(NonZeroU32::MIN);
+    Quota::per_second(per_second).allow_burst(burst)
+}
+
+/// Parses a CIDR, accepting a bare address as a single-host network.
+pub(crate) fn parse_cidr(cidr: &str) -> Option<IpNet> {
+    let cidr = cidr.trim();
+    cidr.parse()
+        .ok()
+        .or_else(|| cidr.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(trusted: &[&str]) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            key: RateLimitKey::ClientIpHeader,
+            trusted_proxies: trusted.iter().map(|s| (*s).to_string()).collect(),
+            ..RateLimitConfig::default()
+        })
+    }
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(value) {
+            headers.insert("x-forwarded-for", value);
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap_or_else(|e| unreachable!("{e}"))
+    }
+
+    #[test]
+    fn uses_header_only_from_trusted_proxies() {
+        let limiter = limiter(&["10.0.0.0/8"]);
+        let headers = forwarded("203.0.113.7");
+
+        assert_eq!(
+            limiter.forwarded_client(&headers, ip("10.1.2.3")),
+            Some(ip("203.0.113.7"))
+        );
+        assert_eq!(
+            limiter.forwarded_client(&headers, ip("198.51.100.1")),
+            Some(ip("198.51.100.1"))
+        );
+    }
+
+    #[test]
+    fn skips_trusted_hops_and_ignores_spoofed_prefix() {
+        let limiter = limiter(&["10.0.0.0/8", "192.168.1.1"]);
+        let headers = forwarded("1.2.3.4, 203.0.113.7, 192.168.1.1");
+
+        assert_eq!(
+            limiter.forwarded_client(&headers, ip("10.0.0.1")),
+            Some(ip("203.0.113.7"))
+        );
+    }
+
+    #[test]
+    fn reports_remaining_requests() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_second: 1,
+            burst: 2,
+            ..RateLimitConfig::default()
+        });
+
+        let first = limiter.check("a");
+        assert!(first.allowed);
+        assert_eq!((first.limit, first.remaining), (2, 1));
+        assert!(limiter.check("a").allowed);
+
+        let third = limiter.check("a");
+        assert!(!third.allowed);
+        assert_eq!(third.remaining, 0);
+        assert!(third.reset > Duration::ZERO);
+        assert!(limiter.check("b").allowed);
+    }
+
+    #[test]
+    fn overrides_apply_to_keys_and_addresses_only() {
+        assert_eq!(RateLimiter::override_name("key:docs"), Some("docs"));
+        assert_eq!(
+            RateLimiter::override_name("203.0.113.1"),
+            Some("203.0.113.1")
+        );
+        assert_eq!(RateLimiter::override_name("uid:1000"), Some("uid:1000"));
+        assert_eq!(RateLimiter::override_name("jwt:docs"), None);
+        assert_eq!(RateLimiter::override_name("cert:docs"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn groups_unix_socket_clients_by_user() {
+        let limiter = limiter(&[]);
+        let request = |uid| {
+            let mut request = Request::new(axum::body::Body::empty());
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(UnixPeer { uid, pid: Some(1) }));
+            request
+        };
+
+        assert_eq!(limiter.client_key(&request(Some(1000))), "uid:1000");
+        assert_eq!(limiter.client_key(&request(None)), UNKNOWN_CLIENT);
+    }
+
+    #[test]
+    fn blocks_peers_out_of_failed_auth_tokens() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_second: 1,
+            burst: 2,
+            key: RateLimitKey::ApiKey,
+            ..RateLimitConfig::default()
+        });
+
+        limiter.record_failed_auth("a");
+        limiter.record_failed_auth("a");
+        assert_eq!(limiter.blocked_for("a"), None);
+        limiter.record_failed_auth("a");
+        assert!(limiter.blocked_for("a").is_some());
+        assert_eq!(limiter.blocked_for("b"), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Replacing the running application when configuration is reloaded.
+
+use crate::{
+    audit::AuditLog, create_routers_with_usage, new_executor, new_rate_limiter, open_feedback,
+    open_usage, AppRouters, Config, ConfigError, FeedbackStore, Health, LintExecutor, RateLimiter,
+    UsageTracker,
+};
+use axum::{extract::Request, Router};
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+};
+use tower::{service_fn, ServiceExt};
+
+/// An application whose configuration and dictionaries can be swapped
+/// while it is serving.
+///
+/// Each request runs against the snapshot that was current when it
+/// arrived, so requests in flight during a reload finish with the old one.
+#[derive(Clone)]
+pub struct ReloadableApp {
+    current: Arc<RwLock<AppRouters>>,
+    limiters: Arc<Mutex<Limiters>>,
+    usage: UsageTracker,
+    health: Health,
+    audit: AuditLog,
+    feedback: FeedbackStore,
+}
+
+/// The lint executor and rate limiter serving the current snapshot.
+#[derive(Clone)]
+struct Limiters {
+    executor: LintExecutor,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Limiters {
+    fn new(config: &Config) -> Self {
+        Self {
+            executor: new_executor(&config.lint),
+            rate_limiter: new_rate_limiter(&config.rate_limit),
+        }
+    }
+
+    /// These limiters, replacing only those whose settings differ in
+    /// `config`.
+    fn update(&self, config: &Config) -> Self {
+        let lint = &config.lint;
+        let executor = if self.executor.max_concurrency() == lint.max_concurrency.max(1)
+            && self.executor.queue_depth() == lint.queue_depth
+        {
+            self.executor.clone()
+        } else {
+            new_executor(lint)
+        };
+        let rate_limiter = match &self.rate_limiter {
+            Some(limiter) if *limiter.config() == config.rate_limit => Some(limiter.clone()),
+            _ => new_rate_limiter(&config.rate_limit),
+        };
+        Self {
+            executor,
+            rate_limiter,
+        }
+    }
+}
+
+impl fmt::Debug for ReloadableApp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableApp").finish_non_exhaustive()
+    }
+}
+
+impl ReloadableApp {
+    /// Builds the application from `config`.
+    pub fn new(config: &Config) -> Result<Self, ConfigError> {
+        Self::with_health(config, Health::new())
+    }
+
+    /// Like [`new`](Self::new), reporting to `health`, which probes served
+    /// while the application loads already share.
+    pub fn with_health(config: &Config, health: Health) -> Result<Self, ConfigError> {
+        let usage = open_usage(config)?;
+        let audit = AuditLog::open(&config.audit)?;
+        let feedback = open_feedback(config)?;
+        let limiters = Limiters::new(config);
+        let routers = create_routers_with_usage(
+            config,
+            usage.clone(),
+            health.clone(),
+            audit.clone(),
+            feedback.clone(),
+            limiters.executor.clone(),
+            limiters.rate_limiter.clone(),
+        )?;
+        Ok(Self {
+            current: Arc::new(RwLock::new(routers)),
+            limiters: Arc::new(Mutex::new(limiters)),
+            usage,
+            health,
+            audit,
+            feedback,
+        })
+    }
+
+    /// Usage counters, kept across reloads.
+    pub fn usage(&self) -> &UsageTracker {
+        &self.usage
+    }
+
+    /// Health state reported by the readiness probe, kept across reloads.
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// A router that forwards every request to the current snapshot.
+    pub fn router(&self) -> Router {
+        self.forward(|routers| &routers.public)
+    }
+
+    /// Like [`router`](Self::router), for the admin listener.
+    pub fn admin_router(&self) -> Router {
+        self.forward(|routers| &routers.admin)
+    }
+
+    /// Like [`router`](Self::router), for the dedicated gRPC listener.
+    pub fn grpc_router(&self) -> Router {
+        self.forward(|routers| &routers.grpc)
+    }
+
+    fn forward(&self, select: fn(&AppRouters) -> &Router) -> Router {
+        let current = self.current.clone();
+        Router::new().fallback_service(service_fn(move |request: Request| {
+            let router = select(&current.read().unwrap_or_else(PoisonError::into_inner)).clone();
+            async move { router.oneshot(request).await }
+        }))
+    }
+
+    /// Builds a new snapshot from `config` and swaps it in.
+    ///
+    /// Word lists are read before anything is replaced, so on error the
+    /// current snapshot keeps serving unchanged. Blocking; the linter pool
+    /// is pre-built before the swap. Usage counters, the audit log and
+    /// feedback carry over, so changes to `quota.state_file`, `[audit]` and
+    /// `feedback.file` only apply after a restart. The lint executor and
+    /// rate limiter carry over unless `lint.max_concurrency`,
+    /// `lint.queue_depth` or `[rate_limit]` changed, so running checks
+    /// still count against the limits and rate limits are not reset.
+    pub fn reload(&self, config: &Config) -> Result<(), ConfigError> {
+        let mut limiters = self.limiters.lock().unwrap_or_else(PoisonError::into_inner);
+        let updated = limiters.update(config);
+        let routers = create_routers_with_usage(
+            config,
+            self.usage.clone(),
+            self.health.clone(),
+            self.audit.clone(),
+            self.feedback.clone(),
+            updated.executor.clone(),
+            updated.rate_limiter.clone(),
+        )?;
+        self.replace(routers);
+        *limiters = updated;
+        Ok(())
+    }
+
+    /// Swaps in `routers` for subsequent requests.
+    pub fn replace(&self, routers: AppRouters) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = routers;
+    }
+}
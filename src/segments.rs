@@ -0,0 +1,237 @@
+//! Splitting input text into independently lintable segments.
+//!
+//! Large documents are linted in sections of whole paragraphs. Paragraphs
+//! too long for one section are cut at sentence or line boundaries, and the
+//! section after such a cut repeats the sentence before it so lints
+//! straddling the cut are still found. Lints reported twice from the
+//! repeated sentence are removed when results are merged.
+
+/// A contiguous slice of the input along with its position in the full text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Segment<'a> {
+    /// The segment text.
+    pub(crate) text: &'a str,
+    /// Character offset of the segment within the full text.
+    pub(crate) char_offset: usize,
+    /// Byte offset of the segment within the full text.
+    pub(crate) byte_offset: usize,
+}
+
+/// Splits `text` on blank lines.
+///
+/// Every character of `text` belongs to exactly one paragraph, including
+/// trailing blank lines, so offsets map back to the full text by adding the
+/// paragraph's offsets.
+pub(crate) fn split_paragraphs(text: &str) -> Vec<Segment<'_>> {
+    let mut paragraphs = Vec::new();
+    let mut start_byte = 0;
+    let mut start_char = 0;
+    let mut byte_pos = 0;
+    let mut char_pos = 0;
+    let mut has_content = false;
+
+    for line in text.split_inclusive('\n') {
+        byte_pos += line.len();
+        char_pos += line.chars().count();
+
+        if !line.trim().is_empty() {
+            has_content = true;
+        } else if has_content {
+            paragraphs.push(Segment {
+                text: &text[start_byte..byte_pos],
+                char_offset: start_char,
+                byte_offset: start_byte,
+            });
+            start_byte = byte_pos;
+            start_char = char_pos;
+            has_content = false;
+        }
+    }
+
+    if start_byte < text.len() {
+        paragraphs.push(Segment {
+            text: &text[start_byte..],
+            char_offset: start_char,
+            byte_offset: start_byte,
+        });
+    }
+
+    paragraphs
+}
+
+/// Splits `text` into sections of roughly `max_chars` characters.
+///
+/// Sections are made of whole paragraphs where possible. A section that
+/// begins partway through a paragraph starts one sentence early, repeating
+/// the end of the section before it.
+pub(crate) fn split_sections(text: &str, max_chars: usize) -> Vec<Segment<'_>> {
+    let max_chars = max_chars.max(1);
+    let mut pieces = Vec::new();
+
+    for paragraph in split_paragraphs(text) {
+        let chars = paragraph.text.chars().count();
+        if chars > max_chars {
+            split_long(paragraph, max_chars, &mut pieces);
+        } else {
+            pieces.push(Piece {
+                segment: paragraph,
+                chars,
+                lead: None,
+            });
+        }
+    }
+
+    let mut sections = Vec::new();
+    let mut first = 0;
+    let mut chars = 0;
+
+    for (i, piece) in pieces.iter().enumerate() {
+        if i > first && chars + piece.chars > max_chars {
+            sections.push(join(text, &pieces[first..i]));
+            first = i;
+            chars = piece.lead_chars();
+        }
+        chars += piece.chars;
+    }
+
+    if first < pieces.len() {
+        sections.push(join(text, &pieces[first..]));
+    }
+
+    sections
+}
+
+/// Part of a paragraph, sized to fit within a section.
+struct Piece<'a> {
+    segment: Segment<'a>,
+    chars: usize,
+    /// Absolute (byte, char) position to start from when this piece begins
+    /// a section partway through a paragraph.
+    lead: Option<(usize, usize)>,
+}
+
+impl Piece<'_> {
+    fn lead_chars(&self) -> usize {
+        self.lead
+            .map_or(0, |(_, char_pos)| self.segment.char_offset - char_pos)
+    }
+}
+
+fn join<'a>(text: &'a str, pieces: &[Piece<'a>]) -> Segment<'a> {
+    let first = &pieces[0];
+    let last = pieces[pieces.len() - 1].segment;
+    let (byte_offset, char_offset) = first
+        .lead
+        .unwrap_or((first.segment.byte_offset, first.segment.char_offset));
+
+    Segment {
+        text: &text[byte_offset..last.byte_offset + last.text.len()],
+        char_offset,
+        byte_offset,
+    }
+}
+
+/// Cuts an oversized paragraph at the last sentence end or line break
+/// before each `max_chars` limit, falling back to whitespace and then to a
+/// hard cut.
+fn split_long<'a>(paragraph: Segment<'a>, max_chars: usize, pieces: &mut Vec<Piece<'a>>) {
+    let text = paragraph.text;
+    // Positions are (byte, char) pairs relative to the paragraph.
+    let mut start = (0, 0);
+    let mut lead = None;
+    let mut sentence_breaks: [Option<(usize, usize)>; 2] = [None, None];
+    let mut space_breaks: [Option<(usize, usize)>; 2] = [None, None];
+    let mut prev = None;
+
+    let mut push = |from: (usize, usize), to: (usize, usize), lead: Option<(usize, usize)>| {
+        pieces.push(Piece {
+            segment: Segment {
+                text: &text[from.0..to.0],
+                char_offset: paragraph.char_offset + from.1,
+                byte_offset: paragraph.byte_offset + from.0,
+            },
+            chars: to.1 - from.1,
+            lead: lead.map(|(b, c)| (paragraph.byte_offset + b, paragraph.char_offset + c)),
+        });
+    };
+
+    for (char_idx, (byte_idx, ch)) in text.char_indices().enumerate() {
+        if char_idx - start.1 >= max_chars {
+            let (cut, next_lead) = match (sentence_breaks[1], space_breaks[1]) {
+                (Some(cut), _) => (cut, sentence_breaks[0]),
+                (None, Some(cut)) => (cut, space_breaks[0]),
+                (None, None) => ((byte_idx, char_idx), space_breaks[0]),
+            };
+            push(start, cut, lead);
+            lead = next_lead.filter(|b| b.0 > start.0).or(Some(cut));
+            start = cut;
+            sentence_breaks = [None, None];
+            space_breaks = [None, None];
+        }
+
+        if ch.is_whitespace() {
+            let after = Some((byte_idx + ch.len_utf8(), char_idx + 1));
+            if ch == '\n' || matches!(prev, Some('.' | '!' | '?')) {
+                sentence_breaks = [sentence_breaks[1], after];
+            } else {
+                space_breaks = [space_breaks[1], after];
+            }
+        }
+        prev = Some(ch);
+    }
+
+    push(
+        start,
+        (text.len(), start.1 + text[start.0..].chars().count()),
+        lead,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_blank_lines() {
+        let paragraphs = split_paragraphs("First one.\n\nSécond one.\n  \nThird.");
+        let texts: Vec<_> = paragraphs.iter().map(|p| p.text).collect();
+        let offsets: Vec<_> = paragraphs.iter().map(|p| p.char_offset).collect();
+
+        assert_eq!(texts, ["First one.\n\n", "Sécond one.\n  \n", "Third."]);
+        assert_eq!(offsets, [0, 12, 27]);
+    }
+
+    #[test]
+    fn single_line_breaks_stay_in_paragraph() {
+        let paragraphs = split_paragraphs("Line one\nline two\n");
+        assert_eq!(paragraphs.len(), 1);
+    }
+
+    #[test]
+    fn empty_text_has_no_paragraphs() {
+        assert!(split_paragraphs("").is_empty());
+        assert!(split_sections("", 10).is_empty());
+    }
+
+    #[test]
+    fn sections_group_whole_paragraphs() {
+        let text = "One.\n\nTwo.\n\nThree.\n\nFour.";
+        let sections = split_sections(text, 12);
+        let texts: Vec<_> = sections.iter().map(|s| s.text).collect();
+
+        assert_eq!(texts, ["One.\n\nTwo.\n\n", "Three.\n\n", "Four."]);
+        assert_eq!(sections[1].char_offset, 12);
+        assert_eq!(sections[1].byte_offset, 12);
+    }
+
+    #[test]
+    fn long_paragraph_sections_overlap_at_sentence_cuts() {
+        let text = "Aé b. Cc d. Ee f. Gg h.";
+        let sections = split_sections(text, 12);
+        let texts: Vec<_> = sections.iter().map(|s| s.text).collect();
+
+        assert_eq!(texts, ["Aé b. Cc d. ", "Cc d. Ee f. Gg h."]);
+        assert_eq!(sections[1].char_offset, 6);
+        assert_eq!(sections[1].byte_offset, 7);
+    }
+}
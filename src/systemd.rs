@@ -0,0 +1,127 @@
+//! Integration with the systemd service manager.
+//!
+//! Under systemd the server takes its listening sockets from socket
+//! activation (`LISTEN_FDS`) instead of binding them itself, reports
+//! readiness and shutdown through `sd_notify`, and pings the watchdog when
+//! `WatchdogSec` is set. Outside systemd none of this has any effect.
+
+use crate::ServerListener;
+use listenfd::ListenFd;
+use std::{env, io, time::Duration};
+
+/// `FileDescriptorName` that marks an activated socket as the admin listener.
+pub const ADMIN_FD_NAME: &str = "admin";
+
+/// Listening sockets passed in by socket activation.
+#[derive(Debug, Default)]
+pub struct ActivatedListeners {
+    /// Sockets serving the API.
+    pub api: Vec<ServerListener>,
+    /// The socket named [`ADMIN_FD_NAME`], if any.
+    pub admin: Option<ServerListener>,
+}
+
+impl ActivatedListeners {
+    /// Takes the sockets systemd passed to this process.
+    ///
+    /// Returns no sockets when the process was not socket activated.
+    /// `LISTEN_FDS` and `LISTEN_PID` are cleared so child processes do not
+    /// inherit them; call this before spawning tasks that read the
+    /// environment.
+    pub fn from_env() -> io::Result<Self> {
+        let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+        let mut fds = ListenFd::from_env();
+
+        let mut names = names.split(':');
+        let mut activated = Self::default();
+        for index in 0..fds.len() {
+            let listener = take_listener(&mut fds, index)?;
+            if names.next() == Some(ADMIN_FD_NAME) && activated.admin.is_none() {
+                activated.admin = Some(listener);
+            } else {
+                activated.api.push(listener);
+            }
+        }
+        Ok(activated)
+    }
+
+    /// Whether systemd passed no sockets for the API.
+    pub fn is_empty(&self) -> bool {
+        self.api.is_empty()
+    }
+}
+
+/// Converts the activated socket at `index` into a listener.
+fn take_listener(fds: &mut ListenFd, index: usize) -> io::Result<ServerListener> {
+    let missing = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("activated socket {index} is already in use"),
+        )
+    };
+
+    match fds.take_tcp_listener(index) {
+        Ok(listener) => {
+            let listener = listener.ok_or_else(missing)?;
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener).map(ServerListener::Tcp)
+        }
+        #[cfg(unix)]
+        Err(_) => {
+            let listener = fds.take_unix_listener(index)?.ok_or_else(missing)?;
+            listener.set_nonblocking(true)?;
+            Ok(ServerListener::Unix {
+                listener: tokio::net::UnixListener::from_std(listener)?,
+                path: None,
+            })
+        }
+        #[cfg(not(unix))]
+        Err(e) => Err(e),
+    }
+}
+
+/// Tells systemd that the server is accepting requests.
+pub fn notify_ready() {
+    notify("READY=1");
+}
+
+/// Tells systemd that the server is shutting down.
+pub fn notify_stopping() {
+    notify("STOPPING=1");
+}
+
+/// Pings the systemd watchdog at half its timeout, for as long as the
+/// runtime keeps polling; returns straight away when it is not enabled.
+pub async fn watchdog() {
+    let Some(timeout) = watchdog_timeout() else {
+        return;
+    };
+    tracing::info!("Pinging systemd watchdog every {:?}", timeout / 2);
+
+    let mut ticker = tokio::time::interval(timeout / 2);
+    loop {
+        ticker.tick().await;
+        notify("WATCHDOG=1");
+    }
+}
+
+#[cfg(unix)]
+fn watchdog_timeout() -> Option<Duration> {
+    sd_notify::watchdog_enabled().filter(|timeout| !timeout.is_zero())
+}
+
+#[cfg(not(unix))]
+fn watchdog_timeout() -> Option<Duration> {
+    None
+}
+
+/// Sends `state` to the service manager if `NOTIFY_SOCKET` is set.
+#[cfg(unix)]
+fn notify(state: &str) {
+    if let Err(e) = sd_notify::notify(&[sd_notify::NotifyState::Custom(state)]) {
+        tracing::warn!("Failed to notify systemd: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+fn notify(_state: &str) {}
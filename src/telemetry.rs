@@ -0,0 +1,402 @@
+//! Logging and OpenTelemetry export.
+//!
+//! Logs go to stdout in the configured format, with a line for every
+//! completed request. When an OTLP endpoint is configured, request spans and
+//! their `parse`, `lint` and `serialize` children are exported as traces,
+//! continuing the trace of a W3C `traceparent` header, and everything
+//! recorded through the `metrics` macros is exported next to the Prometheus
+//! endpoint.
+
+use crate::{Config, LogConfig, LogFormat};
+use axum::http::{HeaderMap, Request};
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use opentelemetry::{
+    metrics::{Meter, MeterProvider as _},
+    propagation::{Extractor, TextMapPropagator},
+    trace::TracerProvider as _,
+    KeyValue,
+};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    metrics::{PeriodicReader, SdkMeterProvider},
+    propagation::TraceContextPropagator,
+    trace::SdkTracerProvider,
+    Resource,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+use tracing::{level_filters::LevelFilter, Level};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer, Registry,
+};
+
+/// Instrumentation scope of exported spans and metrics.
+const SCOPE: &str = env!("CARGO_PKG_NAME");
+
+/// Errors raised while setting up logging and telemetry export.
+#[derive(Debug)]
+pub enum TelemetryError {
+    /// An OTLP exporter could not be created.
+    Exporter(String),
+    /// A global tracing subscriber was already installed.
+    Subscriber(String),
+    /// Metrics were recorded before export was set up, so the global
+    /// recorder could no longer be replaced.
+    MetricsInstalled,
+}
+
+impl fmt::Display for TelemetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exporter(message) => write!(f, "cannot create OTLP exporter: {message}"),
+            Self::Subscriber(message) => write!(f, "cannot install tracing subscriber: {message}"),
+            Self::MetricsInstalled => write!(f, "metrics recorder is already installed"),
+        }
+    }
+}
+
+impl Error for TelemetryError {}
+
+/// Installed logging and telemetry export; call [`Telemetry::shutdown`]
+/// before exiting so buffered spans and metrics are sent.
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    tracer_provider: Option<SdkTracerProvider>,
+    meter_provider: Option<SdkMeterProvider>,
+}
+
+impl Telemetry {
+    /// Installs the global tracing subscriber and, when `config` names an
+    /// OTLP endpoint, the exporters.
+    ///
+    /// Must be called from within a multi-threaded Tokio runtime, before
+    /// the first app is created.
+    pub fn init(config: &Config) -> Result<Self, TelemetryError> {
+        let log = &config.log;
+        let buckets = &config.metrics;
+        let config = &config.telemetry;
+        let endpoint = config.otlp_endpoint.as_deref();
+        let resource = Resource::builder()
+            .with_service_name(config.service_name.clone())
+            .build();
+
+        let tracer_provider = match endpoint.filter(|_| config.traces) {
+            Some(endpoint) => {
+                let exporter = SpanExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(endpoint)
+                    .build()
+                    .map_err(|e| TelemetryError::Exporter(e.to_string()))?;
+                Some(
+                    SdkTracerProvider::builder()
+                        .with_batch_exporter(exporter)
+                        .with_resource(resource.clone())
+                        .build(),
+                )
+            }
+            None => None,
+        };
+
+        let meter_provider = match endpoint.filter(|_| config.metrics) {
+            Some(endpoint) => {
+                let exporter = MetricExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(endpoint)
+                    .build()
+                    .map_err(|e| TelemetryError::Exporter(e.to_string()))?;
+                let reader = PeriodicReader::builder(exporter)
+                    .with_interval(Duration::from_secs(config.metrics_interval_secs))
+                    .build();
+                Some(
+                    SdkMeterProvider::builder()
+                        .with_reader(reader)
+                        .with_resource(resource)
+                        .build(),
+                )
+            }
+            None => None,
+        };
+
+        if let Some(provider) = &meter_provider {
+            let recorder =
+                OtlpRecorder::new(provider.meter(SCOPE), crate::histogram_buckets(buckets));
+            if !crate::init_metrics_with(recorder, buckets) {
+                return Err(TelemetryError::MetricsInstalled);
+            }
+        }
+
+        // Spans are exported regardless of the log levels, which only
+        // filter what is printed.
+        let otel = tracer_provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer(SCOPE))
+                .with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::INFO))
+        });
+        tracing_subscriber::registry()
+            .with(log_layer(log).with_filter(log_filter(log)))
+            .with(otel)
+            .try_init()
+            .map_err(|e| TelemetryError::Subscriber(e.to_string()))?;
+
+        Ok(Self {
+            tracer_provider,
+            meter_provider,
+        })
+    }
+
+    /// Sends buffered spans and metrics and stops exporting.
+    ///
+    /// Blocks until the collector answers or the export times out; call it
+    /// through [`tokio::task::spawn_blocking`].
+    pub fn shutdown(self) {
+        if let Some(provider) = self.tracer_provider {
+            if let Err(e) = provider.shutdown() {
+                tracing::warn!("Failed to export remaining spans: {}", e);
+            }
+        }
+        if let Some(provider) = self.meter_provider {
+            if let Err(e) = provider.shutdown() {
+                tracing::warn!("Failed to export remaining metrics: {}", e);
+            }
+        }
+    }
+}
+
+/// The stdout layer for `config.format`.
+fn log_layer(config: &LogConfig) -> Box<dyn Layer<Registry> + Send + Sync> {
+    let layer = tracing_subscriber::fmt::layer();
+    match config.format {
+        LogFormat::Full => layer.boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    }
+}
+
+/// Which events are logged; assumes a validated configuration.
+fn log_filter(config: &LogConfig) -> Targets {
+    let level = |level: &str| level.parse().unwrap_or(LevelFilter::INFO);
+    Targets::new()
+        .with_default(level(&config.level))
+        .with_targets(
+            config
+                .modules
+                .iter()
+                .map(|(module, filter)| (module.clone(), level(filter))),
+        )
+}
+
+/// Prepares the span of `request` for export: names it after `route` and
+/// makes it continue the trace of a W3C `traceparent` header. Does nothing
+/// when traces are not exported, keeping these fields out of the logs.
+pub(crate) fn export_span<B>(span: &tracing::Span, request: &Request<B>, route: &str) {
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(request.headers()));
+    if span.set_parent(parent).is_ok() {
+        span.record("otel.name", format!("{} {}", request.method(), route));
+        span.record("otel.kind", "server");
+    }
+}
+
+/// `value` as a span field; unsigned fields are exported as strings, signed
+/// ones as integer attributes.
+pub(crate) fn count(value: usize) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Records `metrics` macro calls as OpenTelemetry instruments, with labels
+/// as attributes.
+#[derive(Debug)]
+pub(crate) struct OtlpRecorder {
+    meter: Meter,
+    counters: Mutex<HashMap<Key, Arc<OtlpCounter>>>,
+    gauges: Mutex<HashMap<Key, Arc<OtlpGauge>>>,
+    histograms: Mutex<HashMap<Key, Arc<OtlpHistogram>>>,
+    buckets: HashMap<&'static str, Vec<f64>>,
+}
+
+impl OtlpRecorder {
+    fn new(meter: Meter, buckets: HashMap<&'static str, Vec<f64>>) -> Self {
+        Self {
+            meter,
+            counters: Mutex::default(),
+            gauges: Mutex::default(),
+            histograms: Mutex::default(),
+            buckets,
+        }
+    }
+}
+
+/// The handle for `key`, created on first use so that gauges and absolute
+/// counters keep their value between macro calls.
+fn handle<T>(
+    handles: &Mutex<HashMap<Key, Arc<T>>>,
+    key: &Key,
+    create: impl FnOnce() -> T,
+) -> Arc<T> {
+    let mut handles = handles.lock().unwrap_or_else(PoisonError::into_inner);
+    handles
+        .entry(key.clone())
+        .or_insert_with(|| Arc::new(create()))
+        .clone()
+}
+
+fn attributes(key: &Key) -> Vec<KeyValue> {
+    key.labels()
+        .map(|label| KeyValue::new(label.key().to_string(), label.value().to_string()))
+        .collect()
+}
+
+impl Recorder for OtlpRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        Counter::from_arc(handle(&self.counters, key, || OtlpCounter {
+            counter: self.meter.u64_counter(key.name().to_string()).build(),
+            attributes: attributes(key),
+            total: AtomicU64::new(0),
+        }))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(handle(&self.gauges, key, || OtlpGauge {
+            gauge: self.meter.f64_gauge(key.name().to_string()).build(),
+            attributes: attributes(key),
+            value: AtomicU64::new(0f64.to_bits()),
+        }))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let histogram = self.meter.f64_histogram(key.name().to_string());
+        let histogram = match self.buckets.get(key.name()) {
+            Some(buckets) => histogram.with_boundaries(buckets.clone()),
+            None => histogram,
+        };
+        Histogram::from_arc(handle(&self.histograms, key, || OtlpHistogram {
+            histogram: histogram.build(),
+            attributes: attributes(key),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct OtlpCounter {
+    counter: opentelemetry::metrics::Counter<u64>,
+    attributes: Vec<KeyValue>,
+    total: AtomicU64,
+}
+
+impl CounterFn for OtlpCounter {
+    fn increment(&self, value: u64) {
+        self.total.fetch_add(value, Ordering::Relaxed);
+        self.counter.add(value, &self.attributes);
+    }
+
+    fn absolute(&self, value: u64) {
+        let previous = self.total.fetch_max(value, Ordering::Relaxed);
+        if value > previous {
+            self.counter.add(value - previous, &self.attributes);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct OtlpGauge {
+    gauge: opentelemetry::metrics::Gauge<f64>,
+    attributes: Vec<KeyValue>,
+    /// Current value, as `f64` bits.
+    value: AtomicU64,
+}
+
+impl OtlpGauge {
+    fn adjust(&self, delta: f64) {
+        let result = self
+            .value
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + delta).to_bits())
+            });
+        let (Ok(previous) | Err(previous)) = result;
+        self.gauge
+            .record(f64::from_bits(previous) + delta, &self.attributes);
+    }
+}
+
+impl GaugeFn for OtlpGauge {
+    fn increment(&self, value: f64) {
+        self.adjust(value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.adjust(-value);
+    }
+
+    fn set(&self, value: f64) {
+        self.value.store(value.to_bits(), Ordering::Relaxed);
+        self.gauge.record(value, &self.attributes);
+    }
+}
+
+#[derive(Debug)]
+struct OtlpHistogram {
+    histogram: opentelemetry::metrics::Histogram<f64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl HistogramFn for OtlpHistogram {
+    fn record(&self, value: f64) {
+        self.histogram.record(value, &self.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_levels_override_the_default() {
+        let mut config = LogConfig::default();
+        config.level = "warn".to_string();
+        config
+            .modules
+            .insert("grammar_api::auth".to_string(), "debug".to_string());
+        let filter = log_filter(&config);
+
+        assert!(filter.would_enable("grammar_api::auth", &Level::DEBUG));
+        assert!(filter.would_enable("grammar_api::auth::keys", &Level::DEBUG));
+        assert!(!filter.would_enable("grammar_api::auth", &Level::TRACE));
+        assert!(!filter.would_enable("grammar_api", &Level::INFO));
+        assert!(filter.would_enable("tower_http", &Level::WARN));
+    }
+}
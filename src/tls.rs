@@ -0,0 +1,390 @@
+//! TLS termination for the main listener.
+//!
+//! Certificates are loaded with rustls and reloaded when their files
+//! change, without dropping open connections. With a client CA configured,
+//! clients may (or must) present a certificate; a verified certificate
+//! becomes the request [`Identity`], named after its common name.
+
+use crate::{ConfigError, Credential, Identity, TlsConfig};
+use axum::{
+    extract::{ConnectInfo, Request},
+    response::Response,
+    serve::{IncomingStream, Listener},
+    Router,
+};
+use metrics::counter;
+use std::{
+    convert::Infallible,
+    fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{
+        self,
+        crypto::{ring, CryptoProvider},
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+        server::WebPkiClientVerifier,
+        RootCertStore,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tower::Service;
+use x509_parser::{extensions::GeneralName, parse_x509_certificate};
+
+/// Time allowed for a client to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshaken connections waiting to be served.
+const ACCEPT_BACKLOG: usize = 128;
+
+/// The identity from a verified client certificate.
+///
+/// Attached to requests as an extension; the auth middleware uses it when
+/// no bearer token is sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    /// Identity mapped from the certificate subject.
+    pub identity: Identity,
+}
+
+/// Current TLS settings, swapped in when certificate files change.
+///
+/// Cloning is cheap; all clones share the same certificates.
+#[derive(Clone)]
+pub struct TlsTerminator {
+    current: Arc<RwLock<Arc<rustls::ServerConfig>>>,
+    config: Arc<TlsConfig>,
+}
+
+impl fmt::Debug for TlsTerminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsTerminator")
+            .field("cert_file", &self.config.cert_file)
+            .field("client_ca_file", &self.config.client_ca_file)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TlsTerminator {
+    /// Loads the certificate, key and client CA files named in `config`.
+    pub fn load(config: &TlsConfig) -> Result<Self, ConfigError> {
+        Ok(Self {
+            current: Arc::new(RwLock::new(server_config(config)?)),
+            config: Arc::new(config.clone()),
+        })
+    }
+
+    /// Re-reads the certificate files. On error the current certificates
+    /// stay in use. Blocking.
+    pub fn reload(&self) -> Result<(), ConfigError> {
+        let reloaded = server_config(&self.config)?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = reloaded;
+        Ok(())
+    }
+
+    /// Reloads the certificates whenever their files change. Never returns.
+    pub async fn watch(self) {
+        let mut ticker =
+            tokio::time::interval(Duration::from_secs(self.config.reload_interval_secs));
+        ticker.tick().await;
+        let mut loaded = self.modified_times();
+
+        loop {
+            ticker.tick().await;
+            let modified = self.modified_times();
+            if modified == loaded {
+                continue;
+            }
+
+            let terminator = self.clone();
+            match tokio::task::spawn_blocking(move || terminator.reload()).await {
+                Ok(Ok(())) => {
+                    // Only remember files that loaded, so a certificate and
+                    // key written a moment apart are retried together.
+                    loaded = modified;
+                    counter!("tls.reloads", "result" => "success").increment(1);
+                    tracing::info!("TLS certificates reloaded");
+                }
+                Ok(Err(e)) => {
+                    counter!("tls.reloads", "result" => "failure").increment(1);
+                    tracing::error!("TLS reload failed, keeping previous certificates: {}", e);
+                }
+                Err(e) => tracing::error!("TLS reload failed: {}", e),
+            }
+        }
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        [
+            Some(&self.config.cert_file),
+            Some(&self.config.key_file),
+            self.config.client_ca_file.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+    }
+
+    /// Accepts TLS connections on `listener`.
+    ///
+    /// Handshakes run concurrently in the background, so a slow client
+    /// cannot hold up others.
+    pub fn listen(&self, listener: TcpListener) -> io::Result<TlsListener> {
+        let local_addr = listener.local_addr()?;
+        let (sender, incoming) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(accept_connections(listener, self.clone(), sender));
+        Ok(TlsListener {
+            incoming,
+            local_addr,
+        })
+    }
+
+    /// Serves `router` on connections from a [`TlsListener`], attaching the
+    /// peer address and any client certificate identity to each request.
+    pub fn make_service(&self, router: Router) -> TlsMakeService {
+        TlsMakeService {
+            router,
+            client_scopes: self.config.client_scopes.clone().into(),
+        }
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(
+            self.current
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        )
+    }
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    terminator: TlsTerminator,
+    sender: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+) {
+    loop {
+        let accepted = tokio::select! {
+            () = sender.closed() => return,
+            accepted = listener.accept() => accepted,
+        };
+        let (stream, addr) = match accepted {
+            Ok(connection) => connection,
+            Err(e) => {
+                // Usually out of file descriptors; back off instead of spinning.
+                tracing::error!("Failed to accept connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        let acceptor = terminator.acceptor();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    sender.send((stream, addr)).await.ok();
+                }
+                Ok(Err(e)) => {
+                    counter!("tls.handshake_failures", "reason" => "error").increment(1);
+                    tracing::debug!("TLS handshake with {} failed: {}", addr, e);
+                }
+                Err(_) => {
+                    counter!("tls.handshake_failures", "reason" => "timeout").increment(1);
+                }
+            }
+        });
+    }
+}
+
+/// A listener yielding connections that completed the TLS handshake.
+#[derive(Debug)]
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(connection) => connection,
+            // The accept task only stops once this listener is dropped.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Makes a service per TLS connection; see [`TlsTerminator::make_service`].
+#[derive(Debug, Clone)]
+pub struct TlsMakeService {
+    router: Router,
+    client_scopes: Arc<[String]>,
+}
+
+impl Service<IncomingStream<'_, TlsListener>> for TlsMakeService {
+    type Response = ConnectionService;
+    type Error = Infallible;
+    type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, stream: IncomingStream<'_, TlsListener>) -> Self::Future {
+        let certificate = stream
+            .io()
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(<[CertificateDer<'_>]>::first)
+            .and_then(|certificate| certificate_identity(certificate, &self.client_scopes))
+            .map(|identity| ClientCertificate { identity });
+
+        std::future::ready(Ok(ConnectionService {
+            router: self.router.clone(),
+            peer: *stream.remote_addr(),
+            certificate,
+        }))
+    }
+}
+
+/// Serves the requests of one TLS connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionService {
+    router: Router,
+    peer: SocketAddr,
+    certificate: Option<ClientCertificate>,
+}
+
+impl Service<Request> for ConnectionService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = <Router as Service<Request>>::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        <Router as Service<Request>>::poll_ready(&mut self.router, cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        request.extensions_mut().insert(ConnectInfo(self.peer));
+        if let Some(certificate) = &self.certificate {
+            request.extensions_mut().insert(certificate.clone());
+        }
+        self.router.call(request)
+    }
+}
+
+/// Maps a verified client certificate to an identity: the subject common
+/// name (or first DNS name) as the name, and its organization as the tenant.
+fn certificate_identity(certificate: &CertificateDer<'_>, scopes: &[String]) -> Option<Identity> {
+    let (_, parsed) = parse_x509_certificate(certificate.as_ref()).ok()?;
+    let subject = parsed.subject();
+
+    let common_name = subject
+        .iter_common_name()
+        .find_map(|name| name.as_str().ok())
+        .map(str::to_string);
+    let dns_name = || {
+        parsed
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .and_then(|san| {
+                san.value.general_names.iter().find_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some((*dns).to_string()),
+                    _ => None,
+                })
+            })
+    };
+    let tenant = subject
+        .iter_organization()
+        .find_map(|org| org.as_str().ok())
+        .map(str::to_string);
+
+    Some(Identity {
+        name: common_name.or_else(dns_name)?,
+        tenant,
+        scopes: scopes.to_vec(),
+        credential: Credential::Certificate,
+    })
+}
+
+fn server_config(config: &TlsConfig) -> Result<Arc<rustls::ServerConfig>, ConfigError> {
+    let provider = Arc::new(ring::default_provider());
+
+    let certificates = read_certificates(&config.cert_file)?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_file)
+        .map_err(|e| tls_error(&config.key_file, e))?;
+
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error(&config.cert_file, e))?;
+    let builder = match &config.client_ca_file {
+        Some(path) => builder.with_client_cert_verifier(client_verifier(
+            path,
+            provider,
+            config.client_cert_required,
+        )?),
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server = builder
+        .with_single_cert(certificates, key)
+        .map_err(|e| tls_error(&config.cert_file, e))?;
+    server.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(server))
+}
+
+fn client_verifier(
+    path: &Path,
+    provider: Arc<CryptoProvider>,
+    required: bool,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>, ConfigError> {
+    let mut roots = RootCertStore::empty();
+    for certificate in read_certificates(path)? {
+        roots.add(certificate).map_err(|e| tls_error(path, e))?;
+    }
+
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+    let verifier = if required {
+        verifier
+    } else {
+        verifier.allow_unauthenticated()
+    };
+    verifier.build().map_err(|e| tls_error(path, e))
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, ConfigError> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .map_err(|e| tls_error(path, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| tls_error(path, e))?;
+    if certificates.is_empty() {
+        return Err(tls_error(path, "no certificates found"));
+    }
+    Ok(certificates)
+}
+
+fn tls_error(path: &Path, error: impl fmt::Display) -> ConfigError {
+    ConfigError::Tls {
+        path: PathBuf::from(path),
+        message: error.to_string(),
+    }
+}
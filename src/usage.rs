@@ -0,0 +1,444 @@
+//! Per-key usage counters and quota enforcement.
+//!
+//! Requests and characters checked are counted per key for the current UTC
+//! day and calendar month. Counters reset when a new period starts and can
+//! be saved to a JSON state file so they survive restarts.
+
+use crate::{
+    calendar::{civil_from_days, days_from_civil, unix_seconds, SECS_PER_DAY},
+    ConfigError, QuotaLimits,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+
+/// Usage counted for one quota period.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeriodUsage {
+    /// The period counted, as `YYYY-MM-DD` for days and `YYYY-MM` for months.
+    pub period: String,
+    /// Check requests made.
+    pub requests: u64,
+    /// Characters checked.
+    pub characters: u64,
+}
+
+/// Usage counted for one key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyUsage {
+    /// Usage in the current UTC day.
+    pub daily: PeriodUsage,
+    /// Usage in the current calendar month.
+    pub monthly: PeriodUsage,
+}
+
+/// The quota period whose limit was reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaPeriod {
+    /// The current UTC day.
+    Daily,
+    /// The current calendar month.
+    Monthly,
+}
+
+impl QuotaPeriod {
+    /// Lowercase name used in messages and metric labels.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Monthly => "monthly",
+        }
+    }
+}
+
+/// A request was refused because it would exceed a quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    /// The period whose limit was reached.
+    pub period: QuotaPeriod,
+    /// The limit that would have been exceeded.
+    pub limit: u64,
+    /// Time until the period resets.
+    pub retry_after: Duration,
+}
+
+/// Shared usage counters for all keys.
+///
+/// Cloning the tracker is cheap; all clones share the same counters.
+#[derive(Clone)]
+pub struct UsageTracker {
+    inner: Arc<TrackerInner>,
+}
+
+struct TrackerInner {
+    keys: Mutex<HashMap<String, KeyUsage>>,
+    state_file: Option<PathBuf>,
+    dirty: AtomicBool,
+}
+
+impl fmt::Debug for UsageTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UsageTracker")
+            .field("state_file", &self.inner.state_file)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StateFile {
+    keys: HashMap<String, KeyUsage>,
+}
+
+impl UsageTracker {
+    /// Creates a tracker that is not saved to disk.
+    pub fn in_memory() -> Self {
+        Self::with_keys(HashMap::new(), None)
+    }
+
+    /// Creates a tracker saved to `state_file`, starting from the counters
+    /// already saved there.
+    pub fn open(state_file: &Path) -> Result<Self, ConfigError> {
+        let keys = match fs::read_to_string(state_file) {
+            Ok(contents) => {
+                let state: StateFile =
+                    serde_json::from_str(&contents).map_err(|e| ConfigError::UsageState {
+                        path: state_file.to_path_buf(),
+                        message: e.to_string(),
+                    })?;
+                state.keys
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(ConfigError::UsageState {
+                    path: state_file.to_path_buf(),
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        Ok(Self::with_keys(keys, Some(state_file.to_path_buf())))
+    }
+
+    fn with_keys(keys: HashMap<String, KeyUsage>, state_file: Option<PathBuf>) -> Self {
+        Self {
+            inner: Arc::new(TrackerInner {
+                keys: Mutex::new(keys),
+                state_file,
+                dirty: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// The file counters are saved to, if any.
+    pub fn state_file(&self) -> Option<&Path> {
+        self.inner.state_file.as_deref()
+    }
+
+    /// Counts a request of `characters` for `key` at time `now`, unless it
+    /// would take the key over one of its `limits`.
+    pub fn record(
+        &self,
+        key: &str,
+        characters: u64,
+        limits: &QuotaLimits,
+        now: SystemTime,
+    ) -> Result<(), QuotaExceeded> {
+        let periods = Periods::at(now);
+        let mut keys = self.lock();
+        let usage = keys.entry(key.to_string()).or_default();
+        periods.roll(usage);
+
+        let checks = [
+            (
+                QuotaPeriod::Daily,
+                &usage.daily,
+                limits.daily_requests,
+                limits.daily_characters,
+            ),
+            (
+                QuotaPeriod::Monthly,
+                &usage.monthly,
+                limits.monthly_requests,
+                limits.monthly_characters,
+            ),
+        ];
+        for (period, counted, max_requests, max_characters) in checks {
+            let exceeded = max_requests
+                .filter(|&limit| counted.requests + 1 > limit)
+                .or_else(|| {
+                    max_characters.filter(|&limit| counted.characters + characters > limit)
+                });
+            if let Some(limit) = exceeded {
+                return Err(QuotaExceeded {
+                    period,
+                    limit,
+                    retry_after: periods.until_reset(period),
+                });
+            }
+        }
+
+        for counted in [&mut usage.daily, &mut usage.monthly] {
+            counted.requests += 1;
+            counted.characters += characters;
+        }
+        self.inner.dirty.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Takes back `requests` and `characters` counted for `key` by a
+    /// [`record`](Self::record) made at `recorded`, for work that was not
+    /// done. Periods that have ended since are left as they are.
+    pub fn refund(&self, key: &str, requests: u64, characters: u64, recorded: SystemTime) {
+        let periods = Periods::at(recorded);
+        let mut keys = self.lock();
+        let Some(usage) = keys.get_mut(key) else {
+            return;
+        };
+        for (counted, period) in [
+            (&mut usage.daily, &periods.day),
+            (&mut usage.monthly, &periods.month),
+        ] {
+            if counted.period == *period {
+                counted.requests = counted.requests.saturating_sub(requests);
+                counted.characters = counted.characters.saturating_sub(characters);
+            }
+        }
+        self.inner.dirty.store(true, Ordering::Release);
+    }
+
+    /// Drops the counters of periods that ended before `now`, and keys
+    /// left with none.
+    pub fn prune(&self, now: SystemTime) {
+        let periods = Periods::at(now);
+        let mut keys = self.lock();
+        let before = keys.len();
+        keys.retain(|_, usage| {
+            let current =
+                usage.daily.period == periods.day || usage.monthly.period == periods.month;
+            periods.roll(usage);
+            current
+        });
+        if keys.len() < before {
+            self.inner.dirty.store(true, Ordering::Release);
+        }
+    }
+
+    /// Usage of `key` in the periods current at `now`.
+    pub fn usage(&self, key: &str, now: SystemTime) -> KeyUsage {
+        let periods = Periods::at(now);
+        let mut usage = self.lock().get(key).cloned().unwrap_or_default();
+        periods.roll(&mut usage);
+        usage
+    }
+
+    /// Prunes counters of ended periods and saves the rest to the state
+    /// file if they changed since the last save.
+    ///
+    /// Blocking. The file is replaced atomically, so a crash mid-save keeps
+    /// the previous counters.
+    pub fn flush(&self) -> io::Result<()> {
+        self.prune(SystemTime::now());
+        let Some(path) = &self.inner.state_file else {
+            return Ok(());
+        };
+        if !self.inner.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        let state = StateFile {
+            keys: self.lock().clone(),
+        };
+        let result = write_atomically(path, &serde_json::to_vec(&state)?);
+        if result.is_err() {
+            self.inner.dirty.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, KeyUsage>> {
+        self.inner
+            .keys
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)
+}
+
+/// The quota periods containing a point in time.
+struct Periods {
+    day: String,
+    month: String,
+    until_next_day: Duration,
+    until_next_month: Duration,
+}
+
+impl Periods {
+    fn at(now: SystemTime) -> Self {
+        let seconds = unix_seconds(now);
+        let days = (seconds / SECS_PER_DAY).cast_signed();
+        let (year, month, day) = civil_from_days(days);
+
+        let (next_year, next_month) = if month == 12 {
+            (year + 1, 1)
+        } else {
+            (year, month + 1)
+        };
+        let next_month_start =
+            days_from_civil(next_year, next_month, 1).cast_unsigned() * SECS_PER_DAY;
+        let next_day_start = (seconds / SECS_PER_DAY + 1) * SECS_PER_DAY;
+
+        Self {
+            day: format!("{year:04}-{month:02}-{day:02}"),
+            month: format!("{year:04}-{month:02}"),
+            until_next_day: Duration::from_secs(next_day_start - seconds),
+            until_next_month: Duration::from_secs(next_month_start - seconds),
+        }
+    }
+
+    /// Resets counters left over from earlier periods.
+    fn roll(&self, usage: &mut KeyUsage) {
+        if usage.daily.period != self.day {
+            usage.daily = PeriodUsage {
+                period: self.day.clone(),
+                ..PeriodUsage::default()
+            };
+        }
+        if usage.monthly.period != self.month {
+            usage.monthly = PeriodUsage {
+                period: self.month.clone(),
+                ..PeriodUsage::default()
+            };
+        }
+    }
+
+    const fn until_reset(&self, period: QuotaPeriod) -> Duration {
+        match period {
+            QuotaPeriod::Daily => self.until_next_day,
+            QuotaPeriod::Monthly => self.until_next_month,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    /// 2024-03-31T23:00:00Z.
+    fn end_of_march() -> SystemTime {
+        UNIX_EPOCH + Duration::from_hours(475_535)
+    }
+
+    #[test]
+    fn counts_requests_and_characters() {
+        let tracker = UsageTracker::in_memory();
+        let limits = QuotaLimits::default();
+
+        assert!(tracker.record("a", 10, &limits, end_of_march()).is_ok());
+        assert!(tracker.record("a", 5, &limits, end_of_march()).is_ok());
+
+        let usage = tracker.usage("a", end_of_march());
+        assert_eq!(usage.daily.period, "2024-03-31");
+        assert_eq!(usage.daily.requests, 2);
+        assert_eq!(usage.monthly.characters, 15);
+        assert_eq!(tracker.usage("b", end_of_march()).daily.requests, 0);
+    }
+
+    #[test]
+    fn rejects_over_quota_until_period_resets() {
+        let tracker = UsageTracker::in_memory();
+        let limits = QuotaLimits {
+            daily_characters: Some(10),
+            monthly_requests: Some(2),
+            ..QuotaLimits::default()
+        };
+
+        assert!(tracker.record("a", 8, &limits, end_of_march()).is_ok());
+        assert_eq!(
+            tracker.record("a", 8, &limits, end_of_march()),
+            Err(QuotaExceeded {
+                period: QuotaPeriod::Daily,
+                limit: 10,
+                retry_after: Duration::from_hours(1),
+            })
+        );
+
+        let next_month = end_of_march() + Duration::from_hours(2);
+        assert!(tracker.record("a", 8, &limits, next_month).is_ok());
+        assert!(tracker.record("a", 1, &limits, next_month).is_ok());
+        assert!(matches!(
+            tracker.record("a", 0, &limits, next_month),
+            Err(QuotaExceeded {
+                period: QuotaPeriod::Monthly,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn refunds_only_periods_still_current() {
+        let tracker = UsageTracker::in_memory();
+        let limits = QuotaLimits::default();
+        assert!(tracker.record("a", 10, &limits, end_of_march()).is_ok());
+        assert!(tracker.record("a", 5, &limits, end_of_march()).is_ok());
+
+        tracker.refund("a", 1, 5, end_of_march());
+        let usage = tracker.usage("a", end_of_march());
+        assert_eq!((usage.daily.requests, usage.daily.characters), (1, 10));
+        assert_eq!((usage.monthly.requests, usage.monthly.characters), (1, 10));
+
+        // A refund for last month leaves this month's counters alone.
+        let next_month = end_of_march() + Duration::from_hours(2);
+        assert!(tracker.record("a", 7, &limits, next_month).is_ok());
+        tracker.refund("a", 1, 10, end_of_march());
+        assert_eq!(tracker.usage("a", next_month).monthly.characters, 7);
+    }
+
+    #[test]
+    fn prune_drops_keys_without_current_periods() {
+        let tracker = UsageTracker::in_memory();
+        let limits = QuotaLimits::default();
+        let earlier_in_march = end_of_march() - Duration::from_hours(48);
+        assert!(tracker
+            .record("earlier", 1, &limits, earlier_in_march)
+            .is_ok());
+        assert!(tracker.record("today", 2, &limits, end_of_march()).is_ok());
+
+        // Keys still counting this month are kept.
+        tracker.prune(end_of_march());
+        let mut keys: Vec<_> = tracker.lock().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["earlier", "today"]);
+
+        tracker.prune(end_of_march() + Duration::from_hours(2));
+        assert!(tracker.lock().is_empty());
+    }
+
+    #[test]
+    fn saved_counters_survive_reopen() {
+        let path = std::env::temp_dir().join(format!("{}-usage.json", std::process::id()));
+        let tracker = UsageTracker::open(&path).unwrap_or_else(|e| unreachable!("{e}"));
+        // Flushing prunes ended periods, so count in the current one.
+        let now = SystemTime::now();
+        assert!(tracker.record("a", 3, &QuotaLimits::default(), now).is_ok());
+        assert!(tracker.flush().is_ok());
+
+        let reopened = UsageTracker::open(&path).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(reopened.usage("a", now).daily.characters, 3);
+        fs::remove_file(&path).ok();
+    }
+}
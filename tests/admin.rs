@@ -0,0 +1,76 @@
+//! Tests for the separate admin listener.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{HeaderValue, Request, StatusCode},
+    Router,
+};
+use common::{check_request, send_json_to};
+use grammar_api::{create_routers, AdminConfig, AppRouters, Config};
+use serde_json::json;
+use tower::ServiceExt;
+
+fn routers(admin: Option<AdminConfig>) -> AppRouters {
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    config.auth.api_key = Some("secret".to_string());
+    config.server.admin = admin;
+    match create_routers(&config) {
+        Ok(routers) => routers,
+        Err(e) => panic!("Failed to build app: {e}"),
+    }
+}
+
+async fn get(router: &Router, uri: &str) -> StatusCode {
+    let request = match Request::builder().uri(uri).body(Body::empty()) {
+        Ok(request) => request,
+        Err(e) => panic!("Failed to build request: {e}"),
+    };
+    match router.clone().oneshot(request).await {
+        Ok(response) => response.status(),
+        Err(e) => panic!("Request failed: {e}"),
+    }
+}
+
+#[tokio::test]
+async fn admin_listener_takes_health_and_metrics_off_public_router() {
+    let AppRouters { public, admin, .. } = routers(Some(AdminConfig::default()));
+
+    assert_eq!(get(&admin, "/health").await, StatusCode::OK);
+    assert_eq!(get(&admin, "/metrics").await, StatusCode::OK);
+    assert_eq!(get(&admin, "/v1/usage").await, StatusCode::NOT_FOUND);
+
+    // Unknown to the public router, so they need a key like any other route.
+    assert_eq!(get(&public, "/health").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(get(&public, "/metrics").await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn public_router_still_serves_checks() {
+    let AppRouters { public, .. } = routers(Some(AdminConfig::default()));
+
+    let mut request = match check_request(&json!({ "text": "Hello." })) {
+        Ok(request) => request,
+        Err(e) => panic!("{e}"),
+    };
+    request
+        .headers_mut()
+        .insert("authorization", HeaderValue::from_static("Bearer secret"));
+    match send_json_to(public, request).await {
+        Ok((status, _)) => assert_eq!(status, StatusCode::OK),
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[tokio::test]
+async fn without_admin_listener_health_stays_public_and_open() {
+    let AppRouters { public, .. } = routers(None);
+
+    assert_eq!(get(&public, "/health").await, StatusCode::OK);
+    assert_eq!(get(&public, "/metrics").await, StatusCode::OK);
+    assert_eq!(get(&public, "/v1/usage").await, StatusCode::UNAUTHORIZED);
+}
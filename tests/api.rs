@@ -5,7 +5,8 @@
 mod common;
 
 use axum::http::StatusCode;
-use common::{get_health, get_matches, post_check};
+use common::{get_health, get_matches, post_check, post_check_json};
+use serde_json::json;
 
 #[tokio::test]
 async fn health_endpoint_returns_ok() {
@@ -296,3 +297,54 @@ async fn error_has_message() {
     let error = &matches[0];
     assert!(error["message"].is_string(), "Error should have message");
 }
+
+#[tokio::test]
+async fn check_accepts_dialect() {
+    let (status, result) =
+        match post_check_json(json!({ "text": "The colour is nice.", "dialect": "British" })).await
+        {
+            Ok(r) => r,
+            Err(e) => panic!("Request failed: {}", e),
+        };
+
+    assert_eq!(status, StatusCode::OK, "Dialect request should succeed");
+
+    let matches = match get_matches(&result) {
+        Some(m) => m,
+        None => panic!("Response missing matches array"),
+    };
+
+    assert!(
+        matches.is_empty(),
+        "British spelling should pass with British dialect, got {:?}",
+        matches
+    );
+}
+
+#[tokio::test]
+async fn complete_check_is_not_partial() {
+    let result = match post_check("This is an test.").await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    };
+
+    assert_eq!(result["partial"], false, "Check should not be partial");
+    assert!(result["reason"].is_null(), "Complete check has no reason");
+}
+
+#[tokio::test]
+async fn expired_deadline_returns_partial_result() {
+    let payload = json!({ "text": "This is an test.\n\nI saw a elephant.", "timeoutMs": 0 });
+    let (status, result) = match post_check_json(payload).await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    };
+
+    assert_eq!(status, StatusCode::OK, "Partial check should still succeed");
+    assert_eq!(result["partial"], true, "Check should be partial");
+    assert_eq!(result["reason"], "DEADLINE_EXCEEDED");
+    assert!(
+        result["matches"].is_array(),
+        "Partial check should return matches array"
+    );
+}
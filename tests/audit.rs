@@ -0,0 +1,302 @@
+//! Tests for the hash-chained audit log and its verification.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use common::{app_with, check, TempPath};
+use grammar_api::{
+    audit::{self, AuditError},
+    Config, QuotaLimits,
+};
+use serde_json::{json, Value};
+use std::{fs, path::Path, process::Command};
+use tower::ServiceExt;
+
+/// Secret the test logs are chained under.
+const CHAIN_KEY: &str = "paprika";
+
+/// Writes the audit log to `path`, chained under [`CHAIN_KEY`].
+fn log_to(config: &mut Config, path: &Path) {
+    config.audit.file = Some(path.to_path_buf());
+    config.audit.chain_key = Some(CHAIN_KEY.to_string());
+}
+
+fn verify(path: &Path) -> Result<audit::AuditSummary, AuditError> {
+    audit::verify(path, CHAIN_KEY)
+}
+
+fn read(path: &Path) -> String {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => panic!("Failed to read {}: {e}", path.display()),
+    }
+}
+
+fn write(path: &Path, contents: &str) {
+    if let Err(e) = fs::write(path, contents) {
+        panic!("Failed to write {}: {e}", path.display());
+    }
+}
+
+fn records(path: &Path) -> Vec<Value> {
+    read(path)
+        .lines()
+        .map(|line| match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => panic!("Audit line is not JSON ({e}): {line}"),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn records_checks_without_their_text() {
+    let dir = TempPath::dir("audit-records");
+    let path = dir.path().join("audit.log");
+    let app = app_with(|config| {
+        log_to(config, &path);
+        config.audit.hash_salt = Some("pepper".to_string());
+        config.quota.default = QuotaLimits {
+            daily_requests: Some(1),
+            ..QuotaLimits::default()
+        };
+    });
+
+    let text = "This sentense has a typo.";
+    assert_eq!(check(&app, text).await.0, StatusCode::OK);
+    assert_eq!(check(&app, text).await.0, StatusCode::TOO_MANY_REQUESTS);
+
+    assert!(!read(&path).contains("sentense"));
+    let records = records(&path);
+    assert_eq!(records.len(), 2);
+
+    let first = &records[0];
+    assert_eq!(first["seq"], 1);
+    assert_eq!(first["key"], "anonymous");
+    assert_eq!(first["endpoint"], "check");
+    assert_eq!(first["characters"], text.chars().count());
+    assert_eq!(first["outcome"], "ok");
+    assert!(first["matches"].as_u64().is_some_and(|m| m > 0), "{first}");
+    assert!(
+        first["text_hash"].as_str().is_some_and(|h| h.len() == 64),
+        "{first}"
+    );
+    assert!(first["time"].as_str().is_some_and(|t| t.ends_with('Z')));
+
+    let second = &records[1];
+    assert_eq!(second["outcome"], "QUOTA_EXCEEDED");
+    assert!(second.get("matches").is_none(), "{second}");
+    assert_eq!(second["text_hash"], first["text_hash"]);
+    assert_eq!(second["prev"], first["hash"]);
+
+    match verify(&path) {
+        Ok(summary) => {
+            assert_eq!(summary.records, 2);
+            assert!(summary.from_start);
+            assert_eq!(summary.last_seq, Some(2));
+            assert_eq!(summary.last_hash.as_deref(), second["hash"].as_str());
+        }
+        Err(e) => panic!("Intact log failed verification: {e}"),
+    }
+}
+
+#[tokio::test]
+async fn verify_detects_edited_and_removed_records() {
+    let dir = TempPath::dir("audit-tamper");
+    let path = dir.path().join("audit.log");
+    let app = app_with(|config| log_to(config, &path));
+    for text in ["One.", "Two words.", "Three small words."] {
+        assert_eq!(check(&app, text).await.0, StatusCode::OK);
+    }
+    let original = read(&path);
+    let lines: Vec<&str> = original.lines().collect();
+
+    let verify_cli = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_grammar-api"))
+            .args(["audit", "verify"])
+            .arg(&path)
+            .env("AUDIT_CHAIN_KEY", CHAIN_KEY)
+            .output();
+        match output {
+            Ok(output) => output,
+            Err(e) => panic!("Failed to run audit verify: {e}"),
+        }
+    };
+    let output = verify_cli();
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("3 records"));
+
+    write(
+        &path,
+        &original.replacen("\"characters\":10", "\"characters\":1", 1),
+    );
+    match verify(&path) {
+        Err(AuditError::Tampered { line, reason, .. }) => {
+            assert_eq!(line, 2);
+            assert_eq!(reason, "hash does not match the record");
+        }
+        other => panic!("Edited record was not detected: {other:?}"),
+    }
+    assert_eq!(verify_cli().status.code(), Some(1));
+
+    write(&path, &format!("{}\n{}\n", lines[0], lines[2]));
+    match verify(&path) {
+        Err(AuditError::Tampered { line, .. }) => assert_eq!(line, 2),
+        other => panic!("Removed record was not detected: {other:?}"),
+    }
+
+    // Without a rotation anchor, the chain must start at the first record.
+    write(&path, &format!("{}\n{}\n", lines[1], lines[2]));
+    match verify(&path) {
+        Err(AuditError::Tampered { line, reason, .. }) => {
+            assert_eq!(line, 1);
+            assert_eq!(
+                reason,
+                "log starts at record 2 without an anchor for the ones before"
+            );
+        }
+        other => panic!("Removed first record was not detected: {other:?}"),
+    }
+
+    // The chain cannot be checked, or rebuilt, without its key.
+    write(&path, &original);
+    assert!(verify(&path).is_ok());
+    assert!(matches!(
+        audit::verify(&path, "other"),
+        Err(AuditError::Tampered { line: 1, .. })
+    ));
+
+    write(&path, &format!("{}\n{}\n{}", lines[0], lines[1], lines[2]));
+    match verify(&path) {
+        Err(AuditError::Tampered { line, reason, .. }) => {
+            assert_eq!(line, 3);
+            assert_eq!(reason, "record is incomplete");
+        }
+        other => panic!("Incomplete record was not detected: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn chain_continues_across_rotation_and_restart() {
+    let temp = TempPath::dir("audit-rotation");
+    let dir = temp.path();
+    let path = dir.join("audit.log");
+    let configure = |config: &mut Config| {
+        log_to(config, &path);
+        // Every record after the first starts a new file.
+        config.audit.max_file_bytes = 1;
+        config.audit.max_files = 2;
+    };
+
+    let first = app_with(configure);
+    for _ in 0..4 {
+        assert_eq!(check(&first, "Hello.").await.0, StatusCode::OK);
+    }
+    let restarted = app_with(configure);
+    assert_eq!(check(&restarted, "Hello again.").await.0, StatusCode::OK);
+
+    assert!(!dir.join("audit.log.3").exists());
+    assert_eq!(records(&dir.join("audit.log.2"))[0]["seq"], 3);
+    assert_eq!(records(&path)[0]["seq"], 5);
+
+    match verify(&path) {
+        Ok(summary) => {
+            assert_eq!(summary.files, 3);
+            assert_eq!(summary.records, 3);
+            assert_eq!(summary.first_seq, Some(3));
+            assert!(!summary.from_start);
+            assert_eq!(summary.last_seq, Some(5));
+        }
+        Err(e) => panic!("Rotated log failed verification: {e}"),
+    }
+
+    // Rotation anchors the chain where it dropped records, so dropping
+    // more by hand is caught.
+    assert!(dir.join("audit.log.anchor").exists());
+    if let Err(e) = fs::remove_file(dir.join("audit.log.2")) {
+        panic!("{e}");
+    }
+    assert!(matches!(verify(&path), Err(AuditError::Tampered { .. })));
+}
+
+/// Posts `body` to `uri`, with `token` as the bearer token if given.
+async fn post(app: &Router, uri: &str, body: &Value, token: Option<&str>) -> StatusCode {
+    let mut builder = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        builder = builder.header("authorization", format!("Bearer {token}"));
+    }
+    let request = match builder.body(Body::from(body.to_string())) {
+        Ok(request) => request,
+        Err(e) => panic!("Failed to build request: {e}"),
+    };
+    match app.clone().oneshot(request).await {
+        Ok(response) => response.status(),
+        Err(e) => panic!("Request failed: {e}"),
+    }
+}
+
+#[tokio::test]
+async fn records_rejected_requests_and_feedback() {
+    let dir = TempPath::dir("audit-rejected");
+    let path = dir.path().join("audit.log");
+    let app = app_with(|config| {
+        log_to(config, &path);
+        config.auth.api_key = Some("secret".to_string());
+        config.rate_limit.enabled = true;
+        config.rate_limit.per_second = 1;
+        config.rate_limit.burst = 4;
+    });
+
+    let text = json!({ "text": "Hello." });
+    let feedback = json!({ "rule": "Spelling", "verdict": "accepted" });
+    let expected = [
+        (post(&app, "/v1/check", &text, None).await, "UNAUTHORIZED"),
+        (
+            post(&app, "/v1/check", &json!({}), Some("secret")).await,
+            "MISSING_FIELD",
+        ),
+        (post(&app, "/v1/check", &text, Some("secret")).await, "ok"),
+        (
+            post(&app, "/v1/feedback", &feedback, Some("secret")).await,
+            "ok",
+        ),
+        (
+            post(&app, "/v1/check", &text, Some("secret")).await,
+            "RATE_LIMITED",
+        ),
+    ];
+    let statuses: Vec<StatusCode> = expected.iter().map(|(status, _)| *status).collect();
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::UNAUTHORIZED,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            StatusCode::OK,
+            StatusCode::NO_CONTENT,
+            StatusCode::TOO_MANY_REQUESTS,
+        ]
+    );
+
+    let records = records(&path);
+    let outcomes: Vec<&str> = records
+        .iter()
+        .filter_map(|r| r["outcome"].as_str())
+        .collect();
+    let wanted: Vec<&str> = expected.iter().map(|(_, outcome)| *outcome).collect();
+    assert_eq!(outcomes, wanted);
+
+    assert_eq!(records[0]["key"], "anonymous");
+    assert_eq!(records[0]["characters"], 0);
+    assert_eq!(records[1]["key"], "default");
+    assert_eq!(records[3]["endpoint"], "feedback");
+    assert_eq!(records[4]["endpoint"], "check");
+    assert!(verify(&path).is_ok());
+}
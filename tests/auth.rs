@@ -0,0 +1,189 @@
+//! Tests for API key authentication with a key store.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use common::send_json_to;
+use grammar_api::{create_app, hash_secret, Config, JwtConfig};
+use http_body_util::BodyExt;
+use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tower::ServiceExt;
+
+static STORE_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn hex(secret: &str) -> String {
+    hash_secret(secret)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn app_with_keys(store: &str) -> Router {
+    let id = STORE_ID.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("{}-{id}-keys.toml", std::process::id()));
+    if let Err(e) = std::fs::write(&path, store) {
+        panic!("Failed to write key store: {e}");
+    }
+
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    config.auth.key_store = Some(path);
+    match create_app(&config) {
+        Ok(app) => app,
+        Err(e) => panic!("Failed to build app: {e}"),
+    }
+}
+
+fn app_with_jwt() -> Router {
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    config.auth.jwt = Some(JwtConfig {
+        secret: Some("jwt-secret".to_string()),
+        audience: vec!["grammar-api".to_string()],
+        ..JwtConfig::default()
+    });
+    match create_app(&config) {
+        Ok(app) => app,
+        Err(e) => panic!("Failed to build app: {e}"),
+    }
+}
+
+fn jwt(scope: &str, expires_in: i64) -> String {
+    let exp = get_current_timestamp().saturating_add_signed(expires_in);
+    let claims = json!({
+        "sub": "user-1",
+        "tenant": "acme",
+        "aud": "grammar-api",
+        "scope": scope,
+        "exp": exp
+    });
+    match encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(b"jwt-secret"),
+    ) {
+        Ok(token) => token,
+        Err(e) => panic!("Failed to sign token: {e}"),
+    }
+}
+
+fn key_store() -> String {
+    format!(
+        r#"
+[[keys]]
+name = "docs-team"
+secret_sha256 = "{}"
+
+[[keys]]
+name = "reporting"
+secret_sha256 = "{}"
+scopes = ["usage"]
+
+[[keys]]
+name = "retired"
+secret_sha256 = "{}"
+expires_at = 2020-01-01
+"#,
+        hex("docs-secret"),
+        hex("reporting-secret"),
+        hex("retired-secret"),
+    )
+}
+
+async fn check_with_key(app: Router, key: Option<&str>) -> (StatusCode, Value) {
+    let mut builder = Request::builder()
+        .method("POST")
+        .uri("/v1/check")
+        .header("content-type", "application/json");
+    if let Some(key) = key {
+        builder = builder.header("authorization", format!("Bearer {key}"));
+    }
+
+    let request = match builder.body(Body::from(json!({ "text": "Hello." }).to_string())) {
+        Ok(req) => req,
+        Err(e) => panic!("Failed to build request: {e}"),
+    };
+
+    match send_json_to(app, request).await {
+        Ok(response) => response,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[tokio::test]
+async fn accepts_key_from_store() {
+    let (status, _) = check_with_key(app_with_keys(&key_store()), Some("docs-secret")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn rejects_missing_and_unknown_keys() {
+    let app = app_with_keys(&key_store());
+
+    let (status, body) = check_with_key(app.clone(), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "UNAUTHORIZED");
+
+    let (status, _) = check_with_key(app, Some("wrong-secret")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn rejects_expired_key() {
+    let (status, _) = check_with_key(app_with_keys(&key_store()), Some("retired-secret")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn rejects_key_without_check_scope() {
+    let (status, body) =
+        check_with_key(app_with_keys(&key_store()), Some("reporting-secret")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "FORBIDDEN");
+}
+
+#[tokio::test]
+async fn accepts_valid_jwt() {
+    let app = app_with_jwt();
+    let (status, _) = check_with_key(app.clone(), Some(&jwt("check", 600))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Token subjects are not metric labels; their tenant is.
+    let request = match Request::builder().uri("/metrics").body(Body::empty()) {
+        Ok(request) => request,
+        Err(e) => panic!("Failed to build request: {e}"),
+    };
+    let metrics = match app.oneshot(request).await {
+        Ok(response) => match response.into_body().collect().await {
+            Ok(body) => String::from_utf8_lossy(&body.to_bytes()).into_owned(),
+            Err(e) => panic!("Failed to read body: {e}"),
+        },
+        Err(e) => panic!("Request failed: {e}"),
+    };
+    assert!(
+        metrics.contains(r#"api_requests{endpoint="check",key="jwt",tenant="acme"}"#),
+        "{metrics}"
+    );
+    assert!(!metrics.contains("user-1"), "{metrics}");
+}
+
+#[tokio::test]
+async fn rejects_expired_jwt() {
+    let (status, body) = check_with_key(app_with_jwt(), Some(&jwt("check", -600))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "UNAUTHORIZED");
+}
+
+#[tokio::test]
+async fn rejects_jwt_without_check_scope() {
+    let (status, _) = check_with_key(app_with_jwt(), Some(&jwt("usage", 600))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
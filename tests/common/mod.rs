@@ -10,15 +10,115 @@ use axum::{
     http::{Request, StatusCode},
     Router,
 };
-use grammar_api::create_app_for_testing;
+use grammar_api::{create_app, create_app_for_testing, Config};
 use http_body_util::BodyExt;
 use serde_json::{json, Value};
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 use tower::ServiceExt;
 
 fn create_test_app() -> Router {
     create_app_for_testing()
 }
 
+/// The default configuration with rate limiting off, adjusted by
+/// `configure`.
+pub fn config_with(configure: impl FnOnce(&mut Config)) -> Config {
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    configure(&mut config);
+    config
+}
+
+/// An app built from [`config_with`].
+pub fn app_with(configure: impl FnOnce(&mut Config)) -> Router {
+    match create_app(&config_with(configure)) {
+        Ok(app) => app,
+        Err(e) => panic!("Failed to build app: {e}"),
+    }
+}
+
+/// Checks `text` against `app`.
+pub async fn check(app: &Router, text: &str) -> (StatusCode, Value) {
+    let request = match check_request(&json!({ "text": text })) {
+        Ok(request) => request,
+        Err(e) => panic!("{e}"),
+    };
+    match send_json_to(app.clone(), request).await {
+        Ok(response) => response,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// A JSON request to `uri`, with `body` if given.
+pub fn request(method: &str, uri: &str, body: Option<&Value>) -> Request<Body> {
+    let builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    match builder.body(body) {
+        Ok(request) => request,
+        Err(e) => panic!("Failed to build request: {e}"),
+    }
+}
+
+/// A file or directory in the temp directory, unique to this test process
+/// and removed when dropped.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    /// A path named after `name` that does not exist yet.
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("grammar-api-test-{}-{name}", std::process::id()));
+        let temp = Self(path);
+        temp.remove();
+        temp
+    }
+
+    /// Like [`new`](Self::new), holding `contents`.
+    pub fn with_contents(name: &str, contents: &str) -> Self {
+        let temp = Self::new(name);
+        if let Err(e) = fs::write(&temp.0, contents) {
+            panic!("Failed to write {}: {e}", temp.0.display());
+        }
+        temp
+    }
+
+    /// Like [`new`](Self::new), as an empty directory.
+    pub fn dir(name: &str) -> Self {
+        let temp = Self::new(name);
+        if let Err(e) = fs::create_dir_all(&temp.0) {
+            panic!("Failed to create {}: {e}", temp.0.display());
+        }
+        temp
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    fn remove(&self) {
+        if self.0.is_dir() {
+            fs::remove_dir_all(&self.0).ok();
+        } else {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
 pub async fn post_check(text: &str) -> Result<Value, String> {
     let app = create_test_app();
 
@@ -48,6 +148,45 @@ pub async fn post_check(text: &str) -> Result<Value, String> {
     }
 }
 
+pub async fn post_check_json(payload: Value) -> Result<(StatusCode, Value), String> {
+    send_json(check_request(&payload)?).await
+}
+
+pub fn check_request(payload: &Value) -> Result<Request<Body>, String> {
+    Request::builder()
+        .method("POST")
+        .uri("/v1/check")
+        .header("content-type", "application/json")
+        .body(Body::from(payload.to_string()))
+        .map_err(|e| format!("Failed to build request: {}", e))
+}
+
+pub async fn send_json(request: Request<Body>) -> Result<(StatusCode, Value), String> {
+    send_json_to(create_test_app(), request).await
+}
+
+pub async fn send_json_to(
+    app: Router,
+    request: Request<Body>,
+) -> Result<(StatusCode, Value), String> {
+    let response = match app.oneshot(request).await {
+        Ok(resp) => resp,
+        Err(e) => return Err(format!("Request failed: {}", e)),
+    };
+
+    let status = response.status();
+
+    let body = match response.into_body().collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => return Err(format!("Failed to read body: {}", e)),
+    };
+
+    match serde_json::from_slice(&body) {
+        Ok(json) => Ok((status, json)),
+        Err(e) => Err(format!("Failed to parse JSON: {}", e)),
+    }
+}
+
 pub async fn get_health() -> Result<(StatusCode, String), String> {
     let app = create_test_app();
 
@@ -105,3 +244,56 @@ pub fn has_replacement(error: &Value, expected: &str) -> bool {
         false
     }
 }
+
+/// A running server binary, killed if a test fails before stopping it.
+pub struct Server(pub Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.0.kill().ok();
+        self.0.wait().ok();
+    }
+}
+
+pub fn free_port() -> u16 {
+    match TcpListener::bind("127.0.0.1:0").and_then(|l| l.local_addr()) {
+        Ok(addr) => addr.port(),
+        Err(e) => panic!("Failed to find a free port: {e}"),
+    }
+}
+
+/// Connects to `addr`, retrying while the server starts.
+pub fn connect(addr: SocketAddr) -> TcpStream {
+    let start = Instant::now();
+    loop {
+        match TcpStream::connect(addr) {
+            Ok(stream) => return stream,
+            Err(e) if start.elapsed() > Duration::from_secs(30) => {
+                panic!("Server did not start: {e}")
+            }
+            Err(_) => sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+/// Waits until the server at `addr` reports ready, which it only does once
+/// its dictionary has loaded.
+pub fn wait_until_ready(addr: SocketAddr) {
+    let start = Instant::now();
+    loop {
+        let mut stream = connect(addr);
+        let mut response = String::new();
+        let request = "GET /readyz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        let sent = stream
+            .write_all(request.as_bytes())
+            .and_then(|()| stream.read_to_string(&mut response));
+        if sent.is_ok() && response.starts_with("HTTP/1.1 200") {
+            return;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(30),
+            "Server did not become ready: {response}"
+        );
+        sleep(Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,111 @@
+//! Tests for operator-defined house-style rules.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{app_with, check, config_with, get_matches, TempPath};
+use grammar_api::{create_app, ConfigError};
+use serde_json::{json, Value};
+
+const RULES: &str = r#"
+[[rules]]
+name = "github"
+phrase = "GitHub"
+message = "Write GitHub with a capital H."
+replacement = "GitHub"
+severity = "error"
+
+[[rules]]
+name = "leverage"
+words = ["leverage"]
+message = "Prefer use."
+severity = "info"
+"#;
+
+async fn check_matches(app: &Router, text: &str) -> Vec<Value> {
+    match check(app, text).await {
+        (StatusCode::OK, body) => get_matches(&body).cloned().unwrap_or_default(),
+        (status, body) => panic!("Check failed with {status}: {body}"),
+    }
+}
+
+#[tokio::test]
+async fn custom_rules_report_matches_next_to_harper() {
+    let rules = TempPath::with_contents("rules-matches.toml", RULES);
+    let app = app_with(|config| config.lint.custom_rules = Some(rules.path().to_path_buf()));
+
+    let matches = check_matches(&app, "We leverage Github and GitHub to host thier code.").await;
+    let custom: Vec<&Value> = matches
+        .iter()
+        .filter(|m| {
+            m["rule"]["id"]
+                .as_str()
+                .is_some_and(|id| id.starts_with("custom:"))
+        })
+        .collect();
+    assert_eq!(custom.len(), 2, "{matches:?}");
+
+    let leverage = custom[0];
+    assert_eq!(leverage["rule"]["id"], "custom:leverage");
+    assert_eq!(leverage["rule"]["severity"], "info");
+    assert_eq!(leverage["offset"], 3);
+    assert_eq!(leverage["replacements"], json!([]));
+
+    let github = custom[1];
+    assert_eq!(
+        github["rule"],
+        json!({ "id": "custom:github", "category": "style", "severity": "error" })
+    );
+    assert_eq!(github["message"], "Write GitHub with a capital H.");
+    assert_eq!(github["offset"], 12);
+    assert_eq!(github["length"], 6);
+    assert_eq!(github["replacements"], json!(["GitHub"]));
+    assert_eq!(
+        github["context"]["text"],
+        "We leverage Github and GitHub to host "
+    );
+
+    // Harper's own rules still run and carry no severity.
+    let spelling = matches.iter().find(|m| m["rule"]["category"] == "spelling");
+    match spelling {
+        Some(spelling) => assert!(spelling["rule"].get("severity").is_none()),
+        None => panic!("No spelling match in {matches:?}"),
+    }
+}
+
+#[tokio::test]
+async fn custom_rules_match_across_paragraphs() {
+    let rules = TempPath::with_contents(
+        "rules-paragraphs.toml",
+        "[[rules]]\nname = \"pr\"\nphrase = \"pull request\"\nmessage = \"Say PR.\"\n",
+    );
+    let app = app_with(|config| config.lint.custom_rules = Some(rules.path().to_path_buf()));
+
+    let matches = check_matches(&app, "Open a pull\n\nrequest for it.").await;
+    let pr: Vec<&Value> = matches
+        .iter()
+        .filter(|m| m["rule"]["id"] == "custom:pr")
+        .collect();
+    assert_eq!(pr.len(), 1, "{matches:?}");
+    assert_eq!(pr[0]["offset"], 7);
+    assert_eq!(pr[0]["length"], 13);
+}
+
+#[tokio::test]
+async fn invalid_rules_file_fails_startup() {
+    let rules = TempPath::with_contents(
+        "rules-invalid.toml",
+        "[[rules]]\nname = \"broken\"\npattern = \"(\"\nmessage = \"m\"\n",
+    );
+    let config = config_with(|config| config.lint.custom_rules = Some(rules.path().to_path_buf()));
+    let result = create_app(&config);
+    match result {
+        Err(ConfigError::CustomRules { message, .. }) => {
+            assert!(message.contains("\"broken\""), "{message}");
+        }
+        Err(e) => panic!("Unexpected error: {e}"),
+        Ok(_) => panic!("Invalid rules were accepted"),
+    }
+}
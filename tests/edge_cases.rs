@@ -4,7 +4,13 @@
 
 mod common;
 
-use common::{get_matches, post_check};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::{get_matches, post_check, post_check_json, send_json};
+use grammar_api::MAX_TEXT_SIZE;
+use serde_json::json;
 
 #[tokio::test]
 async fn handles_empty_text() {
@@ -404,3 +410,113 @@ async fn offset_calculation_with_unicode() {
 
     assert!(!matches.is_empty(), "Should detect error after unicode");
 }
+
+#[tokio::test]
+async fn offsets_are_absolute_across_paragraphs() {
+    let text = "This is fine.\n\nThis is an test.";
+    let result = match post_check(text).await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    };
+
+    let matches = match get_matches(&result) {
+        Some(m) => m,
+        None => panic!("Response missing matches array"),
+    };
+
+    let offset = match matches.first().and_then(|m| m["offset"].as_u64()) {
+        Some(o) => o,
+        None => panic!("Expected a match in the second paragraph"),
+    };
+
+    assert_eq!(offset, 23, "Offset should be relative to the full text");
+}
+
+#[tokio::test]
+async fn handles_large_document_with_many_paragraphs() {
+    let paragraphs = 3000;
+    let text = "This is an test.\n\n".repeat(paragraphs);
+    let result = match post_check(&text).await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    };
+
+    let matches = match get_matches(&result) {
+        Some(m) => m,
+        None => panic!("Response missing matches array"),
+    };
+
+    let offsets: Vec<u64> = matches
+        .iter()
+        .filter_map(|m| m["offset"].as_u64())
+        .collect();
+    let expected: Vec<u64> = (0..paragraphs as u64).map(|i| i * 18 + 8).collect();
+
+    assert_eq!(offsets, expected, "Each paragraph should report one match");
+}
+
+#[tokio::test]
+async fn long_paragraph_reports_each_match_once() {
+    let sentences = 2000;
+    let text = "This is an test. ".repeat(sentences);
+    let result = match post_check(&text).await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    };
+
+    let matches = match get_matches(&result) {
+        Some(m) => m,
+        None => panic!("Response missing matches array"),
+    };
+
+    let offsets: Vec<u64> = matches
+        .iter()
+        .filter_map(|m| m["offset"].as_u64())
+        .collect();
+    let expected: Vec<u64> = (0..sentences as u64).map(|i| i * 17 + 8).collect();
+
+    assert_eq!(
+        offsets, expected,
+        "Overlapping sections should not duplicate matches"
+    );
+}
+
+#[tokio::test]
+async fn rejects_text_over_limit_with_json_error() {
+    let text = "a".repeat(MAX_TEXT_SIZE + 1);
+    let (status, result) = match post_check_json(json!({ "text": text })).await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    };
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(result["code"], "PAYLOAD_TOO_LARGE");
+    assert_eq!(
+        result["limit"], MAX_TEXT_SIZE,
+        "Error should report the limit"
+    );
+}
+
+#[tokio::test]
+async fn rejects_oversized_body_before_reading_it() {
+    let declared = 64 * MAX_TEXT_SIZE;
+    let request = match Request::builder()
+        .method("POST")
+        .uri("/v1/check")
+        .header("content-type", "application/json")
+        .header("content-length", declared)
+        .body(Body::from(r#"{"text": "short"}"#))
+    {
+        Ok(req) => req,
+        Err(e) => panic!("Failed to build request: {}", e),
+    };
+
+    let (status, result) = match send_json(request).await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    };
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(result["code"], "PAYLOAD_TOO_LARGE");
+    assert!(result["limit"].is_number(), "Error should report the limit");
+}
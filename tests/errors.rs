@@ -0,0 +1,105 @@
+//! Error response tests for the grammar API.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::send_json;
+use serde_json::Value;
+
+async fn send(
+    method: &str,
+    uri: &str,
+    content_type: Option<&str>,
+    body: &str,
+) -> (StatusCode, Value) {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(content_type) = content_type {
+        builder = builder.header("content-type", content_type);
+    }
+
+    let request = match builder.body(Body::from(body.to_string())) {
+        Ok(req) => req,
+        Err(e) => panic!("Failed to build request: {}", e),
+    };
+
+    match send_json(request).await {
+        Ok(r) => r,
+        Err(e) => panic!("Request failed: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn malformed_json_returns_invalid_json() {
+    let (status, body) = send("POST", "/v1/check", Some("application/json"), "{\"text\": ").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "INVALID_JSON");
+    assert!(body["error"].is_string(), "Error should have a message");
+}
+
+#[tokio::test]
+async fn missing_text_returns_missing_field() {
+    let (status, body) = send("POST", "/v1/check", Some("application/json"), "{}").await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "MISSING_FIELD");
+    assert_eq!(body["field"], "text");
+}
+
+#[tokio::test]
+async fn wrong_field_type_returns_invalid_field() {
+    let (status, body) = send(
+        "POST",
+        "/v1/check",
+        Some("application/json"),
+        "{\"text\": 5}",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "INVALID_FIELD");
+    assert_eq!(body["field"], "text");
+}
+
+#[tokio::test]
+async fn wrong_content_type_returns_unsupported_media_type() {
+    let (status, body) = send(
+        "POST",
+        "/v1/check",
+        Some("text/plain"),
+        "{\"text\": \"Hi\"}",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(body["code"], "UNSUPPORTED_MEDIA_TYPE");
+}
+
+#[tokio::test]
+async fn missing_content_type_returns_unsupported_media_type() {
+    let (status, body) = send("POST", "/v1/check", None, "{\"text\": \"Hi\"}").await;
+
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(body["code"], "UNSUPPORTED_MEDIA_TYPE");
+}
+
+#[tokio::test]
+async fn unknown_route_returns_not_found() {
+    let (status, body) = send("GET", "/v1/nope", None, "").await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "NOT_FOUND");
+}
+
+#[tokio::test]
+async fn wrong_method_returns_method_not_allowed() {
+    let (status, body) = send("GET", "/v1/check", None, "").await;
+
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body["code"], "METHOD_NOT_ALLOWED");
+}
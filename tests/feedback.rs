@@ -0,0 +1,154 @@
+//! Tests for match feedback and its per-rule summary.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{app_with, request, send_json_to, TempPath};
+use serde_json::{json, Value};
+use std::fs;
+use tower::ServiceExt;
+
+async fn report(app: &Router, body: &Value) -> StatusCode {
+    match app
+        .clone()
+        .oneshot(request("POST", "/v1/feedback", Some(body)))
+        .await
+    {
+        Ok(response) => response.status(),
+        Err(e) => panic!("Request failed: {e}"),
+    }
+}
+
+async fn summary(app: &Router) -> Value {
+    match send_json_to(app.clone(), request("GET", "/v1/feedback/summary", None)).await {
+        Ok((StatusCode::OK, body)) => body,
+        Ok((status, body)) => panic!("Summary failed with {status}: {body}"),
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[tokio::test]
+async fn summary_reports_false_positive_rates_per_rule() {
+    let file = TempPath::new("feedback-summary.jsonl");
+    let path = file.path();
+    let first = app_with(|config| config.feedback.file = Some(path.to_path_buf()));
+
+    for (rule, verdict) in [
+        ("Spelling", "accepted"),
+        ("Spelling", "accepted"),
+        ("Spelling", "false_positive"),
+        ("Spelling", "bad_suggestion"),
+        ("Capitalization", "false_positive"),
+    ] {
+        let body = json!({ "rule": rule, "verdict": verdict, "context": "the ____ sat" });
+        assert_eq!(report(&first, &body).await, StatusCode::NO_CONTENT);
+    }
+
+    let counts = summary(&first).await;
+    assert_eq!(counts["reports"], 5);
+    assert_eq!(
+        counts["rules"],
+        json!([
+            {
+                "rule": "Capitalization",
+                "reports": 1,
+                "falsePositives": 1,
+                "badSuggestions": 0,
+                "accepted": 0,
+                "falsePositiveRate": 1.0
+            },
+            {
+                "rule": "Spelling",
+                "reports": 4,
+                "falsePositives": 1,
+                "badSuggestions": 1,
+                "accepted": 2,
+                "falsePositiveRate": 0.25
+            }
+        ])
+    );
+
+    // Saved reports are counted again after a restart.
+    let restarted = app_with(|config| config.feedback.file = Some(path.to_path_buf()));
+    assert_eq!(summary(&restarted).await["reports"], 5);
+
+    let saved = match fs::read_to_string(path) {
+        Ok(saved) => saved,
+        Err(e) => panic!("Failed to read {}: {e}", path.display()),
+    };
+    let record: Value = match saved.lines().next().map(serde_json::from_str) {
+        Some(Ok(record)) => record,
+        other => panic!("No report saved: {other:?}"),
+    };
+    assert_eq!(record["key"], "anonymous");
+    assert_eq!(record["rule"], "Spelling");
+    assert_eq!(record["verdict"], "accepted");
+    assert_eq!(record["context"], "the ____ sat");
+}
+
+#[tokio::test]
+async fn rejects_unknown_verdicts_and_oversized_fields() {
+    let app = app_with(|config| config.feedback.max_context_chars = 10);
+
+    let cases = [
+        (json!({ "rule": "Spelling", "verdict": "wrong" }), "verdict"),
+        (json!({ "rule": "", "verdict": "accepted" }), "rule"),
+        (
+            json!({ "rule": "SpellCheck", "verdict": "accepted" }),
+            "rule",
+        ),
+        (
+            json!({ "rule": "custom:not-loaded", "verdict": "accepted" }),
+            "rule",
+        ),
+        (
+            json!({ "rule": "Spelling", "verdict": "accepted", "context": "eleven chars" }),
+            "context",
+        ),
+    ];
+    for (body, field) in cases {
+        match send_json_to(app.clone(), request("POST", "/v1/feedback", Some(&body))).await {
+            Ok((status, error)) => {
+                assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{error}");
+                assert_eq!(error["field"], field);
+            }
+            Err(e) => panic!("{e}"),
+        }
+    }
+    assert_eq!(summary(&app).await["reports"], 0);
+}
+
+#[tokio::test]
+async fn privacy_mode_drops_context() {
+    let file = TempPath::new("feedback-privacy.jsonl");
+    let path = file.path();
+    let app = app_with(|config| {
+        config.privacy.enabled = true;
+        config.feedback.file = Some(path.to_path_buf());
+    });
+
+    let body = json!({ "rule": "Spelling", "verdict": "false_positive", "context": "Zyzzogeton" });
+    assert_eq!(report(&app, &body).await, StatusCode::NO_CONTENT);
+
+    let saved = match fs::read_to_string(path) {
+        Ok(saved) => saved,
+        Err(e) => panic!("Failed to read {}: {e}", path.display()),
+    };
+    assert!(saved.contains("\"rule\":\"Spelling\""), "{saved}");
+    assert!(!saved.contains("Zyzzogeton"), "{saved}");
+}
+
+#[tokio::test]
+async fn accepts_loaded_custom_rules() {
+    let contents =
+        "[[rules]]\nname = \"leverage\"\nwords = [\"leverage\"]\nmessage = \"Prefer use.\"\n";
+    let rules = TempPath::with_contents("feedback-rules.toml", contents);
+    let app = app_with(|config| config.lint.custom_rules = Some(rules.path().to_path_buf()));
+
+    let body = json!({ "rule": "custom:leverage", "verdict": "false_positive" });
+    assert_eq!(report(&app, &body).await, StatusCode::NO_CONTENT);
+    let body = json!({ "rule": "Word Choice", "verdict": "accepted" });
+    assert_eq!(report(&app, &body).await, StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,243 @@
+//! Tests for the gRPC service, over a real HTTP/2 connection.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+use grammar_api::proto::{
+    check_result::Outcome, grammar_checker_client::GrammarCheckerClient, CheckBatchRequest,
+    CheckRequest, Dialect, ListRulesRequest,
+};
+use grammar_api::{create_app, Config, GrpcConfig, ListenAddress, ServerListener};
+use std::future::pending;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tonic::{transport::Channel, Code};
+
+type Client = GrammarCheckerClient<Channel>;
+
+/// Serves the app with gRPC on the main port and returns its address.
+async fn serve(config: Config) -> SocketAddr {
+    let router = match create_app(&config) {
+        Ok(router) => router,
+        Err(e) => panic!("Failed to build app: {e}"),
+    };
+    let address = ListenAddress::Tcp("127.0.0.1:0".to_string());
+    let listener = match ServerListener::bind(&address, None).await {
+        Ok(listener) => listener,
+        Err(e) => panic!("Failed to bind: {e}"),
+    };
+    let addr = match &listener {
+        ServerListener::Tcp(listener) => match listener.local_addr() {
+            Ok(addr) => addr,
+            Err(e) => panic!("{e}"),
+        },
+        #[cfg(unix)]
+        ServerListener::Unix { .. } => panic!("Expected a TCP listener"),
+    };
+    tokio::spawn(listener.serve(router, None, pending()));
+    addr
+}
+
+fn config() -> Config {
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    config.server.grpc = Some(GrpcConfig::default());
+    config
+}
+
+async fn client(addr: SocketAddr) -> Client {
+    match Channel::from_shared(format!("http://{addr}")) {
+        Ok(endpoint) => match endpoint.connect().await {
+            Ok(channel) => GrammarCheckerClient::new(channel),
+            Err(e) => panic!("Failed to connect: {e}"),
+        },
+        Err(e) => panic!("{e}"),
+    }
+}
+
+fn check(id: &str, text: &str) -> CheckRequest {
+    CheckRequest {
+        id: id.to_string(),
+        text: text.to_string(),
+        ..CheckRequest::default()
+    }
+}
+
+fn with_key<T>(message: T, key: &str) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    match format!("Bearer {key}").parse() {
+        Ok(value) => {
+            request.metadata_mut().insert("authorization", value);
+        }
+        Err(e) => panic!("{e}"),
+    }
+    request
+}
+
+#[tokio::test]
+async fn check_finds_spelling_errors_next_to_http() {
+    let addr = serve(config()).await;
+    let mut client = client(addr).await;
+
+    let response = match client.check(check("", "This sentense has a typo.")).await {
+        Ok(response) => response.into_inner(),
+        Err(status) => panic!("Check failed: {status}"),
+    };
+    assert!(!response.partial);
+    assert!(
+        response
+            .matches
+            .iter()
+            .any(|m| m.rule.as_ref().is_some_and(|r| r.category == "spelling")),
+        "{response:?}"
+    );
+
+    // HTTP/1.1 keeps working on the same port.
+    let mut stream = match TcpStream::connect(addr).await {
+        Ok(stream) => stream,
+        Err(e) => panic!("Failed to connect: {e}"),
+    };
+    let request = "GET /livez HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let mut response = String::new();
+    if let Err(e) = stream.write_all(request.as_bytes()).await {
+        panic!("{e}");
+    }
+    if let Err(e) = stream.read_to_string(&mut response).await {
+        panic!("{e}");
+    }
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+}
+
+#[tokio::test]
+async fn requires_the_same_credentials_as_http() {
+    let mut config = config();
+    config.auth.api_key = Some("secret".to_string());
+    let mut client = client(serve(config).await).await;
+
+    match client.check(check("", "Hello.")).await {
+        Err(status) => {
+            assert_eq!(status.code(), Code::Unauthenticated);
+            assert_eq!(
+                status
+                    .metadata()
+                    .get("error-code")
+                    .and_then(|v| v.to_str().ok()),
+                Some("UNAUTHORIZED")
+            );
+        }
+        Ok(response) => panic!("Expected Unauthenticated, got {response:?}"),
+    }
+
+    let result = client.check(with_key(check("", "Hello."), "secret")).await;
+    assert!(result.is_ok(), "{result:?}");
+}
+
+#[tokio::test]
+async fn batch_reports_errors_per_item() {
+    let mut config = config();
+    config.limits.max_text_size = 16;
+    config.limits.max_body_size = Some(1024);
+    let mut client = client(serve(config).await).await;
+
+    let batch = CheckBatchRequest {
+        requests: vec![
+            check("short", "Hello."),
+            check("long", "This text is longer than sixteen bytes."),
+            CheckRequest {
+                dialect: 99,
+                ..check("dialect", "Hi.")
+            },
+        ],
+    };
+    let results = match client.check_batch(batch).await {
+        Ok(response) => response.into_inner().results,
+        Err(status) => panic!("Batch failed: {status}"),
+    };
+
+    let ids: Vec<_> = results.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, ["short", "long", "dialect"]);
+    assert!(matches!(results[0].outcome, Some(Outcome::Response(_))));
+    let codes: Vec<_> = results[1..]
+        .iter()
+        .map(|r| match &r.outcome {
+            Some(Outcome::Error(e)) => e.code.as_str(),
+            other => panic!("Expected an error, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(codes, ["PAYLOAD_TOO_LARGE", "INVALID_FIELD"]);
+}
+
+#[tokio::test]
+async fn batch_over_the_limit_is_rejected() {
+    let mut config = config();
+    config.limits.max_batch_size = 2;
+    let mut client = client(serve(config).await).await;
+
+    let batch = |size: usize| CheckBatchRequest {
+        requests: (0..size).map(|i| check(&i.to_string(), "Hello.")).collect(),
+    };
+    match client.check_batch(batch(3)).await {
+        Err(status) => {
+            assert_eq!(status.code(), Code::InvalidArgument);
+            assert_eq!(
+                status
+                    .metadata()
+                    .get("error-code")
+                    .and_then(|v| v.to_str().ok()),
+                Some("INVALID_FIELD")
+            );
+        }
+        Ok(response) => panic!("Oversized batch was accepted: {response:?}"),
+    }
+
+    let results = match client.check_batch(batch(2)).await {
+        Ok(response) => response.into_inner().results,
+        Err(status) => panic!("Batch failed: {status}"),
+    };
+    assert_eq!(results.len(), 2);
+}
+
+#[tokio::test]
+async fn list_rules_describes_enabled_rules() {
+    let mut client = client(serve(config()).await).await;
+
+    let request = ListRulesRequest {
+        dialect: Dialect::British.into(),
+    };
+    let rules = match client.list_rules(request).await {
+        Ok(response) => response.into_inner().rules,
+        Err(status) => panic!("ListRules failed: {status}"),
+    };
+
+    assert!(rules.iter().any(|r| r.enabled));
+    assert!(rules.iter().all(|r| !r.name.is_empty()));
+    assert!(rules.windows(2).all(|w| w[0].name < w[1].name));
+}
+
+#[tokio::test]
+async fn stream_answers_each_request_in_order() {
+    let mut client = client(serve(config()).await).await;
+
+    let requests = tokio_stream::iter(vec![
+        check("1", "This sentense has a typo."),
+        check("2", "This sentence is fine."),
+    ]);
+    let mut results = match client.check_stream(requests).await {
+        Ok(response) => response.into_inner(),
+        Err(status) => panic!("CheckStream failed: {status}"),
+    };
+
+    let mut ids = Vec::new();
+    loop {
+        let message = results.message().await;
+        match message {
+            Ok(Some(result)) => {
+                assert!(matches!(result.outcome, Some(Outcome::Response(_))));
+                ids.push(result.id);
+            }
+            Ok(None) => break,
+            Err(status) => panic!("Stream failed: {status}"),
+        }
+    }
+    assert_eq!(ids, ["1", "2"]);
+}
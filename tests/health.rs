@@ -0,0 +1,154 @@
+//! Tests for the liveness and readiness probes.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use common::send_json_to;
+use grammar_api::{Config, Health, ReloadableApp, Startup};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use tower::ServiceExt;
+
+fn new_app() -> ReloadableApp {
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    match ReloadableApp::new(&config) {
+        Ok(app) => app,
+        Err(e) => panic!("Failed to build app: {e}"),
+    }
+}
+
+fn get(uri: &str) -> Request<Body> {
+    match Request::builder().uri(uri).body(Body::empty()) {
+        Ok(request) => request,
+        Err(e) => panic!("Failed to build request: {e}"),
+    }
+}
+
+async fn get_text(router: Router, uri: &str) -> (StatusCode, String) {
+    let response = match router.oneshot(get(uri)).await {
+        Ok(response) => response,
+        Err(e) => panic!("Request failed: {e}"),
+    };
+    let status = response.status();
+    let body = match response.into_body().collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => panic!("Failed to read body: {e}"),
+    };
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+async fn get_json(router: Router, uri: &str) -> (StatusCode, Value) {
+    match send_json_to(router, get(uri)).await {
+        Ok(response) => response,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[tokio::test]
+async fn probes_pass_when_ready() {
+    let app = new_app();
+
+    assert_eq!(
+        get_text(app.router(), "/livez").await,
+        (StatusCode::OK, "ok".to_string())
+    );
+    assert_eq!(
+        get_text(app.router(), "/readyz").await,
+        (StatusCode::OK, "ok".to_string())
+    );
+}
+
+#[tokio::test]
+async fn verbose_probe_reports_details() {
+    let app = new_app();
+    let (status, body) = get_json(app.router(), "/readyz?verbose").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    // Resolved from Cargo.lock, e.g. "0.29.1".
+    assert!(
+        body["harperVersion"]
+            .as_str()
+            .is_some_and(|v| v.split('.').all(|part| part.parse::<u32>().is_ok())),
+        "Got: {body}"
+    );
+    assert!(body["uptimeSecs"].is_u64());
+    assert!(body["dictionaryWords"].as_u64().is_some_and(|n| n > 0));
+    assert_eq!(body["checks"]["queue"], "ok");
+    assert_eq!(body["checks"]["dictionary"], "ok");
+}
+
+#[tokio::test]
+async fn readiness_fails_once_shutdown_starts() {
+    let app = new_app();
+    app.health().start_shutdown();
+
+    let (status, body) = get_text(app.router(), "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body, "unavailable: shutting down");
+    assert_eq!(
+        get_text(app.router(), "/health").await.0,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    assert_eq!(get_text(app.router(), "/livez").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn readiness_fails_after_bad_reload() {
+    let app = new_app();
+    app.health()
+        .set_config_error(Some("invalid port".to_string()));
+
+    let (status, body) = get_json(app.router(), "/readyz?verbose").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["checks"]["config"], "last reload failed: invalid port");
+
+    app.health().set_config_error(None);
+    assert_eq!(get_text(app.router(), "/readyz").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn readiness_fails_while_the_dictionary_loads() {
+    let health = Health::new();
+    let startup = Startup::new(health.clone());
+
+    assert_eq!(
+        get_text(startup.router(), "/livez").await,
+        (StatusCode::OK, "ok".to_string())
+    );
+    assert_eq!(
+        get_text(startup.router(), "/readyz").await,
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "unavailable: dictionary is loading".to_string()
+        )
+    );
+    let (status, body) = get_json(startup.router(), "/readyz?verbose").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["dictionary"], "dictionary is loading");
+    assert_eq!(body["dictionaryWords"], 0);
+    let (status, body) = get_json(startup.router(), "/v1/check").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["code"], "STARTING");
+
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    let app = match ReloadableApp::with_health(&config, health) {
+        Ok(app) => app,
+        Err(e) => panic!("Failed to build app: {e}"),
+    };
+    startup.ready(app.router());
+    assert_eq!(
+        get_text(startup.router(), "/readyz").await,
+        (StatusCode::OK, "ok".to_string())
+    );
+}
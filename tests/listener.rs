@@ -0,0 +1,187 @@
+//! Tests for serving on several listeners and Unix domain sockets.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+use grammar_api::{create_app, Config, ListenAddress, ServerListener};
+use std::future::pending;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn config() -> Config {
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    config
+}
+
+async fn bind(address: &str, mode: Option<u32>) -> ServerListener {
+    let address = match address.parse::<ListenAddress>() {
+        Ok(address) => address,
+        Err(e) => panic!("{e}"),
+    };
+    match ServerListener::bind(&address, mode).await {
+        Ok(listener) => listener,
+        Err(e) => panic!("Failed to bind {address}: {e}"),
+    }
+}
+
+fn serve(listener: ServerListener) {
+    serve_with(listener, &config());
+}
+
+fn serve_with(listener: ServerListener, config: &Config) {
+    let router = match create_app(config) {
+        Ok(router) => router,
+        Err(e) => panic!("Failed to build app: {e}"),
+    };
+    tokio::spawn(listener.serve(router, None, pending()));
+}
+
+/// Sends a bare HTTP/1.1 GET and returns the response head and body.
+async fn get<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, path: &str) -> String {
+    let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+    if let Err(e) = stream.write_all(request.as_bytes()).await {
+        panic!("Failed to send request: {e}");
+    }
+    let mut response = String::new();
+    if let Err(e) = stream.read_to_string(&mut response).await {
+        panic!("Failed to read response: {e}");
+    }
+    response
+}
+
+/// Sends a bare HTTP/1.1 check request and returns the response.
+async fn post_check<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S) -> String {
+    let body = r#"{"text":"Hello."}"#;
+    let request = format!(
+        "POST /v1/check HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(e) = stream.write_all(request.as_bytes()).await {
+        panic!("Failed to send request: {e}");
+    }
+    let mut response = String::new();
+    if let Err(e) = stream.read_to_string(&mut response).await {
+        panic!("Failed to read response: {e}");
+    }
+    response
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn serves_on_unix_socket_with_configured_mode() {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::UnixStream;
+
+    let dir = std::env::temp_dir().join(format!("grammar-api-listener-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create {}: {e}", dir.display());
+    }
+    let path = dir.join("api.sock");
+    // A socket left over from an earlier run is replaced.
+    drop(bind(&format!("unix:{}", path.display()), None).await);
+
+    serve(bind(&format!("unix:{}", path.display()), Some(0o660)).await);
+
+    let mode = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata.permissions().mode() & 0o777,
+        Err(e) => panic!("Socket missing: {e}"),
+    };
+    assert_eq!(mode, 0o660);
+
+    let stream = match UnixStream::connect(&path).await {
+        Ok(stream) => stream,
+        Err(e) => panic!("Failed to connect: {e}"),
+    };
+    let response = get(stream, "/livez").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn refuses_to_replace_regular_file() {
+    let path =
+        std::env::temp_dir().join(format!("grammar-api-listener-{}.txt", std::process::id()));
+    if let Err(e) = std::fs::write(&path, "keep me") {
+        panic!("Failed to write {}: {e}", path.display());
+    }
+
+    let address = ListenAddress::Unix(path.clone());
+    assert!(ServerListener::bind(&address, None).await.is_err());
+    assert!(path.is_file());
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn serves_same_app_on_several_tcp_listeners() {
+    let mut addrs = Vec::new();
+    for _ in 0..2 {
+        let listener = bind("127.0.0.1:0", None).await;
+        let addr = match &listener {
+            ServerListener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => addr,
+                Err(e) => panic!("{e}"),
+            },
+            #[cfg(unix)]
+            ServerListener::Unix { .. } => panic!("Expected a TCP listener"),
+        };
+        serve(listener);
+        addrs.push(addr);
+    }
+
+    for addr in addrs {
+        let stream = match TcpStream::connect(addr).await {
+            Ok(stream) => stream,
+            Err(e) => panic!("Failed to connect to {addr}: {e}"),
+        };
+        let response = get(stream, "/livez").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{addr}: {response}");
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn rate_limits_unix_socket_clients_by_user() {
+    use grammar_api::RateLimitOverride;
+    use std::os::unix::fs::MetadataExt;
+    use tokio::net::UnixStream;
+
+    let path = std::env::temp_dir().join(format!(
+        "grammar-api-listener-{}-rl.sock",
+        std::process::id()
+    ));
+    let listener = bind(&format!("unix:{}", path.display()), None).await;
+    // The socket file is owned by this process's user, which connects.
+    let uid = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata.uid(),
+        Err(e) => panic!("Socket missing: {e}"),
+    };
+
+    let mut config = config();
+    config.rate_limit.enabled = true;
+    config.rate_limit.burst = 5;
+    config.rate_limit.keys.insert(
+        format!("uid:{uid}"),
+        RateLimitOverride {
+            per_second: 1,
+            burst: 1,
+        },
+    );
+    serve_with(listener, &config);
+
+    let mut responses = Vec::new();
+    for _ in 0..2 {
+        let stream = match UnixStream::connect(&path).await {
+            Ok(stream) => stream,
+            Err(e) => panic!("Failed to connect: {e}"),
+        };
+        responses.push(post_check(stream).await);
+    }
+    assert!(responses[0].starts_with("HTTP/1.1 200"), "{}", responses[0]);
+    assert!(responses[1].starts_with("HTTP/1.1 429"), "{}", responses[1]);
+
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,91 @@
+//! Tests for the server's log output, run against the server binary.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use common::{connect, free_port, wait_until_ready, Server};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::process::{Command, Stdio};
+
+#[test]
+fn json_completion_line_describes_the_request() {
+    let port = free_port();
+    let child = Command::new(env!("CARGO_BIN_EXE_grammar-api"))
+        .env("HOST", "127.0.0.1")
+        .env("PORT", port.to_string())
+        .env("API_KEY", "secret")
+        .env("LOG_FORMAT", "json")
+        .env("LOG_LEVEL", "info")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut server = match child {
+        Ok(child) => Server(child),
+        Err(e) => panic!("Failed to start server: {e}"),
+    };
+
+    let text = "This sentense has a typo.";
+    let body = serde_json::json!({ "text": text }).to_string();
+    let request = format!(
+        "POST /v1/check HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer secret\r\n\
+         Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    wait_until_ready(addr);
+    let mut stream = connect(addr);
+    let mut response = String::new();
+    if let Err(e) = stream
+        .write_all(request.as_bytes())
+        .and_then(|()| stream.read_to_string(&mut response))
+    {
+        panic!("Request failed: {e}");
+    }
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    server.0.kill().ok();
+    server.0.wait().ok();
+    let mut output = String::new();
+    if let Some(mut stdout) = server.0.stdout.take() {
+        if let Err(e) = stdout.read_to_string(&mut output) {
+            panic!("Failed to read logs: {e}");
+        }
+    }
+
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => panic!("Log line is not JSON ({e}): {line}"),
+        })
+        .collect();
+    let completed = match lines
+        .iter()
+        .find(|l| l["message"] == "Request completed" && l["span"]["route"] == "/v1/check")
+    {
+        Some(line) => line,
+        None => panic!("No completion line in {output}"),
+    };
+
+    assert_eq!(completed["level"], "INFO");
+    assert_eq!(completed["status"], 200);
+    assert!(completed["latency_ms"].is_u64(), "{completed}");
+    let span = &completed["span"];
+    assert_eq!(span["key"], "default");
+    assert_eq!(span["method"], "POST");
+    assert_eq!(span["text_length"], text.len());
+    assert!(
+        span["matches"].as_u64().is_some_and(|m| m > 0),
+        "{completed}"
+    );
+    assert!(
+        span["request_id"]
+            .as_str()
+            .is_some_and(|id| !id.is_empty() && id != "unknown"),
+        "{completed}"
+    );
+    assert!(span.get("otel.name").is_none(), "{completed}");
+}
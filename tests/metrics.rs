@@ -0,0 +1,100 @@
+//! Tests for the metrics served on `/metrics`.
+//!
+//! The metrics recorder is global, so everything is checked in one test
+//! against the first app built in this process.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+    Router,
+};
+use common::{app_with, check_request};
+use http_body_util::BodyExt;
+use serde_json::json;
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    app_with(|config| {
+        config.auth.api_key = Some("secret".to_string());
+        config.rate_limit.enabled = true;
+        config.rate_limit.per_second = 1;
+        config.rate_limit.burst = 2;
+        config.metrics.duration_buckets_ms = vec![7, 70, 700];
+        config.metrics.text_size_buckets_bytes = vec![10, 1000];
+    })
+}
+
+fn check(text: &str, key: Option<&str>) -> Request<Body> {
+    let mut request = match check_request(&json!({ "text": text })) {
+        Ok(request) => request,
+        Err(e) => panic!("{e}"),
+    };
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 1], 1000))));
+    if let Some(key) = key {
+        let value = match format!("Bearer {key}").parse() {
+            Ok(value) => value,
+            Err(e) => panic!("Bad key {key}: {e}"),
+        };
+        request.headers_mut().insert("authorization", value);
+    }
+    request
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, String) {
+    let response = match app.clone().oneshot(request).await {
+        Ok(response) => response,
+        Err(e) => panic!("Request failed: {e}"),
+    };
+    let status = response.status();
+    match response.into_body().collect().await {
+        Ok(body) => (
+            status,
+            String::from_utf8_lossy(&body.to_bytes()).into_owned(),
+        ),
+        Err(e) => panic!("Failed to read body: {e}"),
+    }
+}
+
+#[tokio::test]
+async fn reports_matches_sizes_statuses_and_configured_buckets() {
+    let app = app();
+
+    let (status, _) = send(&app, check("Thsi sentense has typos.", Some("secret"))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, check("Hello.", Some("wrong"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = send(&app, check("Hello.", Some("secret"))).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let request = match Request::builder().uri("/metrics").body(Body::empty()) {
+        Ok(request) => request,
+        Err(e) => panic!("Failed to build request: {e}"),
+    };
+    let (status, metrics) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for expected in [
+        r#"api_request_duration_ms_bucket{endpoint="check",le="7"}"#,
+        r#"api_request_duration_ms_bucket{endpoint="check",le="700"}"#,
+        r#"api_text_size_bytes_bucket{endpoint="check",le="1000"} 1"#,
+        r#"api_rule_matches{rule="Spelling",category="spelling"} 2"#,
+        r#"api_requests{endpoint="check",key="default",tenant=""} 1"#,
+        r#"api_responses{route="/v1/check",status="200"} 1"#,
+        r#"api_responses{route="/v1/check",status="401"} 1"#,
+        r#"api_responses{route="/v1/check",status="429"} 1"#,
+        // Counted while the metrics request itself is in flight.
+        "api_requests_in_flight 1",
+        "lint_running 0",
+    ] {
+        assert!(metrics.contains(expected), "No {expected} in {metrics}");
+    }
+    assert!(!metrics.contains(r#"le="5""#), "{metrics}");
+}
@@ -0,0 +1,111 @@
+//! Tests that privacy mode keeps submitted text out of the server's output,
+//! run against the server binary.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use common::{connect, free_port, wait_until_ready, Server};
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::process::{Command, Stdio};
+
+/// Marker that only ever appears in submitted text.
+const SECRET: &str = "Zyzzogeton";
+
+/// Posts `body` to `path` and returns the raw response.
+fn post(port: u16, path: &str, body: &str) -> String {
+    let request = format!(
+        "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let mut stream = connect(SocketAddr::from(([127, 0, 0, 1], port)));
+    let mut response = String::new();
+    if let Err(e) = stream
+        .write_all(request.as_bytes())
+        .and_then(|()| stream.read_to_string(&mut response))
+    {
+        panic!("Request failed: {e}");
+    }
+    response
+}
+
+fn read_all(pipe: Option<impl Read>) -> String {
+    let mut output = String::new();
+    if let Some(mut pipe) = pipe {
+        if let Err(e) = pipe.read_to_string(&mut output) {
+            panic!("Failed to read output: {e}");
+        }
+    }
+    output
+}
+
+#[test]
+fn submitted_text_never_reaches_the_output() {
+    let port = free_port();
+    let child = Command::new(env!("CARGO_BIN_EXE_grammar-api"))
+        .env("HOST", "127.0.0.1")
+        .env("PORT", port.to_string())
+        .env("PRIVACY_MODE", "true")
+        .env("LOG_FORMAT", "json")
+        .env("LOG_LEVEL", "trace")
+        .env_remove("API_KEY")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut server = match child {
+        Ok(child) => Server(child),
+        Err(e) => panic!("Failed to start server: {e}"),
+    };
+    wait_until_ready(SocketAddr::from(([127, 0, 0, 1], port)));
+
+    let checked = post(
+        port,
+        &format!("/v1/check?draft={SECRET}"),
+        &serde_json::json!({ "text": format!("Thsi {SECRET} sentense has typos.") }).to_string(),
+    );
+    assert!(checked.starts_with("HTTP/1.1 200"), "{checked}");
+
+    let rejected = post(
+        port,
+        "/v1/check",
+        &serde_json::json!({ "text": "Fine.", "dialect": SECRET }).to_string(),
+    );
+    assert!(rejected.starts_with("HTTP/1.1 422"), "{rejected}");
+    assert!(rejected.contains("\"field\":\"dialect\""), "{rejected}");
+    assert!(!rejected.contains(SECRET), "{rejected}");
+
+    server.0.kill().ok();
+    server.0.wait().ok();
+    let stdout = read_all(server.0.stdout.take());
+    let stderr = read_all(server.0.stderr.take());
+
+    assert!(
+        stdout.contains("\"uri\":\"/v1/check\""),
+        "No request span in {stdout}"
+    );
+    assert!(!stdout.contains(SECRET), "Text leaked into logs: {stdout}");
+    assert!(
+        !stderr.contains(SECRET),
+        "Text leaked into stderr: {stderr}"
+    );
+}
+
+#[test]
+fn unrecognised_privacy_mode_fails_startup() {
+    let output = Command::new(env!("CARGO_BIN_EXE_grammar-api"))
+        .env("HOST", "127.0.0.1")
+        .env("PORT", free_port().to_string())
+        .env("PRIVACY_MODE", "yes")
+        .env_remove("API_KEY")
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => panic!("Failed to run server: {e}"),
+    };
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("PRIVACY_MODE"), "{stderr}");
+}
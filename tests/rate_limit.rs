@@ -0,0 +1,245 @@
+//! Tests for per-client rate limiting and the `X-RateLimit-*` headers.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+    response::Response,
+    Router,
+};
+use common::{app_with, check_request};
+use grammar_api::{Config, JwtConfig, RateLimitKey, RateLimitOverride};
+use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+use serde_json::json;
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+/// An app limiting each client to a burst of 2 at 1 request per second,
+/// adjusted by `configure`.
+fn app(configure: impl FnOnce(&mut Config)) -> Router {
+    app_with(|config| {
+        config.rate_limit.enabled = true;
+        config.rate_limit.per_second = 1;
+        config.rate_limit.burst = 2;
+        configure(config);
+    })
+}
+
+fn check_from(peer: &str, headers: &[(&'static str, &str)]) -> Request<Body> {
+    let mut request = match check_request(&json!({ "text": "Hello." })) {
+        Ok(request) => request,
+        Err(e) => panic!("{e}"),
+    };
+    let peer: SocketAddr = match peer.parse() {
+        Ok(peer) => peer,
+        Err(e) => panic!("Bad address {peer}: {e}"),
+    };
+    request.extensions_mut().insert(ConnectInfo(peer));
+    for (name, value) in headers {
+        let value = match value.parse() {
+            Ok(value) => value,
+            Err(e) => panic!("Bad header value {value}: {e}"),
+        };
+        request.headers_mut().insert(*name, value);
+    }
+    request
+}
+
+async fn send(app: &Router, request: Request<Body>) -> Response {
+    match app.clone().oneshot(request).await {
+        Ok(response) => response,
+        Err(e) => panic!("Request failed: {e}"),
+    }
+}
+
+fn header(response: &Response, name: &str) -> String {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[tokio::test]
+async fn limits_each_peer_and_reports_headers() {
+    let app = app(|_| {});
+
+    let first = send(&app, check_from("203.0.113.1:1000", &[])).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(header(&first, "x-ratelimit-limit"), "2");
+    assert_eq!(header(&first, "x-ratelimit-remaining"), "1");
+    assert_eq!(header(&first, "x-ratelimit-reset"), "1");
+
+    send(&app, check_from("203.0.113.1:1001", &[])).await;
+    let limited = send(&app, check_from("203.0.113.1:1002", &[])).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&limited, "x-ratelimit-remaining"), "0");
+    assert_eq!(header(&limited, "retry-after"), "1");
+
+    let other = send(&app, check_from("203.0.113.2:1000", &[])).await;
+    assert_eq!(other.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn rate_limited_error_has_code() {
+    let app = app(|config| config.rate_limit.burst = 1);
+
+    send(&app, check_from("203.0.113.1:1000", &[])).await;
+    let request = check_from("203.0.113.1:1000", &[]);
+    let (status, body) = match common::send_json_to(app, request).await {
+        Ok(response) => response,
+        Err(e) => panic!("{e}"),
+    };
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "RATE_LIMITED");
+}
+
+#[tokio::test]
+async fn client_ip_header_is_trusted_only_from_proxies() {
+    let app = app(|config| {
+        config.rate_limit.burst = 1;
+        config.rate_limit.key = RateLimitKey::ClientIpHeader;
+        config.rate_limit.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+    });
+
+    let proxied =
+        |client: &'static str| check_from("10.0.0.5:1000", &[("x-forwarded-for", client)]);
+    assert_eq!(
+        send(&app, proxied("198.51.100.1")).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, proxied("198.51.100.2")).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, proxied("198.51.100.1")).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // An untrusted peer cannot pick a fresh address with the header.
+    let spoofed =
+        |client: &'static str| check_from("203.0.113.9:1000", &[("x-forwarded-for", client)]);
+    assert_eq!(
+        send(&app, spoofed("192.0.2.1")).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, spoofed("192.0.2.2")).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn api_key_mode_applies_per_key_overrides() {
+    let app = app(|config| {
+        config.auth.api_key = Some("secret".to_string());
+        config.rate_limit.burst = 1;
+        config.rate_limit.key = RateLimitKey::ApiKey;
+        config.rate_limit.keys.insert(
+            "default".to_string(),
+            RateLimitOverride {
+                per_second: 1,
+                burst: 3,
+            },
+        );
+    });
+
+    let auth = [("authorization", "Bearer secret")];
+    for peer in ["203.0.113.1:1", "203.0.113.2:1", "203.0.113.3:1"] {
+        let response = send(&app, check_from(peer, &auth)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "x-ratelimit-limit"), "3");
+    }
+    assert_eq!(
+        send(&app, check_from("203.0.113.4:1", &auth))
+            .await
+            .status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn api_key_mode_keeps_token_subjects_apart_from_keys() {
+    let app = app(|config| {
+        config.auth.api_key = Some("secret".to_string());
+        config.auth.jwt = Some(JwtConfig {
+            secret: Some("jwt-secret".to_string()),
+            ..JwtConfig::default()
+        });
+        config.rate_limit.burst = 1;
+        config.rate_limit.key = RateLimitKey::ApiKey;
+        config.rate_limit.keys.insert(
+            "default".to_string(),
+            RateLimitOverride {
+                per_second: 1,
+                burst: 3,
+            },
+        );
+    });
+    let claims = json!({
+        "sub": "default",
+        "scope": "check",
+        "exp": get_current_timestamp() + 600
+    });
+    let token = match encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(b"jwt-secret"),
+    ) {
+        Ok(token) => format!("Bearer {token}"),
+        Err(e) => panic!("Failed to sign token: {e}"),
+    };
+
+    // The token's subject names the shared key, but gets neither its
+    // override nor its bucket.
+    let jwt = [("authorization", token.as_str())];
+    let response = send(&app, check_from("203.0.113.1:1", &jwt)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "x-ratelimit-limit"), "1");
+    assert_eq!(
+        send(&app, check_from("203.0.113.2:1", &jwt)).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    let key = [("authorization", "Bearer secret")];
+    let response = send(&app, check_from("203.0.113.3:1", &key)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "x-ratelimit-remaining"), "2");
+}
+
+#[tokio::test]
+async fn api_key_mode_limits_failed_auth_by_peer() {
+    let app = app(|config| {
+        config.auth.api_key = Some("secret".to_string());
+        config.rate_limit.key = RateLimitKey::ApiKey;
+    });
+
+    let bad = [("authorization", "Bearer wrong")];
+    let mut statuses = Vec::new();
+    for _ in 0..4 {
+        statuses.push(send(&app, check_from("203.0.113.1:1", &bad)).await.status());
+    }
+    assert_eq!(statuses[0], StatusCode::UNAUTHORIZED);
+    assert_eq!(statuses[3], StatusCode::TOO_MANY_REQUESTS, "{statuses:?}");
+
+    // A blocked peer is turned away even with a valid key; others are not.
+    let good = [("authorization", "Bearer secret")];
+    assert_eq!(
+        send(&app, check_from("203.0.113.1:1", &good))
+            .await
+            .status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(
+        send(&app, check_from("203.0.113.2:1", &good))
+            .await
+            .status(),
+        StatusCode::OK
+    );
+}
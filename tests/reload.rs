@@ -0,0 +1,94 @@
+//! Tests for custom word lists and configuration reloads.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{check, config_with, find_spelling_errors, get_matches, TempPath};
+use grammar_api::{Config, ConfigError, ReloadableApp};
+use serde_json::Value;
+use std::path::PathBuf;
+
+const TEXT: &str = "Zorblax is our product.";
+
+fn new_app(config: &Config) -> ReloadableApp {
+    match ReloadableApp::new(config) {
+        Ok(app) => app,
+        Err(e) => panic!("Failed to build app: {e}"),
+    }
+}
+
+fn spelling_error_count(result: &Value) -> usize {
+    get_matches(result).map_or(0, |m| find_spelling_errors(m).len())
+}
+
+#[tokio::test]
+async fn word_list_words_are_not_misspelled() {
+    let app = new_app(&config_with(|_| {}));
+    let (_, result) = check(&app.router(), TEXT).await;
+    assert_eq!(spelling_error_count(&result), 1, "Got: {result}");
+
+    let words = TempPath::with_contents("words.txt", "# names\nZorblax\n");
+    let app = new_app(&config_with(|config| {
+        config.dictionary.word_lists = vec![words.path().to_path_buf()];
+    }));
+
+    let (status, result) = check(&app.router(), TEXT).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(spelling_error_count(&result), 0, "Got: {result}");
+}
+
+#[tokio::test]
+async fn reload_applies_new_settings() {
+    let app = new_app(&config_with(|_| {}));
+    let (status, _) = check(&app.router(), TEXT).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let config = config_with(|config| config.auth.api_key = Some("reloaded-key".to_string()));
+    assert!(app.reload(&config).is_ok());
+
+    let (status, result) = check(&app.router(), TEXT).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(result["code"], "UNAUTHORIZED");
+}
+
+#[tokio::test]
+async fn failed_reload_keeps_previous_settings() {
+    let app = new_app(&config_with(|_| {}));
+
+    let config = config_with(|config| {
+        config.auth.api_key = Some("reloaded-key".to_string());
+        config.dictionary.word_lists = vec![PathBuf::from("/nonexistent/words.txt")];
+    });
+    assert!(matches!(
+        app.reload(&config),
+        Err(ConfigError::WordList { .. })
+    ));
+
+    let (status, _) = check(&app.router(), TEXT).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn reload_keeps_spent_rate_limits() {
+    let mut config = config_with(|config| {
+        config.rate_limit.enabled = true;
+        config.rate_limit.per_second = 1;
+        config.rate_limit.burst = 1;
+    });
+    let app = new_app(&config);
+    let (status, _) = check(&app.router(), TEXT).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Settings other than the rate limit's own leave its buckets in place.
+    config.lint.timeout_ms += 1;
+    assert!(app.reload(&config).is_ok());
+    let (status, result) = check(&app.router(), TEXT).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "Got: {result}");
+
+    config.rate_limit.burst = 2;
+    assert!(app.reload(&config).is_ok());
+    let (status, _) = check(&app.router(), TEXT).await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,131 @@
+//! Tests for systemd socket activation and readiness notification, run
+//! against the server binary.
+
+#![cfg(unix)]
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use common::Server;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixDatagram;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Waits for the notification `state`, skipping any others.
+fn expect_notification(socket: &UnixDatagram, state: &str) {
+    let mut buf = [0; 256];
+    loop {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e) => panic!("No {state} notification: {e}"),
+        };
+        if String::from_utf8_lossy(&buf[..len])
+            .lines()
+            .any(|l| l == state)
+        {
+            return;
+        }
+    }
+}
+
+/// Sends `GET path` to `addr` and returns the raw response.
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = match TcpStream::connect(addr) {
+        Ok(stream) => stream,
+        Err(e) => panic!("Failed to connect: {e}"),
+    };
+    let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+    let mut response = String::new();
+    if let Err(e) = stream
+        .write_all(request.as_bytes())
+        .and_then(|()| stream.read_to_string(&mut response))
+    {
+        panic!("Request failed: {e}");
+    }
+    response
+}
+
+#[test]
+fn serves_activated_socket_and_notifies_systemd() {
+    let dir = std::env::temp_dir().join(format!("grammar-api-systemd-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create {}: {e}", dir.display());
+    }
+    let notify_path = dir.join("notify.sock");
+    std::fs::remove_file(&notify_path).ok();
+    let notify = match UnixDatagram::bind(&notify_path) {
+        Ok(socket) => socket,
+        Err(e) => panic!("Failed to bind notify socket: {e}"),
+    };
+    notify.set_read_timeout(Some(Duration::from_secs(30))).ok();
+
+    let (activated, addr) = match TcpListener::bind("127.0.0.1:0") {
+        Ok(listener) => match listener.local_addr() {
+            Ok(addr) => (listener, addr),
+            Err(e) => panic!("{e}"),
+        },
+        Err(e) => panic!("Failed to bind: {e}"),
+    };
+    // Holding the configured port proves the server never binds it.
+    let (_occupied, port) = match TcpListener::bind("127.0.0.1:0") {
+        Ok(listener) => match listener.local_addr() {
+            Ok(addr) => (listener, addr.port()),
+            Err(e) => panic!("{e}"),
+        },
+        Err(e) => panic!("Failed to bind: {e}"),
+    };
+
+    // The activated socket is handed over as stdin, the only descriptor a
+    // child inherits without unsafe code.
+    let child = Command::new(env!("CARGO_BIN_EXE_grammar-api"))
+        .current_dir(&dir)
+        .env("LISTEN_FDS", "1")
+        .env("LISTEN_FDS_FIRST_FD", "0")
+        .env("NOTIFY_SOCKET", &notify_path)
+        .env("WATCHDOG_USEC", "200000")
+        .env("DRAIN_DELAY_SECS", "2")
+        .env("HOST", "127.0.0.1")
+        .env("PORT", port.to_string())
+        .stdin(Stdio::from(OwnedFd::from(activated)))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut server = match child {
+        Ok(child) => Server(child),
+        Err(e) => panic!("Failed to start server: {e}"),
+    };
+
+    expect_notification(&notify, "READY=1");
+
+    let response = get(addr, "/livez");
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    expect_notification(&notify, "WATCHDOG=1");
+
+    let pid = server.0.id().to_string();
+    match Command::new("kill").args(["-TERM", &pid]).status() {
+        Ok(status) => assert!(status.success()),
+        Err(e) => panic!("Failed to send SIGTERM: {e}"),
+    }
+    expect_notification(&notify, "STOPPING=1");
+    // Keep reading watchdog pings through the drain delay, as systemd
+    // would; a full socket blocks the server's notifications.
+    notify.set_read_timeout(Some(Duration::from_secs(1))).ok();
+    let drain = std::thread::spawn(move || {
+        let mut buf = [0; 256];
+        while notify.recv(&mut buf).is_ok() {}
+    });
+    // The listener stays up through the drain delay, failing readiness.
+    let response = get(addr, "/readyz");
+    assert!(response.starts_with("HTTP/1.1 503"), "{response}");
+    match server.0.wait() {
+        Ok(status) => assert!(status.success(), "{status}"),
+        Err(e) => panic!("{e}"),
+    }
+
+    drain.join().ok();
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,198 @@
+//! Tests for OpenTelemetry export, against an in-process OTLP collector.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use common::send_json_to;
+use grammar_api::{create_app, Config, Telemetry};
+use opentelemetry_proto::tonic::{
+    collector::{
+        metrics::v1::{
+            metrics_service_server::{MetricsService, MetricsServiceServer},
+            ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+        },
+        trace::v1::{
+            trace_service_server::{TraceService, TraceServiceServer},
+            ExportTraceServiceRequest, ExportTraceServiceResponse,
+        },
+    },
+    common::v1::{any_value::Value, KeyValue},
+    metrics::v1::Metric,
+    trace::v1::Span,
+};
+use serde_json::json;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Response, Status};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const CALLER_SPAN_ID: &str = "00f067aa0ba902b7";
+
+/// Keeps everything exported to it.
+#[derive(Debug, Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<Span>>>,
+    metrics: Arc<Mutex<Vec<Metric>>>,
+}
+
+#[tonic::async_trait]
+impl TraceService for Collector {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        let spans = request
+            .into_inner()
+            .resource_spans
+            .into_iter()
+            .flat_map(|r| r.scope_spans)
+            .flat_map(|s| s.spans);
+        self.spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(spans);
+        Ok(Response::new(ExportTraceServiceResponse::default()))
+    }
+}
+
+#[tonic::async_trait]
+impl MetricsService for Collector {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportMetricsServiceRequest>,
+    ) -> Result<Response<ExportMetricsServiceResponse>, Status> {
+        let metrics = request
+            .into_inner()
+            .resource_metrics
+            .into_iter()
+            .flat_map(|r| r.scope_metrics)
+            .flat_map(|s| s.metrics);
+        self.metrics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(metrics);
+        Ok(Response::new(ExportMetricsServiceResponse::default()))
+    }
+}
+
+/// Starts the collector and returns its endpoint.
+async fn start_collector(collector: Collector) -> String {
+    let listener = match TcpListener::bind("127.0.0.1:0").await {
+        Ok(listener) => listener,
+        Err(e) => panic!("Failed to bind: {e}"),
+    };
+    let addr = match listener.local_addr() {
+        Ok(addr) => addr,
+        Err(e) => panic!("{e}"),
+    };
+    let server = Server::builder()
+        .add_service(TraceServiceServer::new(collector.clone()))
+        .add_service(MetricsServiceServer::new(collector))
+        .serve_with_incoming(TcpListenerStream::new(listener));
+    tokio::spawn(server);
+    format!("http://{addr}")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn attribute<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a Value> {
+    attributes
+        .iter()
+        .find(|a| a.key == key)
+        .and_then(|a| a.value.as_ref())
+        .and_then(|v| v.value.as_ref())
+}
+
+fn int_attribute(span: &Span, key: &str) -> Option<i64> {
+    match attribute(&span.attributes, key) {
+        Some(Value::IntValue(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exports_check_traces_and_metrics() {
+    let collector = Collector::default();
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    config.telemetry.otlp_endpoint = Some(start_collector(collector.clone()).await);
+
+    let telemetry = match Telemetry::init(&config) {
+        Ok(telemetry) => telemetry,
+        Err(e) => panic!("Failed to set up telemetry: {e}"),
+    };
+    let app = match create_app(&config) {
+        Ok(app) => app,
+        Err(e) => panic!("Failed to build app: {e}"),
+    };
+
+    let text = "This sentense has a typo.";
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/check")
+        .header("content-type", "application/json")
+        .header("traceparent", format!("00-{TRACE_ID}-{CALLER_SPAN_ID}-01"))
+        .body(Body::from(json!({ "text": text }).to_string()));
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => panic!("Failed to build request: {e}"),
+    };
+    let (status, body) = match send_json_to(app, request).await {
+        Ok(response) => response,
+        Err(e) => panic!("{e}"),
+    };
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let matches = body["matches"].as_array().map_or(0, Vec::len);
+    assert!(matches > 0, "{body}");
+
+    if let Err(e) = tokio::task::spawn_blocking(move || telemetry.shutdown()).await {
+        panic!("{e}");
+    }
+
+    let spans = collector
+        .spans
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let request_span = match spans.iter().find(|s| s.name == "POST /v1/check") {
+        Some(span) => span,
+        None => panic!("No request span in {spans:?}"),
+    };
+    assert_eq!(hex(&request_span.trace_id), TRACE_ID);
+    assert_eq!(hex(&request_span.parent_span_id), CALLER_SPAN_ID);
+
+    let child = |name: &str| match spans
+        .iter()
+        .find(|s| s.name == name && s.parent_span_id == request_span.span_id)
+    {
+        Some(span) => span,
+        None => panic!("No {name} span under the request span in {spans:?}"),
+    };
+    let length = i64::try_from(text.len()).unwrap_or(i64::MAX);
+    assert_eq!(int_attribute(child("parse"), "text_length"), Some(length));
+    assert_eq!(int_attribute(child("lint"), "text_length"), Some(length));
+    assert!(int_attribute(child("lint"), "matches").is_some_and(|m| m > 0));
+    assert_eq!(
+        int_attribute(child("serialize"), "matches"),
+        i64::try_from(matches).ok()
+    );
+
+    let metrics = collector
+        .metrics
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    assert!(
+        metrics.iter().any(|m| m.name == "api.requests"),
+        "{metrics:?}"
+    );
+    assert!(
+        metrics.iter().any(|m| m.name == "api.request_duration_ms"),
+        "{metrics:?}"
+    );
+}
@@ -0,0 +1,275 @@
+//! Tests for TLS termination, certificate reload and client certificates.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+use grammar_api::{create_app, Config, TlsConfig, TlsTerminator};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair,
+};
+use serde_json::Value;
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::{
+    rustls::{
+        crypto::ring,
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName},
+        ClientConfig, RootCertStore,
+    },
+    TlsConnector,
+};
+
+static DIR_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn ok<T, E: std::fmt::Display>(result: Result<T, E>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+struct Ca {
+    certificate: Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Self {
+        let key = ok(KeyPair::generate());
+        let mut params = ok(CertificateParams::new(Vec::<String>::new()));
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Test CA");
+        let certificate = ok(params.self_signed(&key));
+        Self { certificate, key }
+    }
+
+    /// Issues a certificate, returning its PEM and the PEM of its key.
+    fn issue(&self, common_name: &str, purpose: ExtendedKeyUsagePurpose) -> (String, String) {
+        let key = ok(KeyPair::generate());
+        let mut params = ok(CertificateParams::new(vec!["localhost".to_string()]));
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "acme");
+        params.extended_key_usages = vec![purpose];
+        let certificate = ok(params.signed_by(&key, &self.certificate, &self.key));
+        (certificate.pem(), key.serialize_pem())
+    }
+}
+
+struct Server {
+    addr: SocketAddr,
+    terminator: TlsTerminator,
+    dir: PathBuf,
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "{}-tls-{}",
+        std::process::id(),
+        DIR_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    ok(std::fs::create_dir_all(&dir));
+    dir
+}
+
+async fn start(ca: &Ca, common_name: &str, client_ca: bool, api_key: Option<&str>) -> Server {
+    let dir = temp_dir();
+    let (cert, key) = ca.issue(common_name, ExtendedKeyUsagePurpose::ServerAuth);
+    ok(std::fs::write(dir.join("cert.pem"), cert));
+    ok(std::fs::write(dir.join("key.pem"), key));
+    ok(std::fs::write(dir.join("ca.pem"), ca.certificate.pem()));
+
+    let tls = TlsConfig {
+        cert_file: dir.join("cert.pem"),
+        key_file: dir.join("key.pem"),
+        client_ca_file: client_ca.then(|| dir.join("ca.pem")),
+        ..TlsConfig::default()
+    };
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    config.auth.api_key = api_key.map(str::to_string);
+    let app = ok(create_app(&config));
+
+    let terminator = ok(TlsTerminator::load(&tls));
+    let listener = ok(TcpListener::bind("127.0.0.1:0").await);
+    let addr = ok(listener.local_addr());
+    let listener = ok(terminator.listen(listener));
+    let service = terminator.make_service(app);
+    tokio::spawn(async move { axum::serve(listener, service).await });
+
+    Server {
+        addr,
+        terminator,
+        dir,
+    }
+}
+
+fn client_config(ca: &Ca, client: Option<(String, String)>) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    ok(roots.add(ca.certificate.der().clone()));
+    let builder = ok(
+        ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions(),
+    )
+    .with_root_certificates(roots);
+
+    match client {
+        Some((cert, key)) => {
+            let chain = vec![ok(CertificateDer::from_pem_slice(cert.as_bytes()))];
+            let key = ok(PrivateKeyDer::from_pem_slice(key.as_bytes()));
+            ok(builder.with_client_auth_cert(chain, key))
+        }
+        None => builder.with_no_client_auth(),
+    }
+}
+
+/// Sends a GET over TLS, returning the status code, body and the DER of
+/// the certificate the server presented.
+async fn get(
+    addr: SocketAddr,
+    config: ClientConfig,
+    path: &str,
+    headers: &str,
+) -> (u16, String, Vec<u8>) {
+    let connector = TlsConnector::from(Arc::new(config));
+    let tcp = ok(TcpStream::connect(addr).await);
+    let name = ok(ServerName::try_from("localhost"));
+    let mut stream = ok(connector.connect(name, tcp).await);
+    let server_cert = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .map(|cert| cert.as_ref().to_vec())
+        .unwrap_or_default();
+
+    let request =
+        format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{headers}\r\n");
+    ok(stream.write_all(request.as_bytes()).await);
+    let mut response = String::new();
+    ok(stream.read_to_string(&mut response).await);
+
+    let status = response
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or_default();
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    (status, body, server_cert)
+}
+
+#[tokio::test]
+async fn serves_https() {
+    let ca = Ca::new();
+    let server = start(&ca, "server", false, None).await;
+
+    let (status, body, _) = get(server.addr, client_config(&ca, None), "/livez", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(body, "ok");
+}
+
+#[tokio::test]
+async fn reloads_changed_certificates() {
+    let ca = Ca::new();
+    let server = start(&ca, "server", false, None).await;
+    let (_, _, before) = get(server.addr, client_config(&ca, None), "/livez", "").await;
+
+    let (cert, key) = ca.issue("renewed", ExtendedKeyUsagePurpose::ServerAuth);
+    ok(std::fs::write(server.dir.join("cert.pem"), cert));
+    ok(std::fs::write(server.dir.join("key.pem"), key));
+    ok(server.terminator.reload());
+
+    let (status, _, after) = get(server.addr, client_config(&ca, None), "/livez", "").await;
+    assert_eq!(status, 200);
+    assert_ne!(before, after);
+}
+
+#[tokio::test]
+async fn failed_reload_keeps_serving_previous_certificates() {
+    let ca = Ca::new();
+    let server = start(&ca, "server", false, None).await;
+
+    ok(std::fs::write(
+        server.dir.join("cert.pem"),
+        "not a certificate",
+    ));
+    assert!(server.terminator.reload().is_err());
+
+    let (status, _, _) = get(server.addr, client_config(&ca, None), "/livez", "").await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn client_certificate_becomes_identity() {
+    let ca = Ca::new();
+    let server = start(&ca, "server", true, Some("secret")).await;
+    let client = ca.issue("svc-a", ExtendedKeyUsagePurpose::ClientAuth);
+
+    let (status, body, _) = get(
+        server.addr,
+        client_config(&ca, Some(client)),
+        "/v1/usage",
+        "",
+    )
+    .await;
+    assert_eq!(status, 200, "{body}");
+    let body: Value = ok(serde_json::from_str(&body));
+    assert_eq!(body["key"], "svc-a");
+
+    // Without a certificate the API key is still required.
+    let (status, _, _) = get(server.addr, client_config(&ca, None), "/v1/usage", "").await;
+    assert_eq!(status, 401);
+    let (status, _, _) = get(
+        server.addr,
+        client_config(&ca, None),
+        "/v1/usage",
+        "Authorization: Bearer secret\r\n",
+    )
+    .await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn untrusted_client_certificates_are_rejected() {
+    let ca = Ca::new();
+    let server = start(&ca, "server", true, None).await;
+    let other_ca = Ca::new();
+    let client = other_ca.issue("intruder", ExtendedKeyUsagePurpose::ClientAuth);
+
+    let connector = TlsConnector::from(Arc::new(client_config(&ca, Some(client))));
+    let tcp = ok(TcpStream::connect(server.addr).await);
+    let name = ok(ServerName::try_from("localhost"));
+    // TLS 1.3 reports client certificate rejection on first read.
+    let result = match connector.connect(name, tcp).await {
+        Ok(mut stream) => {
+            let write = stream
+                .write_all(b"GET /livez HTTP/1.1\r\nHost: x\r\n\r\n")
+                .await;
+            let mut buffer = [0; 64];
+            match write {
+                Ok(()) => stream.read(&mut buffer).await.map(|n| n > 0),
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    };
+    assert!(!matches!(result, Ok(true)), "untrusted client was served");
+}
@@ -0,0 +1,101 @@
+//! Tests for per-key usage quotas and the usage endpoint.
+
+#![allow(clippy::panic, clippy::manual_let_else)]
+
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{app_with, check, request, send_json_to};
+use grammar_api::QuotaLimits;
+use serde_json::Value;
+use std::time::Duration;
+
+async fn usage(app: &Router) -> (StatusCode, Value) {
+    get(app, "/v1/usage").await
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    match send_json_to(app.clone(), request("GET", uri, None)).await {
+        Ok(response) => response,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[tokio::test]
+async fn usage_reports_requests_and_characters() {
+    let app = app_with(|config| {
+        config.quota.default = QuotaLimits {
+            daily_requests: Some(100),
+            ..QuotaLimits::default()
+        };
+    });
+
+    check(&app, "Hello there.").await;
+    check(&app, "Hi.").await;
+
+    let (status, body) = usage(&app).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["key"], "anonymous");
+    assert_eq!(body["daily"]["requests"], 2);
+    assert_eq!(body["daily"]["characters"], 15);
+    assert_eq!(body["daily"]["requestLimit"], 100);
+    assert_eq!(body["monthly"]["requests"], 2);
+    assert!(body["monthly"]["characterLimit"].is_null());
+}
+
+#[tokio::test]
+async fn rejects_requests_over_quota() {
+    let app = app_with(|config| {
+        config.quota.default = QuotaLimits {
+            daily_characters: Some(20),
+            ..QuotaLimits::default()
+        };
+    });
+
+    let (status, _) = check(&app, "Hello there.").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = check(&app, "Hello again.").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "QUOTA_EXCEEDED");
+    assert_eq!(body["limit"], 20);
+
+    let (_, body) = usage(&app).await;
+    assert_eq!(body["daily"]["requests"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn overloaded_checks_do_not_count() {
+    let app = app_with(|config| {
+        config.lint.max_concurrency = 1;
+        config.lint.queue_depth = 0;
+        config.lint.timeout_ms = 60_000;
+    });
+
+    // Keep the only lint worker busy.
+    let text = "Thsi sentense has typos in it. ".repeat(300);
+    let busy = app.clone();
+    let long_check = tokio::spawn(async move { check(&busy, &text).await });
+    let mut saturated = false;
+    for _ in 0..500 {
+        if get(&app, "/readyz?verbose").await.0 == StatusCode::SERVICE_UNAVAILABLE {
+            saturated = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+    assert!(saturated, "The long check never occupied the worker");
+
+    let (status, body) = check(&app, "Hello there.").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "Got: {body}");
+    assert_eq!(body["code"], "OVERLOADED");
+
+    let (status, _) = match long_check.await {
+        Ok(response) => response,
+        Err(e) => panic!("{e}"),
+    };
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = usage(&app).await;
+    assert_eq!(body["daily"]["requests"], 1);
+    assert_eq!(body["daily"]["characters"], 300 * 31);
+}